@@ -20,9 +20,11 @@ echo "Initializing project: $DISPLAY_NAME ($APP_NAME)"
 sed -i "s/name = \"tauri-terminal-template\"/name = \"$APP_NAME\"/" "$PROJECT_DIR/src-tauri/Cargo.toml"
 sed -i "s/name = \"tauri_terminal_template_lib\"/name = \"${APP_NAME//-/_}_lib\"/" "$PROJECT_DIR/src-tauri/Cargo.toml"
 sed -i "s/description = \"A terminal template built with Tauri\"/description = \"$DISPLAY_NAME\"/" "$PROJECT_DIR/src-tauri/Cargo.toml"
+sed -i "s/default-run = \"tauri-terminal-template\"/default-run = \"$APP_NAME\"/" "$PROJECT_DIR/src-tauri/Cargo.toml"
 
-# Update main.rs
+# Update main.rs and the cli binary
 sed -i "s/tauri_terminal_template_lib/${APP_NAME//-/_}_lib/" "$PROJECT_DIR/src-tauri/src/main.rs"
+sed -i "s/tauri_terminal_template_lib/${APP_NAME//-/_}_lib/" "$PROJECT_DIR/src-tauri/src/bin/cli.rs"
 
 # Update tauri.conf.json
 sed -i "s/\"tauri-terminal-template\"/\"$APP_NAME\"/" "$PROJECT_DIR/src-tauri/tauri.conf.json"
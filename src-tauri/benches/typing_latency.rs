@@ -0,0 +1,123 @@
+//! Stress benchmark: keystroke latency on one terminal while other commands
+//! hammer the backend.
+//!
+//! Run with `cargo bench --bench typing_latency`. Set `BENCH_REPO` to point
+//! the git/directory load at a larger repository than this one.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri_terminal_template_lib::api::{self, AppState, NullSink, SessionKind, SpawnOptions};
+
+const KEYSTROKES: usize = 2000;
+const LOAD_THREADS: usize = 4;
+
+fn main() {
+    let repo = std::env::var("BENCH_REPO")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(".."));
+    let state = api::create_state();
+
+    let typing = spawn_sink(&state);
+    let other = spawn_sink(&state);
+    thread::sleep(Duration::from_millis(500));
+
+    let idle = measure(&state, &typing);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut load = Vec::new();
+    for i in 0..LOAD_THREADS {
+        let state = state.clone();
+        let repo = repo.clone();
+        let other = other.clone();
+        let stop = stop.clone();
+        load.push(thread::spawn(move || {
+            let mut ops = 0usize;
+            while !stop.load(Ordering::Relaxed) {
+                match i % 3 {
+                    0 => {
+                        // Force a real `git diff` every time
+                        state.git_cache.invalidate(&repo.canonicalize().unwrap_or(repo.clone()));
+                        let _ = api::git_stats(&state, &repo);
+                    }
+                    1 => {
+                        let _ = api::read_directory_recursive(
+                            Some(repo.to_string_lossy().to_string()),
+                            None,
+                            None,
+                            None,
+                            None,
+                        );
+                    }
+                    _ => {
+                        let _ = api::terminal_cwd(&state, &other);
+                        let _ = api::write_session(&state, &other, "x");
+                    }
+                }
+                ops += 1;
+            }
+            ops
+        }));
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    let loaded = measure(&state, &typing);
+    stop.store(true, Ordering::Relaxed);
+    let ops: usize = load.into_iter().map(|h| h.join().unwrap_or(0)).sum();
+
+    let _ = api::close_session(&state, &typing);
+    let _ = api::close_session(&state, &other);
+
+    println!("write_to_terminal latency over {} keystrokes", KEYSTROKES);
+    println!("{:>8} {:>10} {:>10} {:>10}", "", "p50", "p99", "max");
+    report("idle", &idle);
+    report("loaded", &loaded);
+    println!("background operations completed: {}", ops);
+
+    let (idle_p99, loaded_p99) = (percentile(&idle, 0.99), percentile(&loaded, 0.99));
+    let budget = (idle_p99 * 10).max(Duration::from_millis(5));
+    if loaded_p99 > budget {
+        eprintln!("loaded p99 {:?} exceeds budget {:?}", loaded_p99, budget);
+        std::process::exit(1);
+    }
+}
+
+/// Start a session whose process discards its input.
+fn spawn_sink(state: &AppState) -> String {
+    let opts = SpawnOptions {
+        command: Some("cat > /dev/null".to_string()),
+        ..SpawnOptions::default()
+    };
+    api::start_session(state, &opts, SessionKind::Interactive, Arc::new(NullSink))
+        .expect("failed to spawn benchmark session")
+        .session_id
+}
+
+fn measure(state: &AppState, session_id: &str) -> Vec<Duration> {
+    let mut samples = Vec::with_capacity(KEYSTROKES);
+    for _ in 0..KEYSTROKES {
+        let start = Instant::now();
+        api::write_session(state, session_id, "a").expect("write failed");
+        samples.push(start.elapsed());
+        thread::sleep(Duration::from_micros(500));
+    }
+    samples.sort();
+    samples
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let idx = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+    sorted[idx]
+}
+
+fn report(label: &str, sorted: &[Duration]) {
+    println!(
+        "{:>8} {:>10?} {:>10?} {:>10?}",
+        label,
+        percentile(sorted, 0.5),
+        percentile(sorted, 0.99),
+        sorted[sorted.len() - 1],
+    );
+}
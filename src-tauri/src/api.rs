@@ -0,0 +1,67 @@
+//! Plain Rust API over the backend, free of Tauri types.
+//!
+//! The `#[tauri::command]` functions registered in `lib.rs` are thin wrappers
+//! around these. The `cli` binary and the benchmarks use this module directly,
+//! so every capability can be scripted without a webview:
+//!
+//! ```no_run
+//! use tauri_terminal_template_lib::api;
+//!
+//! let state = api::create_state();
+//! let stats = api::git_stats(&state, std::path::Path::new(".")).unwrap();
+//! println!("{} changed files", stats.len());
+//! ```
+
+pub use crate::error::{AppError, AppResult, ErrorCode};
+pub use crate::events::{EventSink, NullSink, SharedSink};
+pub use crate::logging::{init as init_logging, recent_logs, set_event_sink as set_log_sink, LogEntry};
+pub use crate::state::{create_state, AppState, PtySession};
+
+pub use crate::pty::manager::{
+    close_session, command_history, resize_session, search_terminal, session_exit_code, spawn_pty,
+    start_session, terminal_lines, terminal_screen, write_session, Isolation, SessionKind,
+    SpawnOptions, SpawnedSession,
+};
+pub use crate::pty::container::{
+    container_command, delete_profile as delete_container_profile,
+    load_profiles as load_container_profiles, save_profile as save_container_profile,
+    ContainerBuild, ContainerProfile, ContainerRuntime,
+};
+pub use crate::pty::devcontainer::{
+    inspect as inspect_devcontainer, load_config as load_devcontainer_config,
+    profile_for as devcontainer_profile, DevcontainerConfig, DevcontainerReport,
+};
+pub use crate::pty::expect::{send_and_expect, wait_for_output, ExpectMatch};
+pub use crate::pty::groups::{
+    add_members as add_to_group, create_group, delete_group, list_groups,
+    remove_members as remove_from_group, write_to_group, GroupWriteResult, GroupWriteStatus,
+    TerminalGroup,
+};
+pub use crate::pty::metrics::{
+    aggregate_metrics, terminal_metrics, AggregateMetrics, HistogramBucket, LatencySnapshot,
+    TerminalMetrics,
+};
+pub use crate::pty::paste::{paste_to_session, PasteResult};
+pub use crate::pty::snippets::{
+    delete_snippet, load_snippets, run_snippet, save_snippet, Snippet, SnippetRun, SnippetStep,
+};
+pub use crate::pty::ssh::{
+    delete_profile as delete_ssh_profile, load_profiles as load_ssh_profiles,
+    save_profile as save_ssh_profile, start_profile_session as start_ssh_profile_session,
+    start_ssh_session, ConnectionState, SshProfile,
+};
+pub use crate::pty::screen::{ScreenMatch, ScreenSnapshot, TerminalLines};
+pub use crate::pty::cwd_watcher::{subscribe as subscribe_cwd, unsubscribe as unsubscribe_cwd};
+pub use crate::pty::shell_integration::{CommandRecord, ShellState};
+
+pub use crate::fs::{
+    check_command_exists, Access, AccessPolicy, PROTECTED_PATHS, SECRET_PATHS, copy_path, create_directory, create_file, delete_path, move_path, rename_path, trash,
+    write_file, AffectedPaths, get_current_branch, get_git_diff, git_diff_stats, git_stats,
+    read_directory, read_directory_children, read_directory_recursive, read_content, read_file_content, terminal_cwd, unwatch_tree as unwatch_directory, watch_tree as watch_directory,
+    DirectoryEntry, FileMatch, FindResult, find_files,
+    apply_replace, replace_in_files, undo_replace, FileReplacement, ReplaceApplied, ReplaceOptions, ReplacePreview,
+    cancel_search, start_search, FileMatches, LineMatch, SearchOptions, SearchSummary, Searcher,
+    DirectoryPage, EntryMetadata, FileContent, FileKind, ReadRange, TextEncoding, FsChangeBatch, MetadataField, RenamedPath, GitDiffResult, GitStats, RecursiveDirectoryEntry, TreeEntry,
+};
+
+pub use crate::pty::commands::git_command as run_git_command;
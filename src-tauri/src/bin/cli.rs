@@ -0,0 +1,489 @@
+//! Headless entry point exposing the backend operations for scripting.
+//!
+//! Pass `--verbose` to print backend log records to stderr.
+//!
+//! ```text
+//! cli [--json] git-stats <dir>
+//! cli [--json] tree <dir> [--max-depth N] [--max-files N] [--ignored] [--fields F,...]
+//! cli [--json] children <dir> [--limit N] [--cursor C] [--ignored] [--fields F,...]
+//! cli [--json] ls <dir> [--fields F,...]
+//! cli [--json] read <file> [--head N | --tail N | --offset N [--length N]] [--max-bytes N]
+//! cli [--json] find <dir> <query>... [--limit N]
+//! cli [--json] grep <dir> <pattern> [--regex] [--case] [--word] [--include G,...] [--exclude G,...] [--max-per-file N] [--max-results N]
+//! cli [--json] replace <dir> <pattern> <replacement> [--regex] [--case] [--word] [--include G,...] [--exclude G,...] [--max-files N] [--apply]
+//! cli watch <dir>
+//! cli branch <dir>
+//! cli spawn [--sandbox] [--no-net] [--dir DIR] [-i] -- <command> [args...]
+//! ```
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::sync::Arc;
+use serde::Serialize;
+use tauri_terminal_template_lib::api;
+
+const USAGE: &str = "usage (global flags: --json, --verbose):
+  cli [--json] git-stats <dir>
+  cli [--json] tree <dir> [--max-depth N] [--max-files N] [--ignored] [--fields F,...]
+  cli [--json] children <dir> [--limit N] [--cursor C] [--ignored] [--fields F,...]
+  cli [--json] ls <dir> [--fields F,...]
+  cli [--json] read <file> [--head N | --tail N | --offset N [--length N]] [--max-bytes N]
+  cli [--json] find <dir> <query>... [--limit N]
+  cli [--json] grep <dir> <pattern> [--regex] [--case] [--word] [--include G,...] [--exclude G,...] [--max-per-file N] [--max-results N]
+  cli [--json] replace <dir> <pattern> <replacement> [--regex] [--case] [--word] [--include G,...] [--exclude G,...] [--max-files N] [--apply]
+  cli watch <dir>
+  cli branch <dir>
+  cli spawn [--sandbox] [--no-net] [--dir DIR] [-i] -- <command> [args...]";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let json = take_flag(&mut args, "--json");
+    if take_flag(&mut args, "--verbose") {
+        api::init_logging(None);
+    }
+
+    let result = match args.first().map(String::as_str) {
+        Some("git-stats") => git_stats(&args[1..], json),
+        Some("tree") => tree(&args[1..], json),
+        Some("children") => children(&args[1..], json),
+        Some("ls") => ls(&args[1..], json),
+        Some("read") => read(&args[1..], json),
+        Some("find") => find(&args[1..], json),
+        Some("grep") => grep(&args[1..], json),
+        Some("replace") => replace(&args[1..], json),
+        Some("branch") => branch(&args[1..]),
+        Some("watch") => watch(&args[1..]),
+        Some("spawn") => return spawn(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Position of `name` among the arguments before `--`, so flags meant for a
+/// spawned command are never consumed.
+fn find_arg(args: &[String], name: &str) -> Option<usize> {
+    args.iter().take_while(|a| *a != "--").position(|a| a == name)
+}
+
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match find_arg(args, flag) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match find_arg(args, name) {
+        Some(i) if i + 1 < args.len() => {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("{} requires a value", name)),
+        None => Ok(None),
+    }
+}
+
+fn take_number(args: &mut Vec<String>, name: &str) -> Result<Option<usize>, String> {
+    take_option(args, name)?
+        .map(|v| v.parse().map_err(|_| format!("{} expects a number, got {}", name, v)))
+        .transpose()
+}
+
+/// `--fields size,modified,...` as metadata fields for the listings.
+fn take_fields(args: &mut Vec<String>) -> Result<Option<Vec<api::MetadataField>>, String> {
+    take_option(args, "--fields")?
+        .map(|list| {
+            list.split(',')
+                .map(|name| {
+                    serde_json::from_value(serde_json::Value::String(name.trim().to_string()))
+                        .map_err(|_| format!("unknown field {}", name))
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+fn dir_arg(args: &[String]) -> Result<String, String> {
+    match args {
+        [dir] => Ok(dir.clone()),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+    let out = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    println!("{}", out);
+    Ok(())
+}
+
+fn git_stats(args: &[String], json: bool) -> Result<(), String> {
+    let dir = dir_arg(args)?;
+    let stats = api::git_diff_stats(Path::new(&dir))?;
+    if json {
+        return print_json(&stats);
+    }
+
+    let mut paths: Vec<_> = stats.keys().collect();
+    paths.sort();
+    for path in paths {
+        let s = &stats[path];
+        let status = s.status.as_deref().unwrap_or("modified");
+        println!("+{}\t-{}\t{}\t{}", s.added, s.deleted, status, path);
+    }
+    Ok(())
+}
+
+fn tree(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let max_depth = take_number(&mut args, "--max-depth")?;
+    let max_files = take_number(&mut args, "--max-files")?;
+    let show_ignored = take_flag(&mut args, "--ignored");
+    let fields = take_fields(&mut args)?;
+    let dir = dir_arg(&args)?;
+
+    let entries = api::read_directory_recursive(Some(dir), max_depth, max_files, Some(show_ignored), fields)?;
+    if json {
+        return print_json(&entries);
+    }
+
+    for entry in entries {
+        let indent = "  ".repeat(entry.depth.saturating_sub(1));
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let marker = if entry.ignored { "  (ignored)" } else { "" };
+        println!("{}{}{}{}", indent, entry.name, suffix, marker);
+    }
+    Ok(())
+}
+
+fn children(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let limit = take_number(&mut args, "--limit")?;
+    let cursor = take_option(&mut args, "--cursor")?;
+    let show_ignored = take_flag(&mut args, "--ignored");
+    let fields = take_fields(&mut args)?;
+    let dir = dir_arg(&args)?;
+
+    let page = api::read_directory_children(dir, cursor, limit, Some(show_ignored), fields)?;
+    if json {
+        return print_json(&page);
+    }
+
+    for entry in &page.entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let marker = if entry.ignored { "  (ignored)" } else { "" };
+        println!("{}{}{}", entry.name, suffix, marker);
+    }
+    println!(
+        "-- {} of {} ({} dirs, {} files, {} ignored)",
+        page.entries.len(),
+        page.total,
+        page.total_dirs,
+        page.total_files,
+        page.ignored_count
+    );
+    if let Some(cursor) = &page.next_cursor {
+        println!("-- next: --cursor {}", cursor);
+    }
+    Ok(())
+}
+
+fn ls(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let fields = take_fields(&mut args)?;
+    let dir = dir_arg(&args)?;
+    let entries = api::read_directory(Some(dir), fields)?;
+    if json {
+        return print_json(&entries);
+    }
+
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        println!("{}{}", entry.name, suffix);
+    }
+    Ok(())
+}
+
+fn read(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let head = take_number(&mut args, "--head")?;
+    let tail = take_number(&mut args, "--tail")?;
+    let offset = take_number(&mut args, "--offset")?.map(|n| n as u64);
+    let length = take_number(&mut args, "--length")?.map(|n| n as u64);
+    let max_bytes = take_number(&mut args, "--max-bytes")?.map(|n| n as u64);
+    let file = dir_arg(&args)?;
+
+    let content = api::read_file_content(file, offset, length, head, tail, max_bytes)?;
+    if json {
+        return print_json(&content);
+    }
+    if content.is_binary {
+        return Err(format!("{}: binary file ({} bytes)", content.path, content.size));
+    }
+    print!("{}", content.content);
+    if content.truncated {
+        eprintln!("-- truncated: showing {} of {} bytes from offset {}", content.length, content.size, content.offset);
+    }
+    Ok(())
+}
+
+fn find(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let limit = take_number(&mut args, "--limit")?;
+    let (dir, query) = match args.split_first() {
+        Some((dir, query)) if !query.is_empty() => (dir, query.join(" ")),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let state = api::create_state();
+    let result = api::find_files(&state, Path::new(dir), &query, limit, None)?;
+    if json {
+        return print_json(&result);
+    }
+    for m in &result.matches {
+        println!("{}", m.relative_path);
+    }
+    if result.truncated {
+        eprintln!("-- only the first {} files were searched", result.indexed);
+    }
+    Ok(())
+}
+
+/// Comma-separated globs for `--include` and `--exclude`.
+fn take_globs(args: &mut Vec<String>, name: &str) -> Result<Vec<String>, String> {
+    Ok(take_option(args, name)?
+        .map(|list| list.split(',').map(|g| g.trim().to_string()).filter(|g| !g.is_empty()).collect())
+        .unwrap_or_default())
+}
+
+/// Print matching lines as `path:line:column:preview`, or one JSON object
+/// per file with `--json`, as each file is searched.
+fn grep(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let options = api::SearchOptions {
+        regex: take_flag(&mut args, "--regex"),
+        case_sensitive: take_flag(&mut args, "--case"),
+        whole_word: take_flag(&mut args, "--word"),
+        include: take_globs(&mut args, "--include")?,
+        exclude: take_globs(&mut args, "--exclude")?,
+        max_matches_per_file: take_number(&mut args, "--max-per-file")?,
+        max_results: take_number(&mut args, "--max-results")?,
+    };
+    let [dir, pattern] = args.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let searcher = api::Searcher::new(Path::new(dir), pattern, options)?;
+    let summary = searcher.run(&AtomicBool::new(false), |file| {
+        let mut stdout = std::io::stdout().lock();
+        if json {
+            let _ = writeln!(stdout, "{}", serde_json::to_string(&file).unwrap_or_default());
+            return;
+        }
+        for m in &file.matches {
+            let _ = writeln!(stdout, "{}:{}:{}:{}", file.relative_path, m.line, m.column, m.preview);
+        }
+    });
+    eprintln!(
+        "-- {} matches in {} of {} files{}",
+        summary.matches,
+        summary.files_matched,
+        summary.files_searched,
+        if summary.limit_hit { " (limit reached)" } else { "" }
+    );
+    Ok(())
+}
+
+/// Preview a replace, listing the files it changes, and write them with
+/// `--apply`.
+fn replace(args: &[String], json: bool) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let apply = take_flag(&mut args, "--apply");
+    let options = api::ReplaceOptions {
+        regex: take_flag(&mut args, "--regex"),
+        case_sensitive: take_flag(&mut args, "--case"),
+        whole_word: take_flag(&mut args, "--word"),
+        include: take_globs(&mut args, "--include")?,
+        exclude: take_globs(&mut args, "--exclude")?,
+        max_files: take_number(&mut args, "--max-files")?,
+    };
+    let [dir, pattern, replacement] = args.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+
+    let state = api::create_state();
+    let searcher = api::Searcher::new(Path::new(dir), pattern, options.search_options())?;
+    let preview = api::replace_in_files(&state, &searcher, replacement, options.max_files)?;
+    if json && !apply {
+        return print_json(&preview);
+    }
+    if !json {
+        for file in &preview.files {
+            println!("{}: {} replacements", file.relative_path, file.replacements);
+        }
+    }
+    if preview.truncated {
+        eprintln!("-- more files match; only the first {} are replaced", preview.files.len());
+    }
+    if !apply {
+        eprintln!("-- {} replacements in {} files; pass --apply to write them", preview.replacements, preview.files.len());
+        return Ok(());
+    }
+    if preview.files.is_empty() {
+        return Ok(());
+    }
+    let applied = api::apply_replace(&state, &preview.preview_id, None, None)?;
+    if json {
+        return print_json(&applied);
+    }
+    eprintln!("-- wrote {} replacements in {} files", applied.replacements, applied.affected.modified.len());
+    Ok(())
+}
+
+fn branch(args: &[String]) -> Result<(), String> {
+    let dir = dir_arg(args)?;
+    match api::get_current_branch(dir)? {
+        Some(branch) => println!("{}", branch),
+        None => return Err("No branch (not a git repository or detached HEAD)".to_string()),
+    }
+    Ok(())
+}
+
+/// Print each `fs-changed` batch for a directory as a line of JSON until
+/// interrupted.
+fn watch(args: &[String]) -> Result<(), String> {
+    let dir = dir_arg(args)?;
+    let state = api::create_state();
+    let sink: api::SharedSink = Arc::new(|event: &str, payload: serde_json::Value| {
+        if event == "fs-changed" {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{}", payload);
+            let _ = stdout.flush();
+        }
+    });
+    api::watch_directory(&state, Path::new(&dir), sink)?;
+    loop {
+        std::thread::park();
+    }
+}
+
+/// Run a command under a PTY, streaming its output to stdout, and exit with
+/// the command's exit code.
+fn spawn(args: &[String]) -> ExitCode {
+    let (mut opts_args, command) = match args.iter().position(|a| a == "--") {
+        Some(i) => (args[..i].to_vec(), args[i + 1..].to_vec()),
+        None => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if command.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let sandbox = take_flag(&mut opts_args, "--sandbox");
+    let sandbox_no_net = take_flag(&mut opts_args, "--no-net");
+    let forward_stdin = take_flag(&mut opts_args, "-i");
+    let project_dir = match take_option(&mut opts_args, "--dir") {
+        Ok(dir) => dir.or_else(|| {
+            std::env::current_dir()
+                .ok()
+                .map(|d| d.to_string_lossy().to_string())
+        }),
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if !opts_args.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let state = api::create_state();
+    let (done_tx, done_rx) = mpsc::channel::<Option<u32>>();
+    let sink: api::SharedSink = Arc::new(move |event: &str, payload: serde_json::Value| {
+        match event {
+            "hidden-terminal-output" => {
+                if let Some(data) = payload["data"].as_str() {
+                    let mut stdout = std::io::stdout().lock();
+                    let _ = stdout.write_all(data.as_bytes());
+                    let _ = stdout.flush();
+                }
+            }
+            "hidden-terminal-closed" => {
+                let code = payload["exit_code"].as_u64().map(|c| c as u32);
+                let _ = done_tx.send(code);
+            }
+            _ => {}
+        }
+    });
+
+    let opts = api::SpawnOptions {
+        sandbox,
+        sandbox_no_net,
+        project_dir,
+        command: Some(shell_join(&command)),
+        ..api::SpawnOptions::default()
+    };
+    let spawned = match api::start_session(&state, &opts, api::SessionKind::Hidden, sink) {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    if sandbox && !spawned.sandboxed {
+        eprintln!("warning: sandbox unavailable, running unsandboxed");
+    }
+
+    if forward_stdin {
+        let state = state.clone();
+        let session_id = spawned.session_id.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if api::write_session(&state, &session_id, &format!("{}\n", line)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    match done_rx.recv() {
+        Ok(Some(code)) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// Quote arguments for the `-lc` string so they reach the command unchanged.
+fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|a| {
+            if !a.is_empty() && a.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c)) {
+                a.clone()
+            } else {
+                format!("'{}'", a.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,36 @@
+//! Small JSON lists persisted in the app config dir (snippets, SSH profiles).
+
+use std::fs;
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::error::{AppError, AppResult, ErrorCode};
+
+/// Items in `config_dir/file`, or an empty list if the file doesn't exist.
+/// `what` names the items in error messages.
+pub fn load<T: DeserializeOwned>(config_dir: &Path, file: &str, what: &str) -> AppResult<Vec<T>> {
+    let path = config_dir.join(file);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AppError::io(&format!("Failed to read {}", what), &e).with_path(&path)),
+    };
+    serde_json::from_str(&content).map_err(|e| {
+        AppError::new(ErrorCode::InvalidInput, format!("Invalid {} file: {}", what, e)).with_path(&path)
+    })
+}
+
+/// Replace `config_dir/file` with `items`.
+pub fn store<T: Serialize>(config_dir: &Path, file: &str, what: &str, items: &[T]) -> AppResult<()> {
+    fs::create_dir_all(config_dir)
+        .map_err(|e| AppError::io("Failed to create config dir", &e).with_path(config_dir))?;
+    let path = config_dir.join(file);
+    let json = serde_json::to_string_pretty(items)
+        .map_err(|e| AppError::new(ErrorCode::Io, format!("Failed to serialize {}: {}", what, e)))?;
+
+    // Write then rename so a crash never leaves a truncated file
+    let tmp = path.with_extension("json.tmp");
+    let context = format!("Failed to write {}", what);
+    fs::write(&tmp, json).map_err(|e| AppError::io(&context, &e).with_path(&tmp))?;
+    fs::rename(&tmp, &path).map_err(|e| AppError::io(&context, &e).with_path(&path))
+}
@@ -0,0 +1,103 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Machine-readable error category, serialized as `snake_case` so the
+/// frontend can branch on `error.code` instead of matching message text.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    SessionNotFound,
+    NotFound,
+    AlreadyExists,
+    /// The target changed since it was last read
+    Conflict,
+    PermissionDenied,
+    NotAGitRepository,
+    InvalidInput,
+    Pty,
+    Git,
+    Io,
+    Lock,
+    Unsupported,
+    Timeout,
+}
+
+/// Error returned by backend commands.
+///
+/// Serializes as `{ code, message, path?, session_id? }`. `message` keeps the
+/// human-readable text the commands always returned.
+#[derive(Serialize, Clone, Debug)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            path: None,
+            session_id: None,
+        }
+    }
+
+    pub fn session_not_found(session_id: &str) -> Self {
+        Self::new(ErrorCode::SessionNotFound, format!("Session not found: {}", session_id))
+            .with_session(session_id)
+    }
+
+    pub fn not_a_git_repository(path: impl AsRef<std::path::Path>) -> Self {
+        Self::new(ErrorCode::NotAGitRepository, "Not a git repository").with_path(path)
+    }
+
+    /// Wrap an I/O error as `"<context>: <error>"`, keeping not-found,
+    /// already-exists and permission failures distinguishable.
+    pub fn io(context: &str, err: &std::io::Error) -> Self {
+        let code = match err.kind() {
+            std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            std::io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+            std::io::ErrorKind::AlreadyExists => ErrorCode::AlreadyExists,
+            _ => ErrorCode::Io,
+        };
+        Self::new(code, format!("{}: {}", context, err))
+    }
+
+    pub fn pty(context: &str, err: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Pty, format!("{}: {}", context, err))
+    }
+
+    pub fn lock(err: impl fmt::Display) -> Self {
+        Self::new(ErrorCode::Lock, format!("Failed to lock session: {}", err))
+    }
+
+    pub fn with_path(mut self, path: impl AsRef<std::path::Path>) -> Self {
+        self.path = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    pub fn with_session(mut self, session_id: &str) -> Self {
+        self.session_id = Some(session_id.to_string());
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<AppError> for String {
+    fn from(err: AppError) -> Self {
+        err.message
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+/// Destination for backend events.
+///
+/// The Tauri app forwards events to the webview through `AppHandle::emit`;
+/// headless callers (the `cli` binary, benchmarks) supply their own sink so
+/// the same reader threads and watchers work without a window.
+pub trait EventSink: Send + Sync {
+    fn emit_event(&self, event: &str, payload: serde_json::Value);
+}
+
+pub type SharedSink = Arc<dyn EventSink>;
+
+impl<R: tauri::Runtime> EventSink for tauri::AppHandle<R> {
+    fn emit_event(&self, event: &str, payload: serde_json::Value) {
+        use tauri::Emitter;
+        let _ = self.emit(event, payload);
+    }
+}
+
+/// Sink that drops every event, for callers that only need return values.
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn emit_event(&self, _event: &str, _payload: serde_json::Value) {}
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(&str, serde_json::Value) + Send + Sync,
+{
+    fn emit_event(&self, event: &str, payload: serde_json::Value) {
+        self(event, payload)
+    }
+}
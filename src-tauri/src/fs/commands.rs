@@ -1,4 +1,24 @@
+//! Tauri commands for the filesystem. Each checks its paths against the
+//! [`AccessPolicy`] before calling the plain function of the same job, which
+//! the `api` module and the `cli` binary use without the policy.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::content::{self, FileContent};
+use crate::fs::directory::{self, DirectoryEntry, RecursiveDirectoryEntry};
+use crate::fs::finder::{self, FindResult};
+use crate::fs::git::{self, GitDiffResult, GitStats};
+use crate::fs::metadata::MetadataField;
+use crate::fs::ops::{self, AffectedPaths};
+use crate::fs::policy::{Access, AccessPolicy};
+use crate::fs::replace::{self, ReplaceApplied, ReplaceOptions, ReplacePreview};
+use crate::fs::search::{self, SearchOptions, Searcher};
+use crate::fs::tree::{self, DirectoryPage};
+use crate::fs::watch;
+use crate::state::AppState;
 
 #[tauri::command]
 pub fn check_command_exists(command: String) -> Result<bool, String> {
@@ -15,3 +35,359 @@ pub fn check_command_exists(command: String) -> Result<bool, String> {
 
     Ok(output.status.success())
 }
+
+/// A path the policy allowed.
+///
+/// The operation runs on `resolved`, so a symlink swapped in after the check
+/// can't send it somewhere else. Paths in the result are mapped back under
+/// the prefix the frontend passed, which is how its tree knows them.
+struct Allowed {
+    resolved: PathBuf,
+    /// Leading part of `resolved` that differs from the path as passed...
+    from: PathBuf,
+    /// ...and what it was in the path as passed
+    to: PathBuf,
+}
+
+impl Allowed {
+    fn check(policy: &AccessPolicy, path: &Path, access: Access) -> AppResult<Self> {
+        let resolved = policy.check(path, access)?;
+        // Drop the components both paths end with
+        let (mut from, mut to) = (resolved.as_path(), path);
+        while let (Some(a), Some(b)) = (from.file_name(), to.file_name()) {
+            match (from.parent(), to.parent()) {
+                (Some(from_parent), Some(to_parent)) if a == b => {
+                    from = from_parent;
+                    to = to_parent;
+                }
+                _ => break,
+            }
+        }
+        Ok(Self {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            resolved,
+        })
+    }
+
+    /// `path` from the operation's result, as the frontend would name it.
+    fn report(&self, path: &str) -> Option<String> {
+        let rest = Path::new(path).strip_prefix(&self.from).ok()?;
+        let mapped = if rest.as_os_str().is_empty() { self.to.clone() } else { self.to.join(rest) };
+        Some(mapped.to_string_lossy().to_string())
+    }
+}
+
+/// Map `path` with the first of `allowed` it falls under.
+fn report(path: &str, allowed: &[&Allowed]) -> String {
+    allowed
+        .iter()
+        .find_map(|a| a.report(path))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// `path`, or the process's cwd when the frontend didn't pass one.
+fn path_or_cwd(path: Option<String>) -> AppResult<PathBuf> {
+    match path {
+        Some(p) => Ok(PathBuf::from(p)),
+        None => std::env::current_dir().map_err(|e| AppError::io("Failed to get current directory", &e)),
+    }
+}
+
+#[tauri::command]
+pub fn read_directory(
+    path: Option<String>,
+    fields: Option<Vec<MetadataField>>,
+    state: tauri::State<AppState>,
+) -> AppResult<Vec<DirectoryEntry>> {
+    let dir = path_or_cwd(path)?;
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), &dir, Access::Read)?;
+    let mut entries = directory::read_directory(Some(allowed.resolved.to_string_lossy().to_string()), fields)?;
+    for entry in &mut entries {
+        entry.path = report(&entry.path, &[&allowed]);
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn read_directory_recursive(
+    path: Option<String>,
+    max_depth: Option<usize>,
+    max_files: Option<usize>,
+    show_ignored: Option<bool>,
+    fields: Option<Vec<MetadataField>>,
+    state: tauri::State<AppState>,
+) -> AppResult<Vec<RecursiveDirectoryEntry>> {
+    let dir = path_or_cwd(path)?;
+    let policy = AccessPolicy::from_state(&state);
+    let allowed = Allowed::check(&policy, &dir, Access::Read)?;
+    let mut entries = directory::read_directory_recursive(
+        Some(allowed.resolved.to_string_lossy().to_string()),
+        max_depth,
+        max_files,
+        show_ignored,
+        fields,
+    )?;
+    // Protected directories are listed, but not what's inside them
+    entries.retain(|entry| !policy.is_denied(Path::new(&entry.parent_path)));
+    for entry in &mut entries {
+        entry.path = report(&entry.path, &[&allowed]);
+        entry.parent_path = report(&entry.parent_path, &[&allowed]);
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn read_directory_children(
+    path: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    show_ignored: Option<bool>,
+    fields: Option<Vec<MetadataField>>,
+    state: tauri::State<AppState>,
+) -> AppResult<DirectoryPage> {
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), Path::new(&path), Access::Read)?;
+    let resolved = allowed.resolved.to_string_lossy().to_string();
+    let mut page = tree::read_directory_children(resolved, cursor, limit, show_ignored, fields)?;
+    page.path = path;
+    for entry in &mut page.entries {
+        entry.path = report(&entry.path, &[&allowed]);
+    }
+    Ok(page)
+}
+
+#[tauri::command]
+pub fn read_file_content(
+    path: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    max_bytes: Option<u64>,
+    state: tauri::State<AppState>,
+) -> AppResult<FileContent> {
+    let resolved = AccessPolicy::from_state(&state).check(Path::new(&path), Access::Read)?;
+    let mut content = content::read_file_content(resolved.to_string_lossy().to_string(), offset, length, head, tail, max_bytes)?;
+    content.path = path;
+    state.file_indexes.record_opened(&resolved);
+    Ok(content)
+}
+
+/// Fuzzy-find files under `root`; see `fs::finder`.
+#[tauri::command]
+pub async fn find_files(
+    root: String,
+    query: String,
+    limit: Option<usize>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<FindResult> {
+    let policy = AccessPolicy::from_state(&state);
+    let allowed = Allowed::check(&policy, Path::new(&root), Access::Read)?;
+    let state = state.inner().clone();
+    blocking(move || {
+        let mut result = finder::find_files(&state, &allowed.resolved, &query, limit, Some(&policy))?;
+        result.root = root;
+        for m in &mut result.matches {
+            m.path = report(&m.path, &[&allowed]);
+        }
+        Ok(result)
+    })
+    .await
+}
+
+/// Search file contents under `root`. Results stream as `search-result`
+/// events; returns the search id for them and for `cancel_search`.
+#[tauri::command]
+pub fn search_in_files(
+    root: String,
+    pattern: String,
+    options: Option<SearchOptions>,
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+) -> AppResult<String> {
+    let policy = AccessPolicy::from_state(&state);
+    policy.check(Path::new(&root), Access::Read)?;
+    let searcher = Searcher::new(Path::new(&root), &pattern, options.unwrap_or_default())?
+        .skip_paths(move |path| policy.is_denied(path));
+    search::start(&state, searcher, Arc::new(app))
+}
+
+#[tauri::command]
+pub fn cancel_search(search_id: String, state: tauri::State<AppState>) -> AppResult<()> {
+    search::cancel(&state, &search_id)
+}
+
+/// Preview replacing `pattern` with `replacement` in the files under `root`.
+/// Denied files are left out of the preview.
+#[tauri::command]
+pub async fn replace_in_files(
+    root: String,
+    pattern: String,
+    replacement: String,
+    options: Option<ReplaceOptions>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<ReplacePreview> {
+    let policy = AccessPolicy::from_state(&state);
+    policy.check(Path::new(&root), Access::Read)?;
+    let options = options.unwrap_or_default();
+    let state = state.inner().clone();
+    blocking(move || {
+        let searcher = Searcher::new(Path::new(&root), &pattern, options.search_options())?
+            .skip_paths(move |path| policy.is_denied(path));
+        replace::replace_in_files(&state, &searcher, &replacement, options.max_files)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn apply_replace(
+    preview_id: String,
+    files: Option<Vec<String>>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<ReplaceApplied> {
+    // Roots can close between the preview and the apply, so every file is
+    // checked again
+    let policy = AccessPolicy::from_state(&state);
+    let state = state.inner().clone();
+    blocking(move || replace::apply_replace(&state, &preview_id, files, Some(&policy))).await
+}
+
+#[tauri::command]
+pub async fn undo_replace(journal_id: String, state: tauri::State<'_, AppState>) -> AppResult<AffectedPaths> {
+    let policy = AccessPolicy::from_state(&state);
+    let state = state.inner().clone();
+    blocking(move || replace::undo_replace(&state, &journal_id, Some(&policy))).await
+}
+
+/// Run a file operation off the command thread; copies, trash moves and
+/// indexing of large trees take a while.
+async fn blocking<T, F>(op: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(op)
+        .await
+        .map_err(|e| AppError::new(ErrorCode::Io, format!("File operation failed: {}", e)))?
+}
+
+#[tauri::command]
+pub async fn write_file_content(path: String, content: String, state: tauri::State<'_, AppState>) -> AppResult<AffectedPaths> {
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), Path::new(&path), Access::Write)?;
+    let target = allowed.resolved.clone();
+    let affected = blocking(move || ops::write_file(&target, &content)).await?;
+    Ok(affected.map_paths(|p| report(p, &[&allowed])))
+}
+
+#[tauri::command]
+pub async fn create_file_entry(
+    path: String,
+    content: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<AffectedPaths> {
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), Path::new(&path), Access::Entry)?;
+    let target = allowed.resolved.clone();
+    let affected = blocking(move || ops::create_file(&target, content.as_deref())).await?;
+    Ok(affected.map_paths(|p| report(p, &[&allowed])))
+}
+
+#[tauri::command]
+pub async fn create_directory_entry(path: String, state: tauri::State<'_, AppState>) -> AppResult<AffectedPaths> {
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), Path::new(&path), Access::Entry)?;
+    let target = allowed.resolved.clone();
+    let affected = blocking(move || ops::create_directory(&target)).await?;
+    Ok(affected.map_paths(|p| report(p, &[&allowed])))
+}
+
+#[tauri::command]
+pub async fn rename_entry(path: String, new_name: String, state: tauri::State<'_, AppState>) -> AppResult<AffectedPaths> {
+    let policy = AccessPolicy::from_state(&state);
+    let allowed = Allowed::check(&policy, Path::new(&path), Access::Entry)?;
+    if let Some(parent) = allowed.resolved.parent() {
+        policy.check(&parent.join(&new_name), Access::Entry)?;
+    }
+    let source = allowed.resolved.clone();
+    let affected = blocking(move || ops::rename_path(&source, &new_name)).await?;
+    Ok(affected.map_paths(|p| report(p, &[&allowed])))
+}
+
+#[tauri::command]
+pub async fn move_entry(
+    source: String,
+    destination_dir: String,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<AffectedPaths> {
+    let policy = AccessPolicy::from_state(&state);
+    let source = Allowed::check(&policy, Path::new(&source), Access::Entry)?;
+    let destination = Allowed::check(&policy, Path::new(&destination_dir), Access::Read)?;
+    // The entry that gets created, not just the directory it lands in
+    let target = ops::move_target(&source.resolved, &destination.resolved)?;
+    policy.check(&target, Access::Entry)?;
+    let from = source.resolved.clone();
+    let affected = blocking(move || ops::move_to(&from, &target)).await?;
+    Ok(affected.map_paths(|p| report(p, &[&source, &destination])))
+}
+
+#[tauri::command]
+pub async fn copy_entry(
+    source: String,
+    destination_dir: String,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<AffectedPaths> {
+    let policy = AccessPolicy::from_state(&state);
+    let source = policy.check(Path::new(&source), Access::Read)?;
+    let destination = Allowed::check(&policy, Path::new(&destination_dir), Access::Read)?;
+    let target = ops::copy_target(&source, &destination.resolved)?;
+    policy.check(&target, Access::Entry)?;
+    let affected = blocking(move || ops::copy_to(&source, &target)).await?;
+    Ok(affected.map_paths(|p| report(p, &[&destination])))
+}
+
+/// Delete an entry. Goes to the trash unless `permanent` is true.
+#[tauri::command]
+pub async fn delete_entry(
+    path: String,
+    permanent: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<AffectedPaths> {
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), Path::new(&path), Access::Entry)?;
+    let target = allowed.resolved.clone();
+    let affected = blocking(move || ops::delete_path(&target, permanent.unwrap_or(false))).await?;
+    Ok(affected.map_paths(|p| report(p, &[&allowed])))
+}
+
+/// Watch a directory tree and emit batched `fs-changed` events for it.
+#[tauri::command]
+pub fn watch_directory(path: String, app: tauri::AppHandle, state: tauri::State<AppState>) -> AppResult<String> {
+    let resolved = AccessPolicy::from_state(&state).check(Path::new(&path), Access::Read)?;
+    watch::watch_as(&state, &resolved, Some(Path::new(&path)), Arc::new(app))
+}
+
+#[tauri::command]
+pub fn unwatch_directory(watch_id: String, state: tauri::State<AppState>) -> AppResult<()> {
+    watch::unwatch(&state, &watch_id)
+}
+
+#[tauri::command]
+pub fn get_git_stats(path: Option<String>, state: tauri::State<AppState>) -> AppResult<HashMap<String, GitStats>> {
+    let repo_path = path_or_cwd(path)?;
+    let allowed = Allowed::check(&AccessPolicy::from_state(&state), &repo_path, Access::Read)?;
+    let stats = git::git_stats(&state, &allowed.resolved)?;
+    Ok(stats.into_iter().map(|(path, stats)| (report(&path, &[&allowed]), stats)).collect())
+}
+
+#[tauri::command]
+pub fn get_current_branch(repo_path: String, state: tauri::State<AppState>) -> AppResult<Option<String>> {
+    let resolved = AccessPolicy::from_state(&state).check(Path::new(&repo_path), Access::Read)?;
+    git::get_current_branch(resolved.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub fn get_git_diff(file_path: String, repo_path: String, state: tauri::State<AppState>) -> AppResult<GitDiffResult> {
+    let policy = AccessPolicy::from_state(&state);
+    let repo = policy.check(Path::new(&repo_path), Access::Read)?;
+    let file = policy.check(&Path::new(&repo_path).join(&file_path), Access::Read)?;
+    let mut diff = git::get_git_diff(file.to_string_lossy().to_string(), repo.to_string_lossy().to_string())?;
+    diff.file_path = file_path;
+    Ok(diff)
+}
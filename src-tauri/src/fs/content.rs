@@ -0,0 +1,483 @@
+//! Bounded, encoding-aware file reads for the viewer.
+//!
+//! Nothing reads more than `max_bytes` (1 MiB by default, 16 MiB at most),
+//! whether the caller asks for the whole file, a byte range, or the first or
+//! last N lines. The encoding is detected once from the start of the file:
+//! a BOM wins, then UTF-16 without a BOM (ASCII-heavy text with every other
+//! byte zero), then UTF-8, and anything else that doesn't look binary is read
+//! as ISO-8859-1, which never fails to decode.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use serde::Serialize;
+use crate::error::{AppError, AppResult, ErrorCode};
+
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+const MAX_BYTES: u64 = 16 * 1024 * 1024;
+/// Bytes inspected for the BOM, encoding and binary check
+const SAMPLE_LEN: usize = 8 * 1024;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[serde(rename = "iso-8859-1")]
+    Latin1,
+}
+
+impl TextEncoding {
+    pub(crate) fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        TextEncoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+        }
+    }
+
+    /// Bytes `text` took up in the file.
+    fn encoded_len(self, text: &str) -> u64 {
+        match self {
+            TextEncoding::Utf8 => text.len() as u64,
+            TextEncoding::Latin1 => text.chars().count() as u64,
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => text.encode_utf16().count() as u64 * 2,
+        }
+    }
+
+    fn is_utf16(self) -> bool {
+        matches!(self, TextEncoding::Utf16Le | TextEncoding::Utf16Be)
+    }
+}
+
+/// Encoding and BOM length for a file starting with `head`, or `None` if
+/// it looks binary.
+pub(crate) fn detect_encoding(head: &[u8]) -> Option<(TextEncoding, usize)> {
+    if head.starts_with(b"\xef\xbb\xbf") {
+        return Some((TextEncoding::Utf8, 3));
+    }
+    if head.starts_with(b"\xff\xfe") {
+        return Some((TextEncoding::Utf16Le, 2));
+    }
+    if head.starts_with(b"\xfe\xff") {
+        return Some((TextEncoding::Utf16Be, 2));
+    }
+    if let Some(encoding) = bomless_utf16(head) {
+        return Some((encoding, 0));
+    }
+    if head.contains(&0) {
+        return None;
+    }
+    // Mostly control characters: not text in any encoding we read
+    let control = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    if control * 10 > head.len() {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => Some((TextEncoding::Utf8, 0)),
+        // Cut off in the middle of a character
+        Err(e) if e.error_len().is_none() => Some((TextEncoding::Utf8, 0)),
+        Err(_) => Some((TextEncoding::Latin1, 0)),
+    }
+}
+
+/// UTF-16 without a BOM, recognised by its zero high bytes on ASCII text.
+fn bomless_utf16(head: &[u8]) -> Option<TextEncoding> {
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even_zeros = head.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_zeros = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if odd_zeros * 10 >= pairs * 9 && even_zeros == 0 {
+        Some(TextEncoding::Utf16Le)
+    } else if even_zeros * 10 >= pairs * 9 && odd_zeros == 0 {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Result of `read_file_content`.
+#[derive(Serialize, Clone, Debug)]
+pub struct FileContent {
+    pub path: String,
+    /// Decoded text; empty for binary files
+    pub content: String,
+    /// `None` for binary files
+    pub encoding: Option<TextEncoding>,
+    /// The file starts with a byte order mark (never part of `content`)
+    pub bom: bool,
+    pub is_binary: bool,
+    /// Part of the file (or of the requested range) was left out
+    pub truncated: bool,
+    /// File size in bytes
+    pub size: u64,
+    /// Byte offset in the file where `content` starts
+    pub offset: u64,
+    /// Bytes of the file `content` covers
+    pub length: u64,
+    /// Lines in `content`
+    pub line_count: usize,
+}
+
+/// Which part of the file to read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadRange {
+    /// From the start, up to the size cap
+    Whole,
+    /// `length` bytes (up to the cap) from `offset`
+    Bytes { offset: u64, length: Option<u64> },
+    /// The first N lines
+    Head(usize),
+    /// The last N lines
+    Tail(usize),
+}
+
+impl ReadRange {
+    /// The range named by `read_file_content`'s arguments; at most one kind
+    /// may be given.
+    pub fn from_args(
+        offset: Option<u64>,
+        length: Option<u64>,
+        head: Option<usize>,
+        tail: Option<usize>,
+    ) -> AppResult<Self> {
+        let bytes = offset.is_some() || length.is_some();
+        match (bytes, head, tail) {
+            (false, None, None) => Ok(ReadRange::Whole),
+            (true, None, None) => Ok(ReadRange::Bytes {
+                offset: offset.unwrap_or(0),
+                length,
+            }),
+            (false, Some(n), None) => Ok(ReadRange::Head(n)),
+            (false, None, Some(n)) => Ok(ReadRange::Tail(n)),
+            _ => Err(AppError::new(
+                ErrorCode::InvalidInput,
+                "Pass only one of offset/length, head or tail",
+            )),
+        }
+    }
+}
+
+fn read_at(file: &mut File, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Length of `bytes` without an incomplete UTF-8 sequence at the end.
+fn utf8_complete_len(bytes: &[u8]) -> usize {
+    let tail_start = bytes.len().saturating_sub(3);
+    for i in (tail_start..bytes.len()).rev() {
+        let b = bytes[i];
+        if b & 0xc0 == 0x80 {
+            continue;
+        }
+        let needed = match b {
+            0xf0..=0xf7 => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        };
+        return if i + needed > bytes.len() { i } else { bytes.len() };
+    }
+    bytes.len()
+}
+
+/// Read part of `path` as text.
+pub fn read_content(path: &Path, range: ReadRange, max_bytes: Option<u64>) -> AppResult<FileContent> {
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_BYTES).clamp(1, MAX_BYTES);
+    let mut file = File::open(path).map_err(|e| AppError::io("Failed to open file", &e).with_path(path))?;
+    let meta = file
+        .metadata()
+        .map_err(|e| AppError::io("Failed to read metadata", &e).with_path(path))?;
+    if meta.is_dir() {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Is a directory").with_path(path));
+    }
+    let size = meta.len();
+    let io_err = |e: std::io::Error| AppError::io("Failed to read file", &e).with_path(path);
+
+    let sample = read_at(&mut file, 0, SAMPLE_LEN as u64).map_err(io_err)?;
+    let mut result = FileContent {
+        path: path.to_string_lossy().to_string(),
+        content: String::new(),
+        encoding: None,
+        bom: false,
+        is_binary: false,
+        truncated: false,
+        size,
+        offset: 0,
+        length: 0,
+        line_count: 0,
+    };
+    let Some((encoding, bom_len)) = detect_encoding(&sample) else {
+        result.is_binary = true;
+        return Ok(result);
+    };
+    result.encoding = Some(encoding);
+    result.bom = bom_len > 0;
+    let text_start = bom_len as u64;
+
+    // The byte window to decode, before trimming to characters or lines
+    let (mut start, mut end, wanted_end) = match range {
+        ReadRange::Whole | ReadRange::Head(_) => {
+            (text_start, size.min(text_start + max_bytes), size)
+        }
+        ReadRange::Bytes { offset, length } => {
+            let start = offset.max(text_start).min(size);
+            let wanted_end = match length {
+                Some(length) => start.saturating_add(length).min(size),
+                None => size,
+            };
+            (start, wanted_end.min(start + max_bytes), wanted_end)
+        }
+        ReadRange::Tail(_) => (size.saturating_sub(max_bytes).max(text_start), size, size),
+    };
+    // Keep UTF-16 code units whole
+    if encoding.is_utf16() {
+        if (start - text_start) % 2 == 1 {
+            start += 1;
+        }
+        if end > start && (end - start) % 2 == 1 {
+            end -= 1;
+        }
+    }
+    let end = end.max(start);
+
+    let mut bytes = read_at(&mut file, start, end - start).map_err(io_err)?;
+    if encoding == TextEncoding::Utf8 {
+        // Start and end on character boundaries
+        if start > text_start {
+            let skip = bytes.iter().take(3).take_while(|&&b| b & 0xc0 == 0x80).count();
+            bytes.drain(..skip);
+            start += skip as u64;
+        }
+        if end < size {
+            bytes.truncate(utf8_complete_len(&bytes));
+        }
+    }
+    let end = start + bytes.len() as u64;
+    let text = encoding.decode(&bytes);
+
+    let (content, offset) = match range {
+        ReadRange::Head(n) => {
+            let cut = text.match_indices('\n').nth(n.saturating_sub(1)).map(|(i, _)| i + 1);
+            let content = match cut {
+                Some(cut) if n > 0 => &text[..cut],
+                _ if n == 0 => "",
+                _ => text.as_str(),
+            };
+            result.truncated = encoding.encoded_len(content) < size - text_start;
+            (content.to_string(), start)
+        }
+        ReadRange::Tail(n) => {
+            let body = text.strip_suffix('\n').unwrap_or(&text);
+            let cut = body.rmatch_indices('\n').nth(n.saturating_sub(1)).map(|(i, _)| i + 1);
+            let from = match cut {
+                _ if n == 0 => text.len(),
+                Some(cut) => cut,
+                // Fewer than N lines in the window: drop a partial first line
+                None if start > text_start => text.find('\n').map_or(0, |i| i + 1),
+                None => 0,
+            };
+            let offset = start + encoding.encoded_len(&text[..from]);
+            result.truncated = offset > text_start;
+            (text[from..].to_string(), offset)
+        }
+        ReadRange::Whole | ReadRange::Bytes { .. } => {
+            result.truncated = end < wanted_end;
+            (text, start)
+        }
+    };
+
+    result.offset = offset;
+    result.length = encoding.encoded_len(&content);
+    result.line_count = content.lines().count();
+    result.content = content;
+    Ok(result)
+}
+
+/// Read a file as text, bounded by `max_bytes`. Pass `offset`/`length` for
+/// a byte range, or `head`/`tail` for the first or last N lines.
+pub fn read_file_content(
+    path: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    max_bytes: Option<u64>,
+) -> AppResult<FileContent> {
+    let range = ReadRange::from_args(offset, length, head, tail)?;
+    read_content(Path::new(&path), range, max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `read_content` on a temporary file holding `bytes`.
+    fn read(bytes: &[u8], range: ReadRange, max_bytes: Option<u64>) -> FileContent {
+        let path = std::env::temp_dir().join(format!("content-test-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, bytes).unwrap();
+        let result = read_content(&path, range, max_bytes);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap()
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn detect_encoding_table() {
+        type Detected = Option<(TextEncoding, usize)>;
+        let cases: [(&[u8], Detected); 12] = [
+            (b"", Some((TextEncoding::Utf8, 0))),
+            (b"hello\n", Some((TextEncoding::Utf8, 0))),
+            ("h\u{e9}llo \u{20ac}".as_bytes(), Some((TextEncoding::Utf8, 0))),
+            (b"\xef\xbb\xbfhi", Some((TextEncoding::Utf8, 3))),
+            (b"\xff\xfeh\0i\0", Some((TextEncoding::Utf16Le, 2))),
+            (b"\xfe\xff\0h\0i", Some((TextEncoding::Utf16Be, 2))),
+            (b"h\0i\0!\0\n\0", Some((TextEncoding::Utf16Le, 0))),
+            (b"\0h\0i\0!\0\n", Some((TextEncoding::Utf16Be, 0))),
+            // Cut off in the middle of `é`
+            (b"caf\xc3", Some((TextEncoding::Utf8, 0))),
+            (b"caf\xe9 au lait", Some((TextEncoding::Latin1, 0))),
+            (b"\x7fELF\x02\x01\x01\0\0\0\0\0", None),
+            (b"\x01\x02\x03\x04\x05abc", None),
+        ];
+        for (head, expected) in cases {
+            assert_eq!(detect_encoding(head), expected, "{:?}", head);
+        }
+    }
+
+    #[test]
+    fn bom_is_skipped() {
+        let utf8 = read(b"\xef\xbb\xbfa\nb\n", ReadRange::Whole, None);
+        assert_eq!(
+            (utf8.content.as_str(), utf8.bom, utf8.offset, utf8.length, utf8.line_count, utf8.truncated),
+            ("a\nb\n", true, 3, 4, 2, false)
+        );
+
+        let mut bytes = b"\xff\xfe".to_vec();
+        bytes.extend(utf16le("ab\ncd\n"));
+        let utf16 = read(&bytes, ReadRange::Whole, None);
+        assert_eq!(utf16.encoding, Some(TextEncoding::Utf16Le));
+        assert_eq!((utf16.content.as_str(), utf16.offset, utf16.length), ("ab\ncd\n", 2, 12));
+
+        // An offset inside the BOM starts after it
+        let range = read(b"\xef\xbb\xbfabc", ReadRange::Bytes { offset: 1, length: None }, None);
+        assert_eq!((range.content.as_str(), range.offset), ("abc", 3));
+    }
+
+    #[test]
+    fn utf16_ranges_keep_code_units_whole() {
+        let mut bytes = b"\xff\xfe".to_vec();
+        bytes.extend(utf16le("ab\ncd\n"));
+        // (offset, length, content, offset, truncated)
+        let cases = [
+            (2, Some(4), "ab", 2, false),
+            // Odd start moves up, odd end moves down
+            (3, Some(4), "b", 4, true),
+            (4, Some(3), "b", 4, true),
+            (8, None, "cd\n", 8, false),
+        ];
+        for (offset, length, content, start, truncated) in cases {
+            let result = read(&bytes, ReadRange::Bytes { offset, length }, None);
+            assert_eq!(
+                (result.content.as_str(), result.offset, result.truncated),
+                (content, start, truncated),
+                "offset {} length {:?}",
+                offset,
+                length
+            );
+            assert_eq!(result.length, content.encode_utf16().count() as u64 * 2);
+        }
+    }
+
+    #[test]
+    fn utf8_windows_end_on_character_boundaries() {
+        // a é € b: 1 + 2 + 3 + 1 bytes
+        let text = "a\u{e9}\u{20ac}b";
+        // (range, max_bytes, content, offset, truncated)
+        let cases = [
+            (ReadRange::Bytes { offset: 0, length: Some(3) }, None, "a\u{e9}", 0, false),
+            (ReadRange::Bytes { offset: 0, length: Some(4) }, None, "a\u{e9}", 0, true),
+            (ReadRange::Bytes { offset: 0, length: Some(2) }, None, "a", 0, true),
+            // Starting inside `é` skips to `€`
+            (ReadRange::Bytes { offset: 2, length: None }, None, "\u{20ac}b", 3, false),
+            // Entirely inside `€`
+            (ReadRange::Bytes { offset: 4, length: Some(2) }, None, "", 6, false),
+            (ReadRange::Whole, Some(4), "a\u{e9}", 0, true),
+            (ReadRange::Whole, Some(7), text, 0, false),
+        ];
+        for (range, max_bytes, content, offset, truncated) in cases {
+            let result = read(text.as_bytes(), range, max_bytes);
+            assert_eq!(
+                (result.content.as_str(), result.offset, result.truncated),
+                (content, offset, truncated),
+                "{:?} max {:?}",
+                range,
+                max_bytes
+            );
+            assert_eq!(result.length, content.len() as u64);
+        }
+    }
+
+    #[test]
+    fn head_and_tail() {
+        let text = b"one\ntwo\nthree\n";
+        // (range, max_bytes, content, offset, truncated)
+        let cases = [
+            (ReadRange::Head(2), None, "one\ntwo\n", 0, true),
+            (ReadRange::Head(5), None, "one\ntwo\nthree\n", 0, false),
+            (ReadRange::Head(0), None, "", 0, true),
+            (ReadRange::Tail(1), None, "three\n", 8, true),
+            (ReadRange::Tail(2), None, "two\nthree\n", 4, true),
+            // Fewer lines than asked for
+            (ReadRange::Tail(5), None, "one\ntwo\nthree\n", 0, false),
+            (ReadRange::Tail(0), None, "", 14, true),
+            // The window starts inside `two`; the partial line is dropped
+            (ReadRange::Tail(5), Some(9), "three\n", 8, true),
+        ];
+        for (range, max_bytes, content, offset, truncated) in cases {
+            let result = read(text, range, max_bytes);
+            assert_eq!(
+                (result.content.as_str(), result.offset, result.truncated),
+                (content, offset, truncated),
+                "{:?} max {:?}",
+                range,
+                max_bytes
+            );
+            assert_eq!(result.line_count, content.lines().count());
+        }
+
+        // No trailing newline
+        let result = read(b"one\ntwo", ReadRange::Tail(1), None);
+        assert_eq!((result.content.as_str(), result.offset), ("two", 4));
+    }
+
+    #[test]
+    fn binary_files_have_no_content() {
+        let result = read(b"\x7fELF\x02\x01\x01\0\0\0\0\0", ReadRange::Whole, None);
+        assert!(result.is_binary);
+        assert_eq!((result.content.as_str(), result.encoding, result.size), ("", None, 12));
+    }
+}
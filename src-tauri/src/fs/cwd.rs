@@ -1,5 +1,7 @@
 use std::fs;
-use crate::state::AppState;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::pty::manager::Isolation;
+use crate::state::{lock_session, AppState};
 
 /// Find a child process of the given PID by reading /proc/[pid]/task/[tid]/children
 #[cfg(target_os = "linux")]
@@ -18,25 +20,55 @@ fn find_child_pid(parent_pid: u32) -> Option<u32> {
 }
 
 #[tauri::command]
-pub fn get_terminal_cwd(session_id: String, state: tauri::State<AppState>) -> Result<String, String> {
-    let state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
+pub fn get_terminal_cwd(session_id: String, state: tauri::State<AppState>) -> AppResult<String> {
+    terminal_cwd(&state, &session_id)
+}
 
-    let session = state_lock
-        .pty_sessions
-        .get(&session_id)
-        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+/// Current working directory of the shell running in `session_id`.
+pub fn terminal_cwd(state: &AppState, session_id: &str) -> AppResult<String> {
+    // A cwd reported by the shell (OSC 7) wins: it is what the user sees
+    // and also works for remote shells.
+    let reported = {
+        let handle = state.session(session_id)?;
+        let session = lock_session(&handle)?;
+        let cwd = session.shell.lock().ok().and_then(|s| s.cwd.clone());
+        cwd
+    };
+    match reported {
+        Some(cwd) => Ok(cwd),
+        None => process_cwd(state, session_id),
+    }
+}
 
-    // Get the PID of the child process (shell or bwrap wrapper)
-    let pid = session.child.process_id()
-        .ok_or_else(|| "Failed to get process ID".to_string())?;
+/// Working directory of the shell process itself, as the OS reports it.
+///
+/// Unlike [`terminal_cwd`] this ignores OSC 7, which any program writing to
+/// the terminal can forge, so it is what the access policy trusts.
+pub(crate) fn process_cwd(state: &AppState, session_id: &str) -> AppResult<String> {
+    // Only hold the session lock long enough to read the PID; the /proc or
+    // sysinfo lookup below runs unlocked.
+    let (pid, sandboxed) = {
+        let handle = state.session(session_id)?;
+        let session = lock_session(&handle)?;
+        // The local process is `ssh` or the container client; its cwd says
+        // nothing about the shell
+        if session.remote.is_some() || session.isolation == Isolation::Container {
+            return Err(AppError::new(
+                ErrorCode::Unsupported,
+                "Remote or container shell has not reported its cwd (needs OSC 7 shell integration)",
+            )
+            .with_session(session_id));
+        }
+        let pid = session.child.process_id()
+            .ok_or_else(|| AppError::new(ErrorCode::NotFound, "Failed to get process ID").with_session(session_id))?;
+        (pid, session.sandboxed())
+    };
 
     #[cfg(target_os = "linux")]
     {
         // When sandboxed, the child PID is bwrap, not the shell.
         // Find the shell by looking for bwrap's child process.
-        let target_pid = if session.sandboxed {
+        let target_pid = if sandboxed {
             find_child_pid(pid).unwrap_or(pid)
         } else {
             pid
@@ -46,9 +78,12 @@ pub fn get_terminal_cwd(session_id: String, state: tauri::State<AppState>) -> Re
         let cwd_link = format!("/proc/{}/cwd", target_pid);
         fs::read_link(&cwd_link)
             .map(|p| p.to_string_lossy().to_string())
-            .map_err(|e| format!("Failed to read cwd from /proc: {}", e))
+            .map_err(|e| AppError::io("Failed to read cwd from /proc", &e).with_session(session_id))
     }
 
+    #[cfg(not(target_os = "linux"))]
+    let _ = sandboxed;
+
     #[cfg(target_os = "windows")]
     {
         use sysinfo::{Pid, System, ProcessRefreshKind, UpdateKind, ProcessesToUpdate};
@@ -68,10 +103,10 @@ pub fn get_terminal_cwd(session_id: String, state: tauri::State<AppState>) -> Re
             if let Some(cwd) = process.cwd() {
                 Ok(cwd.to_string_lossy().to_string())
             } else {
-                Err("Could not get process CWD".to_string())
+                Err(AppError::new(ErrorCode::NotFound, "Could not get process CWD").with_session(session_id))
             }
         } else {
-            Err(format!("Process {} not found", pid))
+            Err(AppError::new(ErrorCode::NotFound, format!("Process {} not found", pid)).with_session(session_id))
         }
     }
 
@@ -92,15 +127,15 @@ pub fn get_terminal_cwd(session_id: String, state: tauri::State<AppState>) -> Re
             if let Some(cwd) = process.cwd() {
                 Ok(cwd.to_string_lossy().to_string())
             } else {
-                Err("Could not get process CWD".to_string())
+                Err(AppError::new(ErrorCode::NotFound, "Could not get process CWD").with_session(session_id))
             }
         } else {
-            Err(format!("Process {} not found", pid))
+            Err(AppError::new(ErrorCode::NotFound, format!("Process {} not found", pid)).with_session(session_id))
         }
     }
 
     #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
     {
-        Err("Getting terminal cwd is not supported on this platform".to_string())
+        Err(AppError::new(ErrorCode::Unsupported, "Getting terminal cwd is not supported on this platform"))
     }
 }
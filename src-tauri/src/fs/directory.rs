@@ -3,39 +3,49 @@ use std::path::PathBuf;
 use std::collections::HashSet;
 use serde::Serialize;
 use walkdir::WalkDir;
+use crate::error::{AppError, AppResult};
+use crate::fs::ignore::IgnoreMatcher;
+use crate::fs::metadata::{EntryMetadata, MetadataField, MetadataFields};
 
 #[derive(Serialize)]
 pub struct DirectoryEntry {
-    name: String,
-    path: String,
-    is_dir: bool,
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
 }
 
 #[derive(Serialize)]
 pub struct RecursiveDirectoryEntry {
-    name: String,
-    path: String,
-    is_dir: bool,
-    depth: usize,
-    parent_path: String,
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub depth: usize,
+    pub parent_path: String,
+    /// Matched by .gitignore, git excludes or .terminalignore. Only ever
+    /// true when the caller asked for ignored entries.
+    pub ignored: bool,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
 }
 
-#[tauri::command]
-pub fn read_directory(path: Option<String>) -> Result<Vec<DirectoryEntry>, String> {
+pub fn read_directory(path: Option<String>, fields: Option<Vec<MetadataField>>) -> AppResult<Vec<DirectoryEntry>> {
+    let fields = MetadataFields::new(fields);
     let dir_path = if let Some(p) = path {
         PathBuf::from(p)
     } else {
-        std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?
+        std::env::current_dir().map_err(|e| AppError::io("Failed to get current directory", &e))?
     };
 
     let entries = fs::read_dir(&dir_path)
-        .map_err(|e| format!("Failed to read directory: {}", e))?;
+        .map_err(|e| AppError::io("Failed to read directory", &e).with_path(&dir_path))?;
 
     let mut result = Vec::new();
 
     for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let metadata = entry.metadata().map_err(|e| format!("Failed to read metadata: {}", e))?;
+        let entry = entry.map_err(|e| AppError::io("Failed to read entry", &e).with_path(&dir_path))?;
+        let metadata = entry.metadata().map_err(|e| AppError::io("Failed to read metadata", &e).with_path(entry.path()))?;
         let path = entry.path();
         let name = path
             .file_name()
@@ -45,8 +55,9 @@ pub fn read_directory(path: Option<String>) -> Result<Vec<DirectoryEntry>, Strin
 
         result.push(DirectoryEntry {
             name,
-            path: path.to_string_lossy().to_string(),
             is_dir: metadata.is_dir(),
+            metadata: fields.read(&path),
+            path: path.to_string_lossy().to_string(),
         });
     }
 
@@ -62,90 +73,51 @@ pub fn read_directory(path: Option<String>) -> Result<Vec<DirectoryEntry>, Strin
     Ok(result)
 }
 
-#[tauri::command]
-pub fn read_file_content(path: String) -> Result<String, String> {
-    fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read file: {}", e))
-}
-
-#[tauri::command]
 pub fn read_directory_recursive(
     path: Option<String>,
     max_depth: Option<usize>,
-    max_files: Option<usize>
-) -> Result<Vec<RecursiveDirectoryEntry>, String> {
+    max_files: Option<usize>,
+    show_ignored: Option<bool>,
+    fields: Option<Vec<MetadataField>>,
+) -> AppResult<Vec<RecursiveDirectoryEntry>> {
     let root_path = if let Some(ref p) = path {
         PathBuf::from(p)
     } else {
-        std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?
+        std::env::current_dir().map_err(|e| AppError::io("Failed to get current directory", &e))?
     };
 
     let max_depth = max_depth.unwrap_or(10);
     let max_files = max_files.unwrap_or(10000);
-
-    // Directories to ignore
-    let ignore_dirs: HashSet<&str> = [
-        ".git",
-        "node_modules",
-        "target",
-        "dist",
-        "build",
-        ".cache",
-        ".next",
-        ".nuxt",
-        "__pycache__",
-        ".venv",
-        "venv",
-    ]
-    .iter()
-    .copied()
-    .collect();
+    let show_ignored = show_ignored.unwrap_or(false);
+    let fields = MetadataFields::new(fields);
+
+    let mut ignore = IgnoreMatcher::new(&root_path);
+    // Everything is ignored when the root itself sits in an ignored dir
+    let root_ignored = ignore.is_ignored_within(&root_path, true);
+    if root_ignored && !show_ignored {
+        return Ok(Vec::new());
+    }
+    // Ignored directories being shown; their contents are ignored too
+    let mut ignored_dirs: HashSet<PathBuf> = HashSet::new();
 
     let mut entries: Vec<RecursiveDirectoryEntry> = Vec::new();
     let root_path_str = root_path.to_string_lossy().to_string();
 
-    // Walk directory tree
-    for entry in WalkDir::new(&root_path)
+    // Walk directory tree. Siblings are sorted as they are read, so entries
+    // come out in tree order: directories first, then files, by name.
+    let mut walker = WalkDir::new(&root_path)
         .max_depth(max_depth)
         .follow_links(false) // Don't follow symlinks
-        .into_iter()
-        .filter_entry(|e| {
-            // Skip ignored directories
-            if e.file_type().is_dir() {
-                if let Some(name) = e.file_name().to_str() {
-                    if ignore_dirs.contains(name) {
-                        return false;
-                    }
-                }
-
-                // On Windows, skip directories with hidden/system attributes
-                // and skip symlinks/junctions at the filter level to prevent traversal
-                #[cfg(target_os = "windows")]
-                {
-                    if e.path_is_symlink() {
-                        return false;
-                    }
-                    if let Ok(metadata) = std::fs::symlink_metadata(e.path()) {
-                        use std::os::windows::fs::MetadataExt;
-                        let attrs = metadata.file_attributes();
-                        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
-                        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
-                        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
-                        if attrs & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM | FILE_ATTRIBUTE_REPARSE_POINT) != 0 {
-                            return false;
-                        }
-                    }
-                }
-            }
-            true
+        .sort_by(|a, b| {
+            let key = |e: &walkdir::DirEntry| (!e.file_type().is_dir(), e.file_name().to_string_lossy().to_lowercase());
+            key(a).cmp(&key(b))
         })
-    {
+        .into_iter();
+    while let Some(entry) = walker.next() {
         // Check if we've reached the file limit
         if entries.len() >= max_files {
-            eprintln!("Warning: Reached max file limit of {}. Last 5 paths:", max_files);
-            for e in entries.iter().rev().take(5) {
-                eprintln!("  - {}", e.path);
-            }
+            let last: Vec<&str> = entries.iter().rev().take(5).map(|e| e.path.as_str()).collect();
+            log::warn!(target: "fs", "Reached max file limit of {} in {}. Last 5 paths: {:?}", max_files, root_path_str, last);
             break;
         }
 
@@ -156,12 +128,33 @@ pub fn read_directory_recursive(
                     continue;
                 }
 
+                let is_dir = e.file_type().is_dir();
+                if is_dir && is_hidden_system_dir(&e) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+
                 // Skip symlinks
                 if e.path_is_symlink() {
                     continue;
                 }
 
                 let path = e.path();
+                let parent_ignored = root_ignored || path.parent().is_some_and(|p| ignored_dirs.contains(p));
+                let ignored = parent_ignored || ignore.is_ignored(path, is_dir);
+                if ignored {
+                    // Git's own directory is never listed
+                    if !show_ignored || e.file_name() == ".git" {
+                        if is_dir {
+                            walker.skip_current_dir();
+                        }
+                        continue;
+                    }
+                    if is_dir {
+                        ignored_dirs.insert(path.to_path_buf());
+                    }
+                }
+
                 let name = path
                     .file_name()
                     .and_then(|n| n.to_str())
@@ -176,7 +169,6 @@ pub fn read_directory_recursive(
                     .map(|p| p.to_string_lossy().to_string())
                     .unwrap_or_else(|| root_path_str.clone());
 
-                let is_dir = e.file_type().is_dir();
                 let depth = e.depth();
 
                 entries.push(RecursiveDirectoryEntry {
@@ -185,23 +177,41 @@ pub fn read_directory_recursive(
                     is_dir,
                     depth,
                     parent_path,
+                    ignored,
+                    metadata: fields.read(path),
                 });
             }
             Err(err) => {
                 // Log error but continue processing
-                eprintln!("Warning: Failed to read entry: {}", err);
+                log::warn!(target: "fs", "Failed to read entry: {}", err);
             }
         }
     }
 
-    // Sort: directories first, then files, both alphabetically
-    entries.sort_by(|a, b| {
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+    Ok(entries)
+}
+
+/// On Windows, directories with hidden/system attributes and symlinks or
+/// junctions are never walked into.
+#[cfg(target_os = "windows")]
+pub(crate) fn is_hidden_system_dir(e: &walkdir::DirEntry) -> bool {
+    if e.path_is_symlink() {
+        return true;
+    }
+    if let Ok(metadata) = std::fs::symlink_metadata(e.path()) {
+        use std::os::windows::fs::MetadataExt;
+        let attrs = metadata.file_attributes();
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+        if attrs & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM | FILE_ATTRIBUTE_REPARSE_POINT) != 0 {
+            return true;
         }
-    });
+    }
+    false
+}
 
-    Ok(entries)
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn is_hidden_system_dir(_e: &walkdir::DirEntry) -> bool {
+    false
 }
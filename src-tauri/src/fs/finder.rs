@@ -0,0 +1,444 @@
+//! Fuzzy file finder.
+//!
+//! `find_files` ranks the files and directories under a root against a query
+//! without sending the tree to the frontend. Each root gets an index of its
+//! entry paths, honoring the tree's ignore rules, built on the first query
+//! and kept current by a watcher. When the watcher overflows or ignore rules
+//! change, the next query walks the tree again. At most [`MAX_INDEXES`] roots
+//! are indexed at once; the least recently queried is dropped first. Files
+//! opened in the viewer rank higher, and an empty query lists them.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use serde::Serialize;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::directory::is_hidden_system_dir;
+use crate::fs::fuzzy::Pattern;
+use crate::fs::ignore::{IgnoreMatcher, TERMINAL_IGNORE_FILE};
+use crate::fs::policy::AccessPolicy;
+use crate::fs::watch::{watch_batches, FsChangeBatch, WatchHandle};
+use crate::state::AppState;
+
+/// Roots indexed at once
+const MAX_INDEXES: usize = 8;
+/// Entries kept per index; the rest of a larger tree isn't searched
+const MAX_INDEXED_FILES: usize = 200_000;
+/// Opened files remembered for ranking
+const MAX_RECENT: usize = 100;
+/// Added to the score of the most recently opened file, less for older ones
+const RECENT_BONUS: i32 = 48;
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1000;
+
+/// One result of `find_files`.
+#[derive(Serialize, Clone, Debug)]
+pub struct FileMatch {
+    pub path: String,
+    /// Path below the root, with `/` separators
+    pub relative_path: String,
+    pub is_dir: bool,
+    pub score: i32,
+    /// The file was opened recently
+    pub recent: bool,
+    /// Matched `[start, end)` ranges of `relative_path`, in UTF-16 code
+    /// units as JavaScript indexes strings
+    pub highlights: Vec<[usize; 2]>,
+}
+
+/// Result of `find_files`.
+#[derive(Serialize, Clone, Debug)]
+pub struct FindResult {
+    pub root: String,
+    /// Best first
+    pub matches: Vec<FileMatch>,
+    /// Entries in the root's index
+    pub indexed: usize,
+    /// The root has more entries than the index holds
+    pub truncated: bool,
+}
+
+/// File indexes by canonical root, and recently opened files.
+#[derive(Default)]
+pub struct FileIndexes {
+    indexes: Mutex<HashMap<PathBuf, Arc<FileIndex>>>,
+    /// Canonical paths, most recent first
+    recent: Mutex<VecDeque<PathBuf>>,
+}
+
+impl FileIndexes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that `path` was opened, so `find_files` ranks it higher.
+    pub fn record_opened(&self, path: &Path) {
+        let Ok(path) = path.canonicalize() else {
+            return;
+        };
+        let Ok(mut recent) = self.recent.lock() else {
+            return;
+        };
+        recent.retain(|p| *p != path);
+        recent.push_front(path);
+        recent.truncate(MAX_RECENT);
+    }
+
+    /// The index for `root`, started if there is none yet.
+    fn index(&self, root: &Path) -> AppResult<Arc<FileIndex>> {
+        let mut indexes = self.indexes.lock().map_err(AppError::lock)?;
+        if let Some(index) = indexes.get(root) {
+            index.touch();
+            return Ok(index.clone());
+        }
+        if indexes.len() >= MAX_INDEXES {
+            let oldest = indexes
+                .iter()
+                .min_by_key(|(_, index)| index.last_used())
+                .map(|(root, _)| root.clone());
+            if let Some(oldest) = oldest {
+                log::debug!(target: "fs", "Dropping file index for {}", oldest.display());
+                indexes.remove(&oldest);
+            }
+        }
+        let index = FileIndex::start(root.to_path_buf());
+        indexes.insert(root.to_path_buf(), index.clone());
+        Ok(index)
+    }
+}
+
+/// The files and directories under one root.
+struct FileIndex {
+    root: PathBuf,
+    files: Mutex<IndexedFiles>,
+    /// Held while walking, so concurrent queries wait for a single walk
+    building: Mutex<()>,
+    last_used: Mutex<Instant>,
+    /// `None` if the root couldn't be watched; then every query walks
+    watch: Mutex<Option<WatchHandle>>,
+}
+
+#[derive(Default)]
+struct IndexedFiles {
+    /// Relative paths with `/` separators, and whether each is a directory
+    paths: BTreeMap<String, bool>,
+    truncated: bool,
+    /// Walk the tree before the next query
+    stale: bool,
+    /// Batches that arrived during a walk, applied to its result
+    pending: Option<Vec<FsChangeBatch>>,
+}
+
+impl FileIndex {
+    fn start(root: PathBuf) -> Arc<Self> {
+        let index = Arc::new(Self {
+            root: root.clone(),
+            files: Mutex::new(IndexedFiles {
+                stale: true,
+                ..IndexedFiles::default()
+            }),
+            building: Mutex::new(()),
+            last_used: Mutex::new(Instant::now()),
+            watch: Mutex::new(None),
+        });
+        // The watcher only holds a weak reference, so dropping the index
+        // drops its handle and stops the watcher
+        let weak = Arc::downgrade(&index);
+        let watch_id = Uuid::new_v4().to_string();
+        match watch_batches(root.clone(), watch_id, move |batch| {
+            if let Some(index) = weak.upgrade() {
+                if let Ok(mut files) = index.files.lock() {
+                    files.apply(&index.root, batch);
+                }
+            }
+        }) {
+            Ok(handle) => {
+                if let Ok(mut watch) = index.watch.lock() {
+                    *watch = Some(handle);
+                }
+            }
+            Err(e) => log::warn!(target: "fs", "File index for {} won't update: {}", root.display(), e.message),
+        }
+        index
+    }
+
+    fn touch(&self) {
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+    }
+
+    fn last_used(&self) -> Instant {
+        self.last_used.lock().map(|t| *t).unwrap_or_else(|_| Instant::now())
+    }
+
+    fn watched(&self) -> bool {
+        self.watch.lock().is_ok_and(|watch| watch.is_some())
+    }
+
+    /// Walk the tree if the index is stale.
+    fn refresh(&self) -> AppResult<()> {
+        let _building = self.building.lock().map_err(AppError::lock)?;
+        {
+            let mut files = self.files.lock().map_err(AppError::lock)?;
+            if !files.stale && self.watched() {
+                return Ok(());
+            }
+            files.stale = false;
+            files.pending = Some(Vec::new());
+        }
+
+        let started = Instant::now();
+        let mut paths = BTreeMap::new();
+        let mut ignore = IgnoreMatcher::new(&self.root);
+        let truncated = walk_entries(&self.root, &self.root, &mut ignore, &mut paths);
+
+        let mut files = self.files.lock().map_err(AppError::lock)?;
+        let pending = files.pending.take().unwrap_or_default();
+        files.paths = paths;
+        files.truncated = truncated;
+        for batch in pending {
+            files.apply(&self.root, batch);
+        }
+        log::debug!(
+            target: "fs",
+            "Indexed {} entries under {} in {:?}",
+            files.paths.len(),
+            self.root.display(),
+            started.elapsed()
+        );
+        Ok(())
+    }
+}
+
+impl IndexedFiles {
+    /// Update the index from a watcher batch.
+    fn apply(&mut self, root: &Path, batch: FsChangeBatch) {
+        if let Some(pending) = &mut self.pending {
+            pending.push(batch);
+            return;
+        }
+        if self.stale {
+            return;
+        }
+        if batch.overflow || changes_ignore_rules(&batch) {
+            self.stale = true;
+            return;
+        }
+        let mut ignore = None;
+        for path in &batch.removed {
+            self.remove(root, Path::new(path));
+        }
+        for renamed in &batch.renamed {
+            self.remove(root, Path::new(&renamed.from));
+            self.add(root, Path::new(&renamed.to), &mut ignore);
+        }
+        for path in &batch.created {
+            self.add(root, Path::new(path), &mut ignore);
+        }
+    }
+
+    /// Add a file, or a directory and everything in it.
+    fn add(&mut self, root: &Path, path: &Path, ignore: &mut Option<IgnoreMatcher>) {
+        let Ok(meta) = path.symlink_metadata() else {
+            return;
+        };
+        if !meta.is_dir() && !meta.is_file() {
+            return;
+        }
+        let ignore = ignore.get_or_insert_with(|| IgnoreMatcher::new(root));
+        if ignore.is_ignored_within(path, meta.is_dir()) {
+            return;
+        }
+        if self.paths.len() >= MAX_INDEXED_FILES {
+            self.truncated = true;
+            return;
+        }
+        let Some(rel) = relative(root, path) else {
+            return;
+        };
+        self.paths.insert(rel, meta.is_dir());
+        if meta.is_dir() {
+            self.truncated |= walk_entries(root, path, ignore, &mut self.paths);
+        }
+    }
+
+    /// Remove a file, or everything under a directory.
+    fn remove(&mut self, root: &Path, path: &Path) {
+        let Some(rel) = relative(root, path) else {
+            return;
+        };
+        self.paths.remove(&rel);
+        let prefix = format!("{}/", rel);
+        let inside: Vec<String> = self
+            .paths
+            .range(prefix.clone()..)
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for path in inside {
+            self.paths.remove(&path);
+        }
+    }
+}
+
+fn changes_ignore_rules(batch: &FsChangeBatch) -> bool {
+    let renamed = batch.renamed.iter().flat_map(|r| [&r.from, &r.to]);
+    batch
+        .created
+        .iter()
+        .chain(&batch.removed)
+        .chain(&batch.modified)
+        .chain(renamed)
+        .any(|path| {
+            Path::new(path)
+                .file_name()
+                .is_some_and(|name| name == ".gitignore" || name == TERMINAL_IGNORE_FILE)
+        })
+}
+
+/// `path` below `root` with `/` separators.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let rel = rel.to_string_lossy().replace('\\', "/");
+    (!rel.is_empty()).then_some(rel)
+}
+
+/// Add the files and directories under `dir` that aren't ignored, the way
+/// `read_directory_recursive` walks. Returns whether the index filled up.
+fn walk_entries(root: &Path, dir: &Path, ignore: &mut IgnoreMatcher, paths: &mut BTreeMap<String, bool>) -> bool {
+    let mut walker = WalkDir::new(dir).follow_links(false).into_iter();
+    while let Some(entry) = walker.next() {
+        let e = match entry {
+            Ok(e) => e,
+            Err(err) => {
+                log::warn!(target: "fs", "Failed to read entry: {}", err);
+                continue;
+            }
+        };
+        if e.depth() == 0 || e.path_is_symlink() {
+            continue;
+        }
+        let is_dir = e.file_type().is_dir();
+        if (is_dir && is_hidden_system_dir(&e)) || ignore.is_ignored(e.path(), is_dir) {
+            if is_dir {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+        if paths.len() >= MAX_INDEXED_FILES {
+            return true;
+        }
+        if let Some(rel) = relative(root, e.path()) {
+            paths.insert(rel, is_dir);
+        }
+    }
+    false
+}
+
+/// Merge matched char indices of `text` into UTF-16 `[start, end)` ranges.
+fn utf16_ranges(text: &str, positions: &[usize]) -> Vec<[usize; 2]> {
+    let mut ranges: Vec<[usize; 2]> = Vec::new();
+    let mut wanted = positions.iter().peekable();
+    let mut offset = 0;
+    for (i, c) in text.chars().enumerate() {
+        let len = c.len_utf16();
+        if wanted.peek() == Some(&&i) {
+            wanted.next();
+            match ranges.last_mut() {
+                Some(last) if last[1] == offset => last[1] += len,
+                _ => ranges.push([offset, offset + len]),
+            }
+        }
+        offset += len;
+    }
+    ranges
+}
+
+fn recent_bonus(rank: usize) -> i32 {
+    RECENT_BONUS * (MAX_RECENT.saturating_sub(rank)) as i32 / MAX_RECENT as i32
+}
+
+/// The best `limit` files and directories under `root` for `query`. An
+/// empty query lists recently opened files. With a `policy`, paths it denies
+/// are skipped before ranking, so they don't take up the limit.
+pub fn find_files(
+    state: &AppState,
+    root: &Path,
+    query: &str,
+    limit: Option<usize>,
+    policy: Option<&AccessPolicy>,
+) -> AppResult<FindResult> {
+    let canonical = root
+        .canonicalize()
+        .map_err(|e| AppError::io("Cannot search directory", &e).with_path(root))?;
+    if !canonical.is_dir() {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Not a directory").with_path(root));
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let index = state.file_indexes.index(&canonical)?;
+    index.refresh()?;
+
+    let recent: HashMap<String, usize> = state
+        .file_indexes
+        .recent
+        .lock()
+        .map_err(AppError::lock)?
+        .iter()
+        .enumerate()
+        .filter_map(|(rank, path)| relative(&canonical, path).map(|rel| (rel, rank)))
+        .collect();
+    let pattern = Pattern::new(query);
+
+    let files = index.files.lock().map_err(AppError::lock)?;
+    // Worst of the best `limit` on top; ties go to shorter paths, then by name
+    let mut best = BinaryHeap::new();
+    let mut consider = |rel: &str, score: i32, positions: Vec<usize>| {
+        if policy.is_some_and(|policy| policy.is_denied(&canonical.join(rel))) {
+            return;
+        }
+        best.push(Reverse((score, Reverse(rel.len()), Reverse(rel.to_string()), positions)));
+        if best.len() > limit {
+            best.pop();
+        }
+    };
+    if pattern.is_empty() {
+        for (rel, &rank) in &recent {
+            if files.paths.contains_key(rel) {
+                consider(rel, recent_bonus(rank), Vec::new());
+            }
+        }
+    } else {
+        for rel in files.paths.keys() {
+            if let Some(found) = pattern.matches(rel) {
+                let bonus = recent.get(rel).map_or(0, |&rank| recent_bonus(rank));
+                consider(rel, found.score + bonus, found.positions);
+            }
+        }
+    }
+    let matches: Vec<FileMatch> = best
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((score, _, Reverse(rel), positions))| FileMatch {
+            path: root.join(&rel).to_string_lossy().to_string(),
+            is_dir: files.paths.get(&rel).copied().unwrap_or(false),
+            highlights: utf16_ranges(&rel, &positions),
+            recent: recent.contains_key(&rel),
+            relative_path: rel,
+            score,
+        })
+        .collect();
+    let (indexed, truncated) = (files.paths.len(), files.truncated);
+    drop(files);
+    Ok(FindResult {
+        root: root.to_string_lossy().to_string(),
+        matches,
+        indexed,
+        truncated,
+    })
+}
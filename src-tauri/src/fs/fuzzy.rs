@@ -0,0 +1,183 @@
+//! Fuzzy matching for `find_files`, in the style of fzf.
+//!
+//! A term matches when its characters appear in the path in order. Of all
+//! the ways to line them up, the one with the most bonus wins: characters at
+//! the start of a path segment score highest, then word boundaries
+//! (`-`, `_`, `.`, camelCase) and runs of consecutive characters, and
+//! characters in the file name get a little extra. Gaps between matched
+//! characters cost a little. Matching is case-insensitive unless the term
+//! has an uppercase letter.
+
+const SCORE_MATCH: i32 = 16;
+const GAP_START: i32 = -3;
+const GAP_EXTENSION: i32 = -1;
+/// At the start of the path or right after a `/`
+const BONUS_SEGMENT: i32 = 10;
+/// After `-`, `_`, `.` or a space, or an uppercase letter after a lowercase one
+const BONUS_BOUNDARY: i32 = 8;
+/// As much as a word boundary, so a run beats the same letters scattered
+const BONUS_CONSECUTIVE: i32 = 8;
+/// Per character matched in the last path segment
+const BONUS_FILE_NAME: i32 = 4;
+/// The term's first character counts its position bonus this many times
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+/// Longest term matched; longer terms are cut
+const MAX_TERM_CHARS: usize = 64;
+
+const NONE: i32 = i32::MIN / 2;
+
+/// Score and matched character indices of a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FuzzyMatch {
+    pub score: i32,
+    /// Indices into the path's chars, ascending and unique
+    pub positions: Vec<usize>,
+}
+
+/// A whitespace-separated query; every term has to match.
+pub(crate) struct Pattern {
+    terms: Vec<Term>,
+}
+
+struct Term {
+    chars: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Pattern {
+    pub fn new(query: &str) -> Self {
+        let terms = query
+            .split_whitespace()
+            .map(|term| Term {
+                chars: term.chars().take(MAX_TERM_CHARS).collect(),
+                case_sensitive: term.chars().any(char::is_uppercase),
+            })
+            .collect();
+        Self { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Match `path`, or `None` if some term doesn't.
+    pub fn matches(&self, path: &str) -> Option<FuzzyMatch> {
+        let chars: Vec<char> = path.chars().collect();
+        let lower: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+        let name_start = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+        let mut total = FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        };
+        for term in &self.terms {
+            let text = if term.case_sensitive { &chars } else { &lower };
+            let found = match_term(&term.chars, text, &chars, name_start)?;
+            total.score += found.score;
+            total.positions.extend(found.positions);
+        }
+        total.positions.sort_unstable();
+        total.positions.dedup();
+        Some(total)
+    }
+}
+
+/// Lowercase a char without changing how many chars there are.
+fn fold(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// Position bonus for `chars[i]`, from the char before it.
+fn bonus(chars: &[char], i: usize) -> i32 {
+    let Some(&prev) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_SEGMENT;
+    };
+    let current = chars[i];
+    match prev {
+        '/' | '\\' => BONUS_SEGMENT,
+        '-' | '_' | '.' | ' ' => BONUS_BOUNDARY,
+        _ if prev.is_lowercase() && current.is_uppercase() => BONUS_BOUNDARY,
+        _ if !prev.is_alphanumeric() && current.is_alphanumeric() => BONUS_BOUNDARY,
+        _ => 0,
+    }
+}
+
+/// Best alignment of `term` in `text` (the path, case-folded or not).
+/// `original` is the path as written, for the boundary bonuses.
+fn match_term(term: &[char], text: &[char], original: &[char], name_start: usize) -> Option<FuzzyMatch> {
+    let m = term.len();
+    let mut chars = term.iter().peekable();
+    for c in text {
+        if chars.peek() == Some(&c) {
+            chars.next();
+        }
+    }
+    if chars.peek().is_some() {
+        return None;
+    }
+    if m == 0 {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    // Every alignment lies between the first occurrence of the term's first
+    // char and the last occurrence of its last char
+    let start = text.iter().position(|&c| c == term[0])?;
+    let end = text.iter().rposition(|&c| c == term[m - 1])? + 1;
+    let window = &text[start..end];
+    let w = window.len();
+
+    // score[i * w + j]: best with term[i] matched at window[j], and the
+    // window index term[i - 1] was matched at on that alignment
+    let mut score = vec![NONE; m * w];
+    let mut from = vec![0usize; m * w];
+    for i in 0..m {
+        // Best earlier match at j - 2 or before, gap penalties included
+        let mut gap = (NONE, 0);
+        for j in 0..w {
+            if i > 0 && j >= 2 {
+                let opened = score[(i - 1) * w + j - 2] + GAP_START;
+                let extended = gap.0 + GAP_EXTENSION;
+                gap = if opened >= extended { (opened, j - 2) } else { (extended, gap.1) };
+            }
+            if window[j] != term[i] {
+                continue;
+            }
+            let at = start + j;
+            let position = bonus(original, at);
+            let base = SCORE_MATCH
+                + if i == 0 { position * FIRST_CHAR_MULTIPLIER } else { position }
+                + if at >= name_start { BONUS_FILE_NAME } else { 0 };
+            if i == 0 {
+                score[j] = base;
+                continue;
+            }
+            let run = if j >= 1 { score[(i - 1) * w + j - 1] + BONUS_CONSECUTIVE } else { NONE };
+            let (prev, k) = if run >= gap.0 { (run, j.saturating_sub(1)) } else { gap };
+            if prev > NONE / 2 {
+                score[i * w + j] = base + prev;
+                from[i * w + j] = k;
+            }
+        }
+    }
+
+    let last = (m - 1) * w;
+    let (best_j, &best) = score[last..last + w]
+        .iter()
+        .enumerate()
+        .max_by_key(|&(j, s)| (*s, std::cmp::Reverse(j)))?;
+    if best <= NONE / 2 {
+        return None;
+    }
+    let mut positions = vec![0; m];
+    let mut j = best_j;
+    for i in (0..m).rev() {
+        positions[i] = start + j;
+        j = from[i * w + j];
+    }
+    Some(FuzzyMatch { score: best, positions })
+}
@@ -1,8 +1,9 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use std::process::Command;
 use serde::Serialize;
+use crate::error::{AppError, AppResult, ErrorCode};
 use crate::state::AppState;
 
 #[derive(Serialize, Clone, Debug)]
@@ -25,8 +26,8 @@ pub struct GitDiffResult {
 }
 
 /// Find the git repository root by walking up the directory tree
-fn find_git_root(start_path: &PathBuf) -> Option<PathBuf> {
-    let mut current = start_path.clone();
+pub(crate) fn find_git_root(start_path: &Path) -> Option<PathBuf> {
+    let mut current = start_path.to_path_buf();
     loop {
         let git_dir = current.join(".git");
         if git_dir.exists() {
@@ -38,7 +39,8 @@ fn find_git_root(start_path: &PathBuf) -> Option<PathBuf> {
     }
 }
 
-fn get_git_diff_stats(repo_path: &PathBuf) -> Result<HashMap<String, GitStats>, String> {
+/// Per-file diff stats against HEAD plus untracked files, without caching.
+pub fn git_diff_stats(repo_path: &Path) -> AppResult<HashMap<String, GitStats>> {
     let git_root = match find_git_root(repo_path) {
         Some(root) => root,
         None => return Ok(HashMap::new()),
@@ -53,7 +55,7 @@ fn get_git_diff_stats(repo_path: &PathBuf) -> Result<HashMap<String, GitStats>,
         .arg("--numstat")
         .current_dir(&git_root)
         .output()
-        .map_err(|e| format!("Failed to execute git command: {}", e))?;
+        .map_err(|e| AppError::io("Failed to execute git command", &e).with_path(&git_root))?;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -90,7 +92,7 @@ fn get_git_diff_stats(repo_path: &PathBuf) -> Result<HashMap<String, GitStats>,
         .arg("--exclude-standard")
         .current_dir(&git_root)
         .output()
-        .map_err(|e| format!("Failed to execute git ls-files command: {}", e))?;
+        .map_err(|e| AppError::io("Failed to execute git ls-files command", &e).with_path(&git_root))?;
 
     if untracked_output.status.success() {
         let stdout = String::from_utf8_lossy(&untracked_output.stdout);
@@ -128,79 +130,50 @@ fn get_git_diff_stats(repo_path: &PathBuf) -> Result<HashMap<String, GitStats>,
     Ok(stats_map)
 }
 
-#[tauri::command]
-pub fn get_git_stats(path: Option<String>, state: tauri::State<AppState>) -> Result<HashMap<String, GitStats>, String> {
-    let repo_path = if let Some(p) = path {
-        PathBuf::from(p)
-    } else {
-        std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?
-    };
-
+/// Per-file diff stats for the repository containing `repo_path`, served from
+/// the state's git cache when fresh.
+pub fn git_stats(state: &AppState, repo_path: &Path) -> AppResult<HashMap<String, GitStats>> {
     // Canonicalize path for consistent cache keys
     let canonical_path = repo_path.canonicalize()
-        .unwrap_or_else(|_| repo_path.clone());
+        .unwrap_or_else(|_| repo_path.to_path_buf());
 
     // Try cache first
-    {
-        let state_lock = state
-            .lock()
-            .map_err(|e| format!("Failed to lock state: {}", e))?;
-
-        if let Some(cached_stats) = state_lock.git_cache.get(&canonical_path) {
-            // Clone from Arc - cheap if caller just reads, necessary for Tauri serialization
-            return Ok((*cached_stats).clone());
-        }
+    if let Some(cached_stats) = state.git_cache.get(&canonical_path) {
+        // Clone from Arc - cheap if caller just reads, necessary for Tauri serialization
+        return Ok((*cached_stats).clone());
     }
 
-    // Cache miss - run git diff
-    let stats = get_git_diff_stats(&repo_path)?;
+    // Cache miss - run git diff. The cache synchronizes internally, so no
+    // lock is held while git runs.
+    let stats = git_diff_stats(repo_path)?;
 
     // Store in cache and setup watcher
-    {
-        let state_lock = state
-            .lock()
-            .map_err(|e| format!("Failed to lock state: {}", e))?;
-
-        state_lock.git_cache.set(canonical_path.clone(), stats.clone());
+    state.git_cache.set(canonical_path.clone(), stats.clone());
 
-        // Try to setup watcher (best-effort, ignore errors)
-        let _ = state_lock.git_cache.setup_watcher(canonical_path);
-    }
+    // Try to setup watcher (best-effort, ignore errors)
+    let _ = state.git_cache.setup_watcher(canonical_path);
 
     Ok(stats)
 }
 
 #[tauri::command]
-pub fn enable_file_watchers(state: tauri::State<AppState>) -> Result<(), String> {
-    let state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-
-    state_lock.git_cache.enable_watchers();
+pub fn enable_file_watchers(state: tauri::State<AppState>) -> AppResult<()> {
+    state.git_cache.enable_watchers();
     Ok(())
 }
 
 #[tauri::command]
-pub fn disable_file_watchers(state: tauri::State<AppState>) -> Result<(), String> {
-    let state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-
-    state_lock.git_cache.disable_watchers();
+pub fn disable_file_watchers(state: tauri::State<AppState>) -> AppResult<()> {
+    state.git_cache.disable_watchers();
     Ok(())
 }
 
 #[tauri::command]
-pub fn get_file_watchers_status(state: tauri::State<AppState>) -> Result<bool, String> {
-    let state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-
-    Ok(state_lock.git_cache.is_enabled())
+pub fn get_file_watchers_status(state: tauri::State<AppState>) -> AppResult<bool> {
+    Ok(state.git_cache.is_enabled())
 }
 
-#[tauri::command]
-pub fn get_current_branch(repo_path: String) -> Result<Option<String>, String> {
+pub fn get_current_branch(repo_path: String) -> AppResult<Option<String>> {
     let repo = PathBuf::from(&repo_path);
     let git_dir = repo.join(".git");
     if !git_dir.exists() {
@@ -209,7 +182,7 @@ pub fn get_current_branch(repo_path: String) -> Result<Option<String>, String> {
 
     let head_path = git_dir.join("HEAD");
     let head_content = std::fs::read_to_string(&head_path)
-        .map_err(|e| format!("Failed to read HEAD: {}", e))?;
+        .map_err(|e| AppError::io("Failed to read HEAD", &e).with_path(&head_path))?;
     let trimmed = head_content.trim();
 
     let branch = trimmed
@@ -219,21 +192,20 @@ pub fn get_current_branch(repo_path: String) -> Result<Option<String>, String> {
     Ok(branch)
 }
 
-#[tauri::command]
-pub fn get_git_diff(file_path: String, repo_path: String) -> Result<GitDiffResult, String> {
+pub fn get_git_diff(file_path: String, repo_path: String) -> AppResult<GitDiffResult> {
     let repo = PathBuf::from(&repo_path);
     let file = PathBuf::from(&file_path);
 
     // Check if .git directory exists
     let git_dir = repo.join(".git");
     if !git_dir.exists() {
-        return Err("Not a git repository".to_string());
+        return Err(AppError::not_a_git_repository(&repo));
     }
 
     // Calculate relative path from repo root
     let relative_path = if file.starts_with(&repo) {
         file.strip_prefix(&repo)
-            .map_err(|e| format!("Failed to get relative path: {}", e))?
+            .map_err(|e| AppError::new(ErrorCode::InvalidInput, format!("Failed to get relative path: {}", e)).with_path(&file))?
             .to_string_lossy()
             .to_string()
     } else {
@@ -246,7 +218,7 @@ pub fn get_git_diff(file_path: String, repo_path: String) -> Result<GitDiffResul
         .arg(format!("HEAD:{}", relative_path))
         .current_dir(&repo)
         .output()
-        .map_err(|e| format!("Failed to execute git show: {}", e))?;
+        .map_err(|e| AppError::io("Failed to execute git show", &e).with_path(&repo))?;
 
     let is_new_file = !old_output.status.success();
     let old_content = if is_new_file {
@@ -258,7 +230,7 @@ pub fn get_git_diff(file_path: String, repo_path: String) -> Result<GitDiffResul
     // Get new content from disk (current working tree)
     let new_content = if file.exists() {
         fs::read_to_string(&file)
-            .map_err(|e| format!("Failed to read file: {}", e))?
+            .map_err(|e| AppError::io("Failed to read file", &e).with_path(&file))?
     } else {
         String::new()
     };
@@ -274,7 +246,7 @@ pub fn get_git_diff(file_path: String, repo_path: String) -> Result<GitDiffResul
         .arg(&relative_path)
         .current_dir(&repo)
         .output()
-        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+        .map_err(|e| AppError::io("Failed to execute git diff", &e).with_path(&repo))?;
 
     let numstat = String::from_utf8_lossy(&numstat_output.stdout);
     let (added_lines, deleted_lines) = numstat
@@ -0,0 +1,485 @@
+//! Gitignore-style matching for the directory walkers.
+//!
+//! Inside a git repository the sources are, lowest precedence first: the
+//! global excludes file (`core.excludesFile`, else
+//! `$XDG_CONFIG_HOME/git/ignore`), `.git/info/exclude`, then `.gitignore` in
+//! each directory from the repo root down. `.terminalignore` files use the
+//! same syntax, are read in every directory too, and override all git
+//! sources, so a project can hide or re-include (`!build/`) entries just for
+//! the file tree. Outside a repository a small list of usual build and
+//! dependency dirs stands in for `.gitignore`. The last matching rule wins,
+//! as in git.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+use regex::Regex;
+use crate::fs::find_git_root;
+
+pub const TERMINAL_IGNORE_FILE: &str = ".terminalignore";
+
+/// Used when the tree is not in a git repository.
+const DEFAULT_IGNORES: &str = "node_modules/\ntarget/\n.cache/\n.next/\n.nuxt/\n__pycache__/\n.venv/\nvenv/\n";
+
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// The rules of one ignore file, matched relative to `base`.
+struct RuleSet {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    fn parse(base: &Path, text: &str) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            rules: text.lines().filter_map(parse_rule).collect(),
+        }
+    }
+
+    fn load(base: &Path, file: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(file).ok()?;
+        let set = Self::parse(base, &text);
+        (!set.rules.is_empty()).then_some(set)
+    }
+
+    /// `Some(true)` if the last matching rule ignores `path`, `Some(false)` if
+    /// it re-includes it, `None` if no rule matches.
+    fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rel = path.strip_prefix(&self.base).ok()?;
+        let rel = rel.to_string_lossy().replace('\\', "/");
+        if rel.is_empty() {
+            return None;
+        }
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&rel))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Gitignore-style globs for include and exclude filters, matched against
+/// paths below `base`. A later `!glob` takes back an earlier one.
+pub(crate) struct GlobList(RuleSet);
+
+impl GlobList {
+    pub fn new(base: &Path, globs: &[String]) -> Self {
+        Self(RuleSet::parse(base, &globs.join("\n")))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.rules.is_empty()
+    }
+
+    /// Whether the last glob matching `path` selects it.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        self.0.decide(path, is_dir) == Some(true)
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    // Trailing spaces are dropped unless escaped
+    let mut line = line.to_string();
+    while line.ends_with(' ') && !line.ends_with("\\ ") {
+        line.pop();
+    }
+    let (negated, mut pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest.to_string()),
+        None => (false, line),
+    };
+    if pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+        pattern.remove(0);
+    }
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    if pattern.is_empty() {
+        return None;
+    }
+    // A slash anywhere but the end anchors the pattern to the file's dir
+    let anchored = pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    let body = glob_to_regex(pattern);
+    let regex = if anchored {
+        format!("^{}$", body)
+    } else {
+        format!("^(?:.*/)?{}$", body)
+    };
+    Some(Rule {
+        regex: Regex::new(&regex).ok()?,
+        negated,
+        dir_only,
+    })
+}
+
+/// Translate a gitignore glob (`*`, `?`, `[...]`, `**`) into a regex body
+/// matching `/`-separated relative paths.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut out = String::with_capacity(glob.len() * 2);
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let at_end = i + 2 == chars.len();
+                let before_slash = chars.get(i + 2) == Some(&'/');
+                if at_start && before_slash {
+                    // `**/`: any number of leading directories
+                    out.push_str("(?:.*/)?");
+                    i += 3;
+                } else if at_start && at_end {
+                    // trailing `/**`: everything inside
+                    out.push_str(".*");
+                    i += 2;
+                } else {
+                    out.push_str("[^/]*");
+                    i += 2;
+                }
+            }
+            '*' => {
+                out.push_str("[^/]*");
+                i += 1;
+            }
+            '?' => {
+                out.push_str("[^/]");
+                i += 1;
+            }
+            '[' => match chars[i + 1..].iter().position(|c| *c == ']') {
+                Some(len) if len > 0 => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{}", rest),
+                        None => class,
+                    };
+                    out.push('[');
+                    out.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    out.push(']');
+                    i += len + 2;
+                }
+                _ => {
+                    out.push_str("\\[");
+                    i += 1;
+                }
+            },
+            '\\' if i + 1 < chars.len() => {
+                out.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            c => {
+                out.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Git's global excludes file, if it exists. Every walker and watcher builds
+/// a matcher, so the setting is looked up once per repository; a changed
+/// `core.excludesFile` is picked up on restart.
+fn global_excludes_file(repo: &Path) -> Option<PathBuf> {
+    static CONFIGURED: OnceLock<Mutex<HashMap<PathBuf, Option<PathBuf>>>> = OnceLock::new();
+    let cache = CONFIGURED.get_or_init(Default::default);
+    let cached = cache.lock().ok().and_then(|cache| cache.get(repo).cloned());
+    let path = match cached {
+        Some(path) => path,
+        None => {
+            let path = excludes_file_setting(repo);
+            if let Ok(mut cache) = cache.lock() {
+                cache.insert(repo.to_path_buf(), path.clone());
+            }
+            path
+        }
+    }?;
+    path.is_file().then_some(path)
+}
+
+/// Where git looks for the global excludes file in `repo`.
+fn excludes_file_setting(repo: &Path) -> Option<PathBuf> {
+    let configured = Command::new("git")
+        .args(["config", "--path", "--get", "core.excludesFile"])
+        .current_dir(repo)
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| PathBuf::from(String::from_utf8_lossy(&out.stdout).trim()));
+    configured.or_else(|| {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("git").join("ignore"))
+    })
+}
+
+/// The ignore files found in one directory.
+#[derive(Clone, Default)]
+struct DirRules {
+    git: Option<Arc<RuleSet>>,
+    terminal: Option<Arc<RuleSet>>,
+}
+
+/// Ignore rules for one tree. Per-directory files are read on first use
+/// and cached for the matcher's lifetime.
+pub struct IgnoreMatcher {
+    /// Repository root, or the walked root outside a repository
+    top: PathBuf,
+    in_repo: bool,
+    /// Global excludes and `info/exclude`, or the defaults
+    base: Vec<RuleSet>,
+    dirs: HashMap<PathBuf, DirRules>,
+}
+
+impl IgnoreMatcher {
+    /// Rules for entries under `root`.
+    pub fn new(root: &Path) -> Self {
+        match find_git_root(root) {
+            Some(repo) => {
+                let mut base = Vec::new();
+                if let Some(file) = global_excludes_file(&repo) {
+                    base.extend(RuleSet::load(&repo, &file));
+                }
+                base.extend(RuleSet::load(&repo, &repo.join(".git").join("info").join("exclude")));
+                Self {
+                    top: repo,
+                    in_repo: true,
+                    base,
+                    dirs: HashMap::new(),
+                }
+            }
+            None => Self {
+                top: root.to_path_buf(),
+                in_repo: false,
+                base: vec![RuleSet::parse(root, DEFAULT_IGNORES)],
+                dirs: HashMap::new(),
+            },
+        }
+    }
+
+    fn dir_rules(&mut self, dir: &Path) -> DirRules {
+        if let Some(rules) = self.dirs.get(dir) {
+            return rules.clone();
+        }
+        let rules = DirRules {
+            git: if self.in_repo {
+                RuleSet::load(dir, &dir.join(".gitignore")).map(Arc::new)
+            } else {
+                None
+            },
+            terminal: RuleSet::load(dir, &dir.join(TERMINAL_IGNORE_FILE)).map(Arc::new),
+        };
+        self.dirs.insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+
+    /// Whether `path` itself matches the rules. Doesn't look at whether a
+    /// parent directory is ignored; walkers carry that down themselves.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let Ok(rel) = path.strip_prefix(&self.top) else {
+            return false;
+        };
+
+        // Directories from the top down to the entry's parent
+        let mut dirs = vec![self.top.clone()];
+        let mut current = self.top.clone();
+        if let Some(parent) = rel.parent() {
+            for part in parent.components() {
+                current.push(part);
+                dirs.push(current.clone());
+            }
+        }
+
+        let mut ignored = self
+            .base
+            .iter()
+            .filter_map(|set| set.decide(path, is_dir))
+            .next_back()
+            .unwrap_or(false);
+        let mut terminal_sets = Vec::new();
+        for dir in &dirs {
+            let rules = self.dir_rules(dir);
+            if let Some(decision) = rules.git.and_then(|set| set.decide(path, is_dir)) {
+                ignored = decision;
+            }
+            terminal_sets.extend(rules.terminal);
+        }
+        for set in terminal_sets {
+            if let Some(decision) = set.decide(path, is_dir) {
+                ignored = decision;
+            }
+        }
+        ignored
+    }
+
+    /// Whether `path` or any directory between the tree's top and it is
+    /// ignored.
+    pub fn is_ignored_within(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(rel) = path.strip_prefix(&self.top) else {
+            return false;
+        };
+        let mut current = self.top.clone();
+        let parts: Vec<_> = rel.components().collect();
+        for (i, part) in parts.iter().enumerate() {
+            current.push(part);
+            let last = i + 1 == parts.len();
+            if self.is_ignored(&current, if last { is_dir } else { true }) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob_matches(glob: &str, path: &str) -> bool {
+        Regex::new(&format!("^{}$", glob_to_regex(glob))).unwrap().is_match(path)
+    }
+
+    /// `Some(true)` ignored, `Some(false)` re-included, `None` no rule matched.
+    fn decide(rules: &str, path: &str, is_dir: bool) -> Option<bool> {
+        RuleSet::parse(Path::new("/r"), rules).decide(&Path::new("/r").join(path), is_dir)
+    }
+
+    #[test]
+    fn glob_to_regex_table() {
+        let cases = [
+            ("*.rs", "main.rs", true),
+            ("*.rs", "src/main.rs", false),
+            ("?.txt", "a.txt", true),
+            ("?.txt", "ab.txt", false),
+            ("?", "/", false),
+            ("[abc].js", "b.js", true),
+            ("[abc].js", "d.js", false),
+            ("[!abc].js", "d.js", true),
+            ("[!abc].js", "a.js", false),
+            ("[a-c]x", "bx", true),
+            ("[", "[", true),
+            ("a[]", "a[]", true),
+            ("\\*", "*", true),
+            ("\\*", "a", false),
+            ("a.b", "axb", false),
+            ("**/foo", "foo", true),
+            ("**/foo", "a/b/foo", true),
+            ("**/foo", "a/foo/b", false),
+            ("foo/**", "foo/a", true),
+            ("foo/**", "foo/a/b", true),
+            ("foo/**", "foo", false),
+            ("a/**/b", "a/b", true),
+            ("a/**/b", "a/x/y/b", true),
+            ("a/**/b", "a/xb", false),
+            ("a**b", "axxb", true),
+            ("a**b", "a/b", false),
+        ];
+        for (glob, path, expected) in cases {
+            assert_eq!(glob_matches(glob, path), expected, "{:?} against {:?}", glob, path);
+        }
+    }
+
+    #[test]
+    fn parse_rule_skips_blank_and_comment_lines() {
+        let cases = ["", "#comment", "/", "!", "   "];
+        for line in cases {
+            assert!(parse_rule(line).is_none(), "{:?}", line);
+        }
+        assert!(parse_rule("\\#file").is_some_and(|r| !r.negated && r.regex.is_match("#file")));
+        assert!(parse_rule("\\!file").is_some_and(|r| !r.negated && r.regex.is_match("!file")));
+        assert!(parse_rule("trailing\\ ").is_some_and(|r| r.regex.is_match("trailing ")));
+        assert!(parse_rule("spaced   ").is_some_and(|r| r.regex.is_match("spaced")));
+    }
+
+    #[test]
+    fn anchoring() {
+        let cases = [
+            // No slash: matches at any depth
+            ("build", "build", Some(true)),
+            ("build", "a/b/build", Some(true)),
+            // Leading or middle slash: relative to the file's directory
+            ("/build", "build", Some(true)),
+            ("/build", "a/build", None),
+            ("doc/*.md", "doc/a.md", Some(true)),
+            ("doc/*.md", "x/doc/a.md", None),
+            ("doc/*.md", "doc/sub/a.md", None),
+        ];
+        for (rules, path, expected) in cases {
+            assert_eq!(decide(rules, path, false), expected, "{:?} against {:?}", rules, path);
+        }
+    }
+
+    #[test]
+    fn dir_only() {
+        let cases = [
+            ("out/", "out", true, Some(true)),
+            ("out/", "out", false, None),
+            ("out/", "a/out", true, Some(true)),
+            ("/out/", "a/out", true, None),
+            ("out", "out", false, Some(true)),
+        ];
+        for (rules, path, is_dir, expected) in cases {
+            assert_eq!(decide(rules, path, is_dir), expected, "{:?} against {:?} (dir: {})", rules, path, is_dir);
+        }
+    }
+
+    #[test]
+    fn negation_last_match_wins() {
+        let cases = [
+            ("*.log\n!keep.log", "keep.log", Some(false)),
+            ("*.log\n!keep.log", "other.log", Some(true)),
+            ("!keep.log\n*.log", "keep.log", Some(true)),
+            ("build/\n!build/", "build", Some(false)),
+            ("*\n!*.rs", "main.rs", Some(false)),
+            ("*\n!*.rs", "main.js", Some(true)),
+        ];
+        for (rules, path, expected) in cases {
+            let is_dir = !path.contains('.');
+            assert_eq!(decide(rules, path, is_dir), expected, "{:?} against {:?}", rules, path);
+        }
+    }
+
+    #[test]
+    fn double_star_rules() {
+        let cases = [
+            ("**/logs", "logs", Some(true)),
+            ("**/logs", "deep/er/logs", Some(true)),
+            ("logs/**", "logs/a/b.txt", Some(true)),
+            ("logs/**", "x/logs/a.txt", None),
+            ("a/**/z", "a/b/c/z", Some(true)),
+            ("a/**/z", "b/a/z", None),
+        ];
+        for (rules, path, expected) in cases {
+            assert_eq!(decide(rules, path, false), expected, "{:?} against {:?}", rules, path);
+        }
+    }
+
+    #[test]
+    fn decide_outside_base() {
+        let set = RuleSet::parse(Path::new("/r/sub"), "*.tmp");
+        assert_eq!(set.decide(Path::new("/r/other/a.tmp"), false), None);
+        assert_eq!(set.decide(Path::new("/r/sub"), true), None);
+        assert_eq!(set.decide(Path::new("/r/sub/x/a.tmp"), false), Some(true));
+    }
+
+    #[test]
+    fn glob_list() {
+        let base = Path::new("/r");
+        let list = GlobList::new(base, &["src/**".to_string(), "!src/gen/**".to_string()]);
+        assert!(!list.is_empty());
+        assert!(list.matches(Path::new("/r/src/a.rs"), false));
+        assert!(!list.matches(Path::new("/r/src/gen/b.rs"), false));
+        assert!(!list.matches(Path::new("/r/docs/a.md"), false));
+        assert!(GlobList::new(base, &[]).is_empty());
+    }
+}
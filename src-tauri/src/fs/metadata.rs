@@ -0,0 +1,204 @@
+//! Optional per-entry metadata for directory listings.
+//!
+//! Listings only stat what the caller names in `fields`, and only read file
+//! contents for `file_type`, so a plain listing costs the same as before.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use crate::fs::content::detect_encoding;
+
+/// Bytes read from the start of a file to guess its type
+const SNIFF_LEN: usize = 512;
+
+/// Metadata a listing can include, named in its `fields` parameter.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataField {
+    /// `size`, in bytes
+    Size,
+    /// `modified`, in milliseconds since the Unix epoch
+    Modified,
+    /// `mode` (Unix only), `readonly` and `executable`
+    Permissions,
+    /// `is_symlink`, plus `symlink_target` and `broken_symlink` for links
+    Symlink,
+    /// `hidden`: a dot name, or the hidden attribute on Windows
+    Hidden,
+    /// `file_type`, sniffed from the file's first bytes
+    FileType,
+}
+
+/// What a file's first bytes look like.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    Text,
+    Binary,
+    Image,
+}
+
+/// Requested metadata for one entry, flattened into the entry. Fields that
+/// weren't asked for (or don't apply) are left out.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EntryMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readonly: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_symlink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken_symlink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<FileKind>,
+}
+
+/// The `fields` a listing was called with.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataFields(HashSet<MetadataField>);
+
+impl MetadataFields {
+    pub fn new(fields: Option<Vec<MetadataField>>) -> Self {
+        Self(fields.unwrap_or_default().into_iter().collect())
+    }
+
+    fn has(&self, field: MetadataField) -> bool {
+        self.0.contains(&field)
+    }
+
+    /// The requested metadata for `path`; empty when no fields were
+    /// requested. Sizes, times and permissions describe a link's target
+    /// when it resolves.
+    pub fn read(&self, path: &Path) -> EntryMetadata {
+        let mut out = EntryMetadata::default();
+        if self.0.is_empty() {
+            return out;
+        }
+        let link_meta = fs::symlink_metadata(path).ok();
+        let is_symlink = link_meta.as_ref().is_some_and(|m| m.file_type().is_symlink());
+        let target_meta = if is_symlink { fs::metadata(path).ok() } else { link_meta.clone() };
+        let meta = target_meta.as_ref().or(link_meta.as_ref());
+
+        if self.has(MetadataField::Size) {
+            out.size = meta.map(|m| m.len());
+        }
+        if self.has(MetadataField::Modified) {
+            out.modified = meta
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+        }
+        if self.has(MetadataField::Permissions) {
+            if let Some(meta) = meta {
+                out.readonly = Some(meta.permissions().readonly());
+                out.executable = Some(meta.is_file() && is_executable(path, meta));
+                out.mode = unix_mode(meta);
+            }
+        }
+        if self.has(MetadataField::Symlink) {
+            out.is_symlink = Some(is_symlink);
+            if is_symlink {
+                out.symlink_target = fs::read_link(path).ok().map(|t| t.to_string_lossy().to_string());
+                out.broken_symlink = Some(target_meta.is_none());
+            }
+        }
+        if self.has(MetadataField::Hidden) {
+            out.hidden = Some(is_hidden(path, link_meta.as_ref()));
+        }
+        if self.has(MetadataField::FileType) && meta.is_some_and(|m| m.is_file()) {
+            out.file_type = sniff_file(path);
+        }
+        out
+    }
+}
+
+#[cfg(unix)]
+fn unix_mode(meta: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_meta: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path, _meta: &fs::Metadata) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ["exe", "bat", "cmd", "com", "ps1"].contains(&e.to_ascii_lowercase().as_str()))
+}
+
+fn is_hidden(path: &Path, meta: Option<&fs::Metadata>) -> bool {
+    let dot = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+    dot || meta.is_some_and(has_hidden_attribute)
+}
+
+#[cfg(target_os = "windows")]
+fn has_hidden_attribute(meta: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(target_os = "windows"))]
+fn has_hidden_attribute(_meta: &fs::Metadata) -> bool {
+    false
+}
+
+fn sniff_file(path: &Path) -> Option<FileKind> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    fs::File::open(path)
+        .ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+    Some(sniff(&buf))
+}
+
+/// Classify a file from its first bytes: known image signatures, then
+/// whatever [`detect_encoding`] can't read as text is binary.
+pub(crate) fn sniff(head: &[u8]) -> FileKind {
+    const IMAGE_MAGIC: &[&[u8]] = &[
+        b"\x89PNG\r\n\x1a\n",
+        b"\xff\xd8\xff",
+        b"GIF87a",
+        b"GIF89a",
+        b"\x00\x00\x01\x00",
+        b"II*\x00",
+        b"MM\x00*",
+    ];
+    let webp = head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP";
+    // "BM", then the file size and four reserved zero bytes
+    let bmp = head.len() >= 10 && &head[..2] == b"BM" && head[6..10] == [0, 0, 0, 0];
+    if webp || bmp || IMAGE_MAGIC.iter().any(|magic| head.starts_with(magic)) {
+        return FileKind::Image;
+    }
+    match detect_encoding(head) {
+        Some(_) => FileKind::Text,
+        None => FileKind::Binary,
+    }
+}
@@ -1,16 +1,33 @@
 mod directory;
+mod content;
 mod cwd;
+mod finder;
+mod fuzzy;
 mod git;
-mod commands;
+pub(crate) mod commands;
+mod ignore;
+mod metadata;
+mod ops;
+mod policy;
+mod replace;
+mod search;
+mod trash;
+mod tree;
+mod watch;
 
-pub use directory::{read_directory, read_file_content, read_directory_recursive};
-pub use cwd::get_terminal_cwd;
-pub use git::{GitStats, get_git_stats, get_git_diff, get_current_branch, enable_file_watchers, disable_file_watchers, get_file_watchers_status};
+pub use directory::{DirectoryEntry, RecursiveDirectoryEntry, read_directory, read_directory_recursive};
+pub use content::{FileContent, ReadRange, TextEncoding, read_content, read_file_content};
+pub use finder::{find_files, FileIndexes, FileMatch, FindResult};
+pub use metadata::{EntryMetadata, FileKind, MetadataField};
+pub use ops::{AffectedPaths, copy_path, create_directory, create_file, delete_path, move_path, rename_path, write_file};
+pub use policy::{Access, AccessPolicy, PROTECTED_PATHS, SECRET_PATHS};
+pub use replace::{apply_replace, replace_in_files, undo_replace, FileReplacement, ReplaceApplied, ReplaceOptions, ReplacePreview, Replacements};
+pub use search::{cancel as cancel_search, start as start_search, FileMatches, LineMatch, SearchOptions, SearchSummary, Searcher, Searches};
+pub use trash::trash;
+pub use tree::{DirectoryPage, TreeEntry, read_directory_children};
+pub use watch::{watch as watch_tree, unwatch as unwatch_tree, DirectoryWatches, FsChangeBatch, RenamedPath};
+pub use cwd::{get_terminal_cwd, terminal_cwd};
+pub use git::{GitStats, GitDiffResult, git_stats, git_diff_stats, get_git_diff, get_current_branch, enable_file_watchers, disable_file_watchers, get_file_watchers_status};
 pub use commands::check_command_exists;
-
-fn home_dir() -> Option<String> {
-    #[cfg(unix)]
-    { std::env::var("HOME").ok() }
-    #[cfg(windows)]
-    { std::env::var("USERPROFILE").ok() }
-}
+pub(crate) use git::find_git_root;
+pub(crate) use policy::home_dir;
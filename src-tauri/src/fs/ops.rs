@@ -0,0 +1,353 @@
+//! File management for the tree: create, rename, move, copy, write and
+//! delete.
+//!
+//! Every operation returns the paths it touched, in the same shape as an
+//! `fs-changed` batch, so the tree can update without a reload. Nothing
+//! overwrites an existing entry; that fails with `already_exists`. Writes go
+//! to a temp file in the same directory that is renamed over the original,
+//! so readers never see a half-written file.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::trash::trash;
+use crate::fs::watch::RenamedPath;
+
+/// Paths an operation created, removed, modified or renamed.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct AffectedPaths {
+    pub created: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub renamed: Vec<RenamedPath>,
+    /// Where a trashed entry went
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trashed_to: Option<String>,
+}
+
+impl AffectedPaths {
+    /// The same changes with every path passed through `f`.
+    pub(crate) fn map_paths(self, f: impl Fn(&str) -> String) -> Self {
+        let map = |paths: Vec<String>| paths.iter().map(|p| f(p)).collect();
+        Self {
+            created: map(self.created),
+            removed: map(self.removed),
+            modified: map(self.modified),
+            renamed: self
+                .renamed
+                .into_iter()
+                .map(|r| RenamedPath {
+                    from: f(&r.from),
+                    to: f(&r.to),
+                })
+                .collect(),
+            trashed_to: self.trashed_to,
+        }
+    }
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn ensure_free(path: &Path) -> AppResult<()> {
+    if path.symlink_metadata().is_ok() {
+        return Err(AppError::new(ErrorCode::AlreadyExists, "Already exists").with_path(path));
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> AppResult<&std::ffi::OsStr> {
+    path.file_name()
+        .ok_or_else(|| AppError::new(ErrorCode::InvalidInput, "Path has no file name").with_path(path))
+}
+
+/// Create the missing ancestors of `path`, returning them top-most first.
+fn create_parents(path: &Path) -> AppResult<Vec<String>> {
+    let mut missing: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .collect();
+    missing.reverse();
+    for dir in &missing {
+        fs::create_dir(dir).map_err(|e| AppError::io("Failed to create directory", &e).with_path(dir))?;
+    }
+    Ok(missing.iter().map(|dir| path_string(dir)).collect())
+}
+
+/// Create a file, and any missing parent directories.
+pub fn create_file(path: &Path, content: Option<&str>) -> AppResult<AffectedPaths> {
+    ensure_free(path)?;
+    let mut created = create_parents(path)?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| AppError::io("Failed to create file", &e).with_path(path))?;
+    if let Some(content) = content {
+        file.write_all(content.as_bytes())
+            .map_err(|e| AppError::io("Failed to write file", &e).with_path(path))?;
+    }
+    created.push(path_string(path));
+    Ok(AffectedPaths {
+        created,
+        ..AffectedPaths::default()
+    })
+}
+
+/// Create a directory, and any missing parents.
+pub fn create_directory(path: &Path) -> AppResult<AffectedPaths> {
+    ensure_free(path)?;
+    let mut created = create_parents(path)?;
+    fs::create_dir(path).map_err(|e| AppError::io("Failed to create directory", &e).with_path(path))?;
+    created.push(path_string(path));
+    Ok(AffectedPaths {
+        created,
+        ..AffectedPaths::default()
+    })
+}
+
+/// Rename an entry within its directory.
+pub fn rename_path(path: &Path, new_name: &str) -> AppResult<AffectedPaths> {
+    if new_name.is_empty() || new_name == "." || new_name == ".." || new_name.contains(['/', '\\']) {
+        return Err(AppError::new(ErrorCode::InvalidInput, format!("Invalid name: {}", new_name)));
+    }
+    let parent = path
+        .parent()
+        .ok_or_else(|| AppError::new(ErrorCode::InvalidInput, "Cannot rename this path").with_path(path))?;
+    move_to(path, &parent.join(new_name))
+}
+
+/// Move an entry into `destination_dir`, keeping its name.
+pub fn move_path(source: &Path, destination_dir: &Path) -> AppResult<AffectedPaths> {
+    move_to(source, &move_target(source, destination_dir)?)
+}
+
+/// Where [`move_path`] puts `source`.
+pub(crate) fn move_target(source: &Path, destination_dir: &Path) -> AppResult<PathBuf> {
+    Ok(destination_dir.join(file_name(source)?))
+}
+
+/// Move `source` to exactly `target`.
+pub(crate) fn move_to(source: &Path, target: &Path) -> AppResult<AffectedPaths> {
+    if source == target {
+        return Ok(AffectedPaths::default());
+    }
+    let meta = source
+        .symlink_metadata()
+        .map_err(|e| AppError::io("Cannot move", &e).with_path(source))?;
+    if meta.is_dir() && within(target, source) {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Cannot move a directory into itself").with_path(target));
+    }
+    // A case-only rename names the same entry on case-insensitive systems
+    let same_entry = fs::canonicalize(target).ok() == fs::canonicalize(source).ok()
+        && path_string(target).to_lowercase() == path_string(source).to_lowercase();
+    if !same_entry {
+        ensure_free(target)?;
+    }
+
+    if let Err(e) = fs::rename(source, target) {
+        if !is_cross_device(&e) {
+            return Err(AppError::io("Failed to move", &e).with_path(source));
+        }
+        // Different filesystem: copy, then remove the original
+        copy_or_clean_up(source, target)?;
+        remove(source)?;
+    }
+    Ok(AffectedPaths {
+        renamed: vec![RenamedPath {
+            from: path_string(source),
+            to: path_string(target),
+        }],
+        ..AffectedPaths::default()
+    })
+}
+
+/// Whether `path` is `dir` or inside it, comparing resolved parents.
+fn within(path: &Path, dir: &Path) -> bool {
+    let resolve = |p: &Path| -> PathBuf {
+        match (p.parent().and_then(|parent| parent.canonicalize().ok()), p.file_name()) {
+            (Some(parent), Some(name)) => parent.join(name),
+            _ => p.to_path_buf(),
+        }
+    };
+    resolve(path).starts_with(resolve(dir))
+}
+
+fn is_cross_device(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        e.raw_os_error() == Some(libc::EXDEV)
+    }
+    #[cfg(not(unix))]
+    {
+        // ERROR_NOT_SAME_DEVICE
+        e.raw_os_error() == Some(17)
+    }
+}
+
+/// Copy an entry into `destination_dir`. Copying into the entry's own
+/// directory makes a duplicate named `name copy`, `name copy 2`, ...
+pub fn copy_path(source: &Path, destination_dir: &Path) -> AppResult<AffectedPaths> {
+    copy_to(source, &copy_target(source, destination_dir)?)
+}
+
+/// Where [`copy_path`] puts the copy of `source`.
+pub(crate) fn copy_target(source: &Path, destination_dir: &Path) -> AppResult<PathBuf> {
+    let name = file_name(source)?.to_string_lossy().to_string();
+    let target = destination_dir.join(&name);
+    let duplicate = source.parent().and_then(|p| p.canonicalize().ok()) == destination_dir.canonicalize().ok();
+    if !duplicate {
+        return Ok(target);
+    }
+    Ok((1..)
+        .map(|n| destination_dir.join(copy_name(&name, n)))
+        .find(|candidate| candidate.symlink_metadata().is_err())
+        .unwrap_or(target))
+}
+
+/// Copy `source` to exactly `target`.
+pub(crate) fn copy_to(source: &Path, target: &Path) -> AppResult<AffectedPaths> {
+    let meta = source
+        .symlink_metadata()
+        .map_err(|e| AppError::io("Cannot copy", &e).with_path(source))?;
+    if meta.is_dir() && within(target, source) {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Cannot copy a directory into itself").with_path(target));
+    }
+    ensure_free(target)?;
+    copy_or_clean_up(source, target)?;
+    Ok(AffectedPaths {
+        created: vec![path_string(target)],
+        ..AffectedPaths::default()
+    })
+}
+
+/// `stem copy.ext`, then `stem copy 2.ext`, ...
+fn copy_name(name: &str, n: usize) -> String {
+    let suffix = if n == 1 { " copy".to_string() } else { format!(" copy {}", n) };
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => format!("{}{}{}", &name[..dot], suffix, &name[dot..]),
+        _ => format!("{}{}", name, suffix),
+    }
+}
+
+/// Copy, removing whatever was copied if it fails partway.
+fn copy_or_clean_up(source: &Path, target: &Path) -> AppResult<()> {
+    copy_recursive(source, target).inspect_err(|_| {
+        let _ = remove(target);
+    })
+}
+
+/// Copy files, directories and symlinks (as links), keeping permissions.
+fn copy_recursive(source: &Path, target: &Path) -> AppResult<()> {
+    let meta = source
+        .symlink_metadata()
+        .map_err(|e| AppError::io("Failed to copy", &e).with_path(source))?;
+    let err = |e: std::io::Error| AppError::io("Failed to copy", &e).with_path(source);
+    if meta.file_type().is_symlink() {
+        copy_symlink(source, target).map_err(err)
+    } else if meta.is_dir() {
+        fs::create_dir(target).map_err(err)?;
+        for entry in fs::read_dir(source).map_err(err)? {
+            let entry = entry.map_err(err)?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+        fs::set_permissions(target, meta.permissions()).map_err(err)
+    } else {
+        fs::copy(source, target).map(|_| ()).map_err(err)
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, target)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, target: &Path) -> std::io::Result<()> {
+    fs::copy(source, target).map(|_| ())
+}
+
+/// Replace a file's content atomically, keeping its permissions. Creates
+/// the file if it doesn't exist. Writing through a symlink replaces the
+/// link's target.
+pub fn write_file(path: &Path, content: &str) -> AppResult<AffectedPaths> {
+    write_bytes(path, content.as_bytes())
+}
+
+/// [`write_file`] for raw bytes.
+pub(crate) fn write_bytes(path: &Path, content: &[u8]) -> AppResult<AffectedPaths> {
+    let target = match fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(AppError::io("Failed to write file", &e).with_path(path)),
+    };
+    let existing = fs::metadata(&target).ok();
+    if existing.as_ref().is_some_and(|m| m.is_dir()) {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Is a directory").with_path(path));
+    }
+    let dir = target
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = file_name(&target)?.to_string_lossy().to_string();
+    let temp = dir.join(format!(".{}.{}.tmp", name, &Uuid::new_v4().simple().to_string()[..8]));
+
+    let written = (|| -> std::io::Result<()> {
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&temp)?;
+        file.write_all(content)?;
+        if let Some(meta) = &existing {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &target)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(AppError::io("Failed to write file", &e).with_path(path));
+    }
+    // Make the rename itself durable
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    let mut affected = AffectedPaths::default();
+    if existing.is_some() {
+        affected.modified.push(path_string(path));
+    } else {
+        affected.created.push(path_string(path));
+    }
+    Ok(affected)
+}
+
+/// Move an entry to the trash, or remove it for good with `permanent`.
+/// Symlinks are removed, never followed.
+pub fn delete_path(path: &Path, permanent: bool) -> AppResult<AffectedPaths> {
+    let mut affected = AffectedPaths {
+        removed: vec![path_string(path)],
+        ..AffectedPaths::default()
+    };
+    if permanent {
+        remove(path)?;
+    } else {
+        affected.trashed_to = Some(path_string(&trash(path)?));
+    }
+    Ok(affected)
+}
+
+fn remove(path: &Path) -> AppResult<()> {
+    let meta = path
+        .symlink_metadata()
+        .map_err(|e| AppError::io("Failed to delete", &e).with_path(path))?;
+    let removed = if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    removed.map_err(|e| AppError::io("Failed to delete", &e).with_path(path))
+}
@@ -0,0 +1,213 @@
+//! Which paths the webview may touch.
+//!
+//! Filesystem commands only reach paths under a root: the project directory
+//! of an open terminal or a local terminal's current cwd, as the OS reports
+//! it for the shell process. Under the home directory, the files the sandbox
+//! keeps read-only ([`PROTECTED_PATHS`]) and credential stores
+//! ([`SECRET_PATHS`]) are off limits even inside a root. Paths are
+//! canonicalized before they are compared, so `..` and symlinks can't lead
+//! out. Blocked attempts are logged under `fs`.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::cwd::process_cwd;
+use crate::pty::manager::Isolation;
+use crate::state::{lock_session, AppState};
+
+/// Paths under the home directory that sandboxed shells get read-only and
+/// the webview can't access: shell startup files, keys, git config, and
+/// anything run on login.
+pub const PROTECTED_PATHS: &[&str] = &[
+    ".ssh",
+    ".gnupg",
+    ".bashrc",
+    ".bash_profile",
+    ".bash_login",
+    ".bash_logout",
+    ".profile",
+    ".zshrc",
+    ".zshenv",
+    ".zprofile",
+    ".zlogin",
+    ".zlogout",
+    ".pam_environment",
+    ".gitconfig",
+    ".config/git",
+    ".config/autostart",
+    ".config/systemd/user",
+    ".config/environment.d",
+    ".local/bin",
+    // Shell configs
+    ".config/fish",
+    ".config/nushell",
+    // Desktop environment startup scripts
+    ".config/plasma-workspace/env",
+    ".config/hypr",
+    ".xinitrc",
+    ".xprofile",
+    // File handler and .desktop registration
+    ".config/mimeapps.list",
+    ".local/share/applications",
+];
+
+/// Credential stores under the home directory, also off limits to the webview.
+pub const SECRET_PATHS: &[&str] = &[
+    ".aws",
+    ".azure",
+    ".config/gcloud",
+    ".kube",
+    ".docker/config.json",
+    ".netrc",
+    ".git-credentials",
+    ".password-store",
+    ".pki",
+    ".local/share/keyrings",
+];
+
+/// What a command does with a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Read it, following symlinks
+    Read,
+    /// Replace its content, following symlinks
+    Write,
+    /// Create, rename, move or delete the entry itself; a symlink is
+    /// checked where it sits, not where it points
+    Entry,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Entry => "modify",
+        })
+    }
+}
+
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    #[cfg(unix)]
+    let home = std::env::var_os("HOME");
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+    home.map(PathBuf::from).filter(|p| p.is_absolute())
+}
+
+/// Resolve `path` to an absolute path without symlinks or `..`. A path that
+/// doesn't exist yet resolves through its nearest existing ancestor. With
+/// `follow` unset the last component is kept as is.
+fn resolve(path: &Path, follow: bool) -> Option<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(path)
+    };
+    if follow {
+        if let Ok(resolved) = path.canonicalize() {
+            return Some(resolved);
+        }
+    }
+    // Canonicalize the longest existing prefix, then append the rest
+    let mut rest = Vec::new();
+    let mut base = path.as_path();
+    if !follow {
+        rest.push(base.file_name()?.to_os_string());
+        base = base.parent()?;
+    }
+    loop {
+        if let Ok(resolved) = base.canonicalize() {
+            let mut out = resolved;
+            for part in rest.iter().rev() {
+                out.push(part);
+            }
+            return Some(out);
+        }
+        rest.push(base.file_name()?.to_os_string());
+        base = base.parent()?;
+    }
+}
+
+/// Roots and denied paths, gathered once per command.
+pub struct AccessPolicy {
+    roots: Vec<PathBuf>,
+    denied: Vec<PathBuf>,
+}
+
+impl AccessPolicy {
+    /// The policy for the sessions open right now.
+    pub fn from_state(state: &AppState) -> Self {
+        let mut roots = Vec::new();
+        let sessions: Vec<(String, crate::state::SessionHandle)> = state
+            .pty_sessions
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        for (session_id, handle) in sessions {
+            let Ok(session) = lock_session(&handle) else {
+                continue;
+            };
+            roots.extend(session.project_dir.as_ref().map(PathBuf::from));
+            // Remote and container cwds aren't host paths
+            let local = session.remote.is_none() && session.isolation != Isolation::Container;
+            drop(session);
+            // The OS's view of the cwd, never the OSC 7 one: any output in the
+            // terminal can claim a cwd, and that would make it a root
+            if local {
+                roots.extend(process_cwd(state, &session_id).ok().map(PathBuf::from));
+            }
+        }
+        let roots = roots.iter().filter_map(|root| root.canonicalize().ok()).collect();
+        Self::new(roots, home_dir().as_deref())
+    }
+
+    pub fn new(roots: Vec<PathBuf>, home: Option<&Path>) -> Self {
+        let denied = home
+            .map(|home| {
+                PROTECTED_PATHS
+                    .iter()
+                    .chain(SECRET_PATHS)
+                    .map(|sub| {
+                        let path = home.join(sub);
+                        path.canonicalize().unwrap_or(path)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { roots, denied }
+    }
+
+    /// Whether `resolved` (a canonical path) is a denied path or inside one.
+    pub fn is_denied(&self, resolved: &Path) -> bool {
+        self.denied.iter().any(|denied| resolved.starts_with(denied))
+    }
+
+    /// Check `path` for `access`, returning its resolved form. Logs and
+    /// fails with `permission_denied` when the policy doesn't allow it.
+    pub fn check(&self, path: &Path, access: Access) -> AppResult<PathBuf> {
+        let Some(resolved) = resolve(path, access != Access::Entry) else {
+            return Err(self.blocked(path, access, "path can't be resolved"));
+        };
+        if self.is_denied(&resolved) {
+            return Err(self.blocked(path, access, "protected path"));
+        }
+        if !self.roots.iter().any(|root| resolved.starts_with(root)) {
+            return Err(self.blocked(path, access, "outside project and terminal directories"));
+        }
+        // Roots themselves can be read but not replaced or removed
+        if access != Access::Read && self.roots.contains(&resolved) {
+            return Err(self.blocked(path, access, "project or terminal directory itself"));
+        }
+        Ok(resolved)
+    }
+
+    fn blocked(&self, path: &Path, access: Access, reason: &str) -> AppError {
+        log::warn!(target: "fs", "Blocked {} of {}: {}", access, path.display(), reason);
+        AppError::new(
+            ErrorCode::PermissionDenied,
+            format!("Access denied ({}): {}", access, reason),
+        )
+        .with_path(path)
+    }
+}
@@ -0,0 +1,560 @@
+//! Project-wide find and replace.
+//!
+//! `replace_in_files` writes nothing: it walks the root the way
+//! `search_in_files` does and returns a preview, one diff per file in the
+//! `GitDiffResult` shape. `apply_replace` then writes the files picked from
+//! that preview, but only if none of them changed since it was made (size,
+//! mtime and content hash); otherwise it fails with `conflict` and the
+//! preview stays usable. Each file is replaced atomically, and a failed
+//! write puts back the files already written, so an apply lands whole or
+//! not at all. Applied replaces are journaled for `undo_replace`, which
+//! restores the originals under the same check.
+//!
+//! Only UTF-8 text is rewritten; other files are skipped. Lines are matched
+//! one at a time, as in search, so `^` and `$` anchor to lines and a match
+//! never spans a line break.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::content::{detect_encoding, TextEncoding};
+use crate::fs::git::GitDiffResult;
+use crate::fs::ops::{write_bytes, AffectedPaths};
+use crate::fs::policy::{Access, AccessPolicy};
+use crate::fs::search::{SearchOptions, Searcher, MAX_FILE_SIZE, SAMPLE_LEN};
+use crate::state::AppState;
+
+/// Previews kept for `apply_replace`; the oldest is dropped first
+const MAX_PREVIEWS: usize = 8;
+/// Applied replaces that can be undone
+const MAX_JOURNALS: usize = 20;
+const DEFAULT_MAX_FILES: usize = 500;
+const MAX_FILES: usize = 5000;
+/// Replaced content one preview holds; more sets `truncated`
+const MAX_PREVIEW_BYTES: usize = 64 * 1024 * 1024;
+/// Content held by all previews and journals together. Past it the oldest
+/// previews go first, then the oldest journals.
+const MAX_HELD_BYTES: usize = 256 * 1024 * 1024;
+
+/// Options for `replace_in_files`.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ReplaceOptions {
+    /// Treat the pattern as a regular expression; the replacement can then
+    /// refer to groups as `$1` or `${name}`
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Globs selecting the files to change, relative to the root
+    pub include: Vec<String>,
+    /// Globs for files and directories to leave alone
+    pub exclude: Vec<String>,
+    /// Files in the preview; more set `truncated`
+    pub max_files: Option<usize>,
+}
+
+impl ReplaceOptions {
+    /// The search that finds what to replace.
+    pub fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            regex: self.regex,
+            case_sensitive: self.case_sensitive,
+            whole_word: self.whole_word,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            ..SearchOptions::default()
+        }
+    }
+}
+
+/// One file of a preview.
+#[derive(Serialize)]
+pub struct FileReplacement {
+    #[serde(flatten)]
+    pub diff: GitDiffResult,
+    /// Path below the root, with `/` separators
+    pub relative_path: String,
+    pub replacements: usize,
+}
+
+/// Result of `replace_in_files`.
+#[derive(Serialize)]
+pub struct ReplacePreview {
+    /// Pass to `apply_replace`
+    pub preview_id: String,
+    pub root: String,
+    pub files: Vec<FileReplacement>,
+    pub replacements: usize,
+    /// Files not rewritten: binary, not UTF-8, too large or unreadable
+    pub files_skipped: usize,
+    /// More files matched than the preview holds
+    pub truncated: bool,
+}
+
+/// Result of `apply_replace`.
+#[derive(Serialize)]
+pub struct ReplaceApplied {
+    /// Pass to `undo_replace`
+    pub journal_id: String,
+    pub replacements: usize,
+    #[serde(flatten)]
+    pub affected: AffectedPaths,
+}
+
+/// A file's state when it was read, to tell whether it changed since.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Snapshot {
+    size: u64,
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl Snapshot {
+    fn new(content: &[u8], meta: &std::fs::Metadata) -> Self {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            hash: hasher.finish(),
+        }
+    }
+
+    /// Read `path`, returning its content and snapshot.
+    fn read(path: &Path) -> std::io::Result<(Vec<u8>, Self)> {
+        let content = std::fs::read(path)?;
+        let meta = std::fs::metadata(path)?;
+        let snapshot = Self::new(&content, &meta);
+        Ok((content, snapshot))
+    }
+}
+
+struct PlannedFile {
+    path: PathBuf,
+    display_path: String,
+    before: Snapshot,
+    content: Vec<u8>,
+    replacements: usize,
+}
+
+struct Preview {
+    id: String,
+    files: Vec<PlannedFile>,
+}
+
+impl Preview {
+    fn bytes(&self) -> usize {
+        self.files.iter().map(|file| file.content.len()).sum()
+    }
+}
+
+struct JournalEntry {
+    path: PathBuf,
+    display_path: String,
+    original: Vec<u8>,
+    /// The file as the replace left it
+    after: Snapshot,
+}
+
+struct Journal {
+    id: String,
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    fn bytes(&self) -> usize {
+        self.entries.iter().map(|entry| entry.original.len()).sum()
+    }
+}
+
+/// Previews waiting to be applied and the undo journal.
+#[derive(Default)]
+pub struct Replacements {
+    held: Mutex<Held>,
+}
+
+impl Replacements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Default)]
+struct Held {
+    previews: VecDeque<Preview>,
+    journals: VecDeque<Journal>,
+}
+
+impl Held {
+    fn bytes(&self) -> usize {
+        self.previews.iter().map(Preview::bytes).sum::<usize>() + self.journals.iter().map(Journal::bytes).sum::<usize>()
+    }
+
+    /// Drop the oldest previews, then the oldest journals, until `bytes`
+    /// more fit in [`MAX_HELD_BYTES`].
+    fn make_room(&mut self, bytes: usize) {
+        while self.bytes() + bytes > MAX_HELD_BYTES {
+            if self.previews.pop_front().is_none() && self.journals.pop_front().is_none() {
+                break;
+            }
+        }
+    }
+
+    fn add_preview(&mut self, preview: Preview) {
+        if self.previews.len() >= MAX_PREVIEWS {
+            self.previews.pop_front();
+        }
+        self.make_room(preview.bytes());
+        self.previews.push_back(preview);
+    }
+
+    fn add_journal(&mut self, journal: Journal) {
+        if self.journals.len() >= MAX_JOURNALS {
+            self.journals.pop_front();
+        }
+        self.make_room(journal.bytes());
+        self.journals.push_back(journal);
+    }
+}
+
+/// Work out what replacing `searcher`'s pattern with `replacement` would
+/// change, without writing anything.
+pub fn replace_in_files(
+    state: &AppState,
+    searcher: &Searcher,
+    replacement: &str,
+    max_files: Option<usize>,
+) -> AppResult<ReplacePreview> {
+    let max_files = max_files.unwrap_or(DEFAULT_MAX_FILES).clamp(1, MAX_FILES);
+    let mut planned = Vec::new();
+    let mut planned_bytes = 0;
+    let mut preview = ReplacePreview {
+        preview_id: Uuid::new_v4().to_string(),
+        root: searcher.display_root(),
+        files: Vec::new(),
+        replacements: 0,
+        files_skipped: 0,
+        truncated: false,
+    };
+    searcher.walk(&AtomicBool::new(false), |path| {
+        let Some((bytes, before, bom_len)) = read_utf8(path) else {
+            preview.files_skipped += 1;
+            return true;
+        };
+        // Checked to be UTF-8 by read_utf8
+        let old_text = std::str::from_utf8(&bytes[bom_len..]).unwrap_or_default();
+        let changed = replace_text(searcher.regex(), old_text, replacement, searcher.is_literal());
+        if changed.replacements == 0 {
+            return true;
+        }
+        let size = bom_len + changed.text.len();
+        if planned.len() == max_files || planned_bytes + size > MAX_PREVIEW_BYTES {
+            preview.truncated = true;
+            return false;
+        }
+        planned_bytes += size;
+        let mut content = bytes[..bom_len].to_vec();
+        content.extend_from_slice(changed.text.as_bytes());
+        let display_path = searcher.display_path(path);
+        preview.replacements += changed.replacements;
+        preview.files.push(FileReplacement {
+            diff: GitDiffResult {
+                file_path: display_path.clone(),
+                old_content: old_text.to_string(),
+                new_content: changed.text,
+                added_lines: changed.added_lines,
+                deleted_lines: changed.deleted_lines,
+                is_new_file: false,
+                is_deleted_file: false,
+            },
+            relative_path: searcher.relative_path(path),
+            replacements: changed.replacements,
+        });
+        planned.push(PlannedFile {
+            path: path.to_path_buf(),
+            display_path,
+            before,
+            content,
+            replacements: changed.replacements,
+        });
+        true
+    });
+
+    if !planned.is_empty() {
+        state.replacements.held.lock().map_err(AppError::lock)?.add_preview(Preview {
+            id: preview.preview_id.clone(),
+            files: planned,
+        });
+    }
+    Ok(preview)
+}
+
+/// Write the replacements of a preview: all its files, or those in `files`
+/// (paths as the preview reported them). Fails with `conflict` if any of
+/// them changed since the preview. A successful apply uses the preview up.
+/// With a `policy`, every file has to be writable under it.
+pub fn apply_replace(
+    state: &AppState,
+    preview_id: &str,
+    files: Option<Vec<String>>,
+    policy: Option<&AccessPolicy>,
+) -> AppResult<ReplaceApplied> {
+    let preview = take(state, |held| &mut held.previews, |p| p.id == preview_id)?.ok_or_else(|| {
+        AppError::new(
+            ErrorCode::NotFound,
+            format!("No replace preview {}; it was applied or has expired", preview_id),
+        )
+    })?;
+    match apply_preview(&preview, files, policy) {
+        Ok(journal) => {
+            let applied = ReplaceApplied {
+                journal_id: journal.id.clone(),
+                replacements: preview
+                    .files
+                    .iter()
+                    .filter(|file| journal.entries.iter().any(|entry| entry.path == file.path))
+                    .map(|file| file.replacements)
+                    .sum(),
+                affected: AffectedPaths {
+                    modified: journal.entries.iter().map(|entry| entry.display_path.clone()).collect(),
+                    ..AffectedPaths::default()
+                },
+            };
+            state.replacements.held.lock().map_err(AppError::lock)?.add_journal(journal);
+            Ok(applied)
+        }
+        Err(e) => {
+            // Nothing was written; the preview can be applied again
+            if let Ok(mut held) = state.replacements.held.lock() {
+                held.previews.push_back(preview);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Put back the files an `apply_replace` changed. Fails with `conflict` if
+/// any of them was modified after the replace. With a `policy`, every file
+/// has to be writable under it.
+pub fn undo_replace(state: &AppState, journal_id: &str, policy: Option<&AccessPolicy>) -> AppResult<AffectedPaths> {
+    let journal = take(state, |held| &mut held.journals, |j| j.id == journal_id)?.ok_or_else(|| {
+        AppError::new(
+            ErrorCode::NotFound,
+            format!("No replace {} to undo; it was undone or has expired", journal_id),
+        )
+    })?;
+    let restored = (|| {
+        if let Some(policy) = policy {
+            for entry in &journal.entries {
+                policy.check(&entry.path, Access::Write)?;
+            }
+        }
+        let mut current = Vec::with_capacity(journal.entries.len());
+        let mut changed = Vec::new();
+        for entry in &journal.entries {
+            match Snapshot::read(&entry.path) {
+                Ok((content, snapshot)) if snapshot == entry.after => current.push(content),
+                _ => changed.push(entry.display_path.as_str()),
+            }
+        }
+        if !changed.is_empty() {
+            return Err(conflict("Modified since the replace", &changed));
+        }
+        let writes: Vec<_> = journal
+            .entries
+            .iter()
+            .zip(&current)
+            .map(|(entry, current)| (entry.path.as_path(), entry.original.as_slice(), current.as_slice()))
+            .collect();
+        write_all(&writes)
+    })();
+    match restored {
+        Ok(_) => Ok(AffectedPaths {
+            modified: journal.entries.iter().map(|entry| entry.display_path.clone()).collect(),
+            ..AffectedPaths::default()
+        }),
+        Err(e) => {
+            if let Ok(mut held) = state.replacements.held.lock() {
+                held.journals.push_back(journal);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Check and write the selected files of `preview`, returning the journal.
+fn apply_preview(preview: &Preview, files: Option<Vec<String>>, policy: Option<&AccessPolicy>) -> AppResult<Journal> {
+    let selected: Vec<&PlannedFile> = match files {
+        None => preview.files.iter().collect(),
+        Some(paths) => {
+            let wanted: HashSet<&str> = paths.iter().map(String::as_str).collect();
+            if let Some(unknown) = wanted
+                .iter()
+                .find(|path| !preview.files.iter().any(|file| file.display_path == **path))
+            {
+                return Err(AppError::new(ErrorCode::InvalidInput, "File is not part of the replace preview")
+                    .with_path(unknown));
+            }
+            preview
+                .files
+                .iter()
+                .filter(|file| wanted.contains(file.display_path.as_str()))
+                .collect()
+        }
+    };
+    if selected.is_empty() {
+        return Err(AppError::new(ErrorCode::InvalidInput, "No files selected to replace in"));
+    }
+    if let Some(policy) = policy {
+        for file in &selected {
+            policy.check(&file.path, Access::Write)?;
+        }
+    }
+
+    let mut originals = Vec::with_capacity(selected.len());
+    let mut changed = Vec::new();
+    for file in &selected {
+        match Snapshot::read(&file.path) {
+            Ok((content, snapshot)) if snapshot == file.before => originals.push(content),
+            _ => changed.push(file.display_path.as_str()),
+        }
+    }
+    if !changed.is_empty() {
+        return Err(conflict("Modified since the preview", &changed));
+    }
+
+    let writes: Vec<_> = selected
+        .iter()
+        .zip(&originals)
+        .map(|(file, original)| (file.path.as_path(), file.content.as_slice(), original.as_slice()))
+        .collect();
+    let after = write_all(&writes)?;
+    Ok(Journal {
+        id: Uuid::new_v4().to_string(),
+        entries: selected
+            .into_iter()
+            .zip(originals)
+            .zip(after)
+            .map(|((file, original), after)| JournalEntry {
+                path: file.path.clone(),
+                display_path: file.display_path.clone(),
+                original,
+                after,
+            })
+            .collect(),
+    })
+}
+
+/// Write each `(path, content, previous)`. If a write fails, the files
+/// already written get `previous` back. Returns the written files' snapshots.
+fn write_all(writes: &[(&Path, &[u8], &[u8])]) -> AppResult<Vec<Snapshot>> {
+    let mut written = Vec::with_capacity(writes.len());
+    for &(path, content, _) in writes {
+        let snapshot = write_bytes(path, content).and_then(|_| {
+            std::fs::metadata(path)
+                .map(|meta| Snapshot::new(content, &meta))
+                .map_err(|e| AppError::io("Failed to read metadata", &e).with_path(path))
+        });
+        match snapshot {
+            Ok(snapshot) => written.push(snapshot),
+            Err(e) => {
+                for &(path, _, previous) in writes[..written.len()].iter().rev() {
+                    if let Err(undo) = write_bytes(path, previous) {
+                        log::error!(target: "fs", "Failed to restore {}: {}", path.display(), undo.message);
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(written)
+}
+
+fn conflict(reason: &str, paths: &[&str]) -> AppError {
+    AppError::new(ErrorCode::Conflict, format!("{}: {}", reason, paths.join(", "))).with_path(paths[0])
+}
+
+/// Remove and return the first item matching `found` from the list that
+/// `list` picks out of what's held.
+fn take<T>(
+    state: &AppState,
+    list: impl FnOnce(&mut Held) -> &mut VecDeque<T>,
+    found: impl Fn(&T) -> bool,
+) -> AppResult<Option<T>> {
+    let mut held = state.replacements.held.lock().map_err(AppError::lock)?;
+    let list = list(&mut held);
+    Ok(list.iter().position(found).and_then(|index| list.remove(index)))
+}
+
+/// Content, snapshot and BOM length of a UTF-8 text file; `None` for
+/// anything else.
+fn read_utf8(path: &Path) -> Option<(Vec<u8>, Snapshot, usize)> {
+    if std::fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let (bytes, snapshot) = Snapshot::read(path).ok()?;
+    let (encoding, bom_len) = detect_encoding(&bytes[..bytes.len().min(SAMPLE_LEN)])?;
+    if encoding != TextEncoding::Utf8 || std::str::from_utf8(&bytes[bom_len..]).is_err() {
+        return None;
+    }
+    Some((bytes, snapshot, bom_len))
+}
+
+struct ReplacedText {
+    text: String,
+    replacements: usize,
+    added_lines: usize,
+    deleted_lines: usize,
+}
+
+/// Replace the non-empty matches of `regex` in each line of `text`,
+/// keeping line endings. A `literal` replacement is inserted as is;
+/// otherwise `$` group references are expanded.
+fn replace_text(regex: &Regex, text: &str, replacement: &str, literal: bool) -> ReplacedText {
+    let mut result = ReplacedText {
+        text: String::with_capacity(text.len()),
+        replacements: 0,
+        added_lines: 0,
+        deleted_lines: 0,
+    };
+    for piece in text.split_inclusive('\n') {
+        let line = piece.strip_suffix('\n').unwrap_or(piece);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let ending = &piece[line.len()..];
+        let mut replaced = String::new();
+        let mut last = 0;
+        let mut count = 0;
+        for captures in regex.captures_iter(line) {
+            let Some(m) = captures.get(0).filter(|m| !m.is_empty()) else {
+                continue;
+            };
+            replaced.push_str(&line[last..m.start()]);
+            if literal {
+                replaced.push_str(replacement);
+            } else {
+                captures.expand(replacement, &mut replaced);
+            }
+            last = m.end();
+            count += 1;
+        }
+        if count == 0 {
+            result.text.push_str(piece);
+            continue;
+        }
+        replaced.push_str(&line[last..]);
+        result.replacements += count;
+        result.deleted_lines += 1;
+        result.added_lines += replaced.split('\n').count();
+        result.text.push_str(&replaced);
+        result.text.push_str(ending);
+    }
+    result
+}
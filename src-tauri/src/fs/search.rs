@@ -0,0 +1,428 @@
+//! Content search across a tree.
+//!
+//! `search_in_files` walks a root with the same ignore rules as the tree,
+//! skips binary files and files over [`MAX_FILE_SIZE`], and matches the
+//! pattern line by line. Each file's matches go out as one `search-result`
+//! event as soon as the file is done, and a `search-done` event with the
+//! totals ends the search. Searches run on their own thread and stop early
+//! when cancelled or when `max_results` is reached.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use walkdir::WalkDir;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+use crate::fs::content::detect_encoding;
+use crate::fs::directory::is_hidden_system_dir;
+use crate::fs::ignore::{GlobList, IgnoreMatcher};
+use crate::state::AppState;
+
+/// Searches running at once
+const MAX_SEARCHES: usize = 8;
+/// Larger files are skipped
+pub(crate) const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+/// Bytes inspected for the encoding and binary check
+pub(crate) const SAMPLE_LEN: usize = 8 * 1024;
+/// Longest preview sent per matching line
+const MAX_PREVIEW_CHARS: usize = 250;
+/// Context kept before the first match when a long line is cut
+const PREVIEW_LEAD_CHARS: usize = 40;
+const DEFAULT_MAX_MATCHES_PER_FILE: usize = 100;
+const DEFAULT_MAX_RESULTS: usize = 2000;
+const MAX_RESULTS: usize = 50_000;
+
+/// How to match, and which files to look at.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Treat the pattern as a regular expression instead of literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// Gitignore-style globs; when given, only matching files are searched
+    pub include: Vec<String>,
+    /// Gitignore-style globs for files and directories to skip
+    pub exclude: Vec<String>,
+    /// Matches reported per file (100 by default)
+    pub max_matches_per_file: Option<usize>,
+    /// Matches reported in total (2000 by default, 50,000 at most)
+    pub max_results: Option<usize>,
+}
+
+/// One matching line.
+#[derive(Serialize, Clone, Debug)]
+pub struct LineMatch {
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in UTF-16 code units, of the first match on the line
+    pub column: usize,
+    /// The line, cut around the first match when it is long
+    pub preview: String,
+    /// Matched `[start, end)` ranges of `preview`, in UTF-16 code units
+    pub ranges: Vec<[usize; 2]>,
+}
+
+/// Payload of `search-result`: the matches in one file.
+#[derive(Serialize, Clone, Debug)]
+pub struct FileMatches {
+    pub search_id: String,
+    pub path: String,
+    /// Path below the root, with `/` separators
+    pub relative_path: String,
+    pub matches: Vec<LineMatch>,
+    /// The file has more matches than `max_matches_per_file`
+    pub truncated: bool,
+}
+
+/// Payload of `search-done`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SearchSummary {
+    pub search_id: String,
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub matches: usize,
+    /// Binary, too large or unreadable files left out
+    pub files_skipped: usize,
+    /// `max_results` was reached before the walk finished
+    pub limit_hit: bool,
+    pub cancelled: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Decides which canonical paths a search leaves out.
+type SkipPaths = Box<dyn Fn(&Path) -> bool + Send>;
+
+/// A compiled search over one root.
+pub struct Searcher {
+    id: String,
+    root: PathBuf,
+    /// The root as the caller named it, for the reported paths
+    display_root: PathBuf,
+    regex: Regex,
+    include: GlobList,
+    exclude: GlobList,
+    max_per_file: usize,
+    max_results: usize,
+    /// The pattern is literal text, so replacements are too
+    literal: bool,
+    skip: Option<SkipPaths>,
+}
+
+impl Searcher {
+    /// Compile `pattern` for a search under `root`. Fails on an invalid
+    /// regex or a root that isn't a directory.
+    pub fn new(root: &Path, pattern: &str, options: SearchOptions) -> AppResult<Self> {
+        if pattern.is_empty() {
+            return Err(AppError::new(ErrorCode::InvalidInput, "Empty search pattern"));
+        }
+        let canonical = root
+            .canonicalize()
+            .map_err(|e| AppError::io("Cannot search directory", &e).with_path(root))?;
+        if !canonical.is_dir() {
+            return Err(AppError::new(ErrorCode::InvalidInput, "Not a directory").with_path(root));
+        }
+        let mut source = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        if options.whole_word {
+            source = format!(r"\b(?:{})\b", source);
+        }
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| AppError::new(ErrorCode::InvalidInput, format!("Invalid pattern: {}", e)))?;
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            include: GlobList::new(&canonical, &options.include),
+            exclude: GlobList::new(&canonical, &options.exclude),
+            root: canonical,
+            display_root: root.to_path_buf(),
+            regex,
+            max_per_file: options
+                .max_matches_per_file
+                .unwrap_or(DEFAULT_MAX_MATCHES_PER_FILE)
+                .max(1),
+            max_results: options.max_results.unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS),
+            literal: !options.regex,
+            skip: None,
+        })
+    }
+
+    /// The id carried by this search's events.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Leave out files and directories for which `skip` returns true. It
+    /// gets canonical paths.
+    pub fn skip_paths(mut self, skip: impl Fn(&Path) -> bool + Send + 'static) -> Self {
+        self.skip = Some(Box::new(skip));
+        self
+    }
+
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    pub(crate) fn is_literal(&self) -> bool {
+        self.literal
+    }
+
+    /// The root as the caller named it.
+    pub(crate) fn display_root(&self) -> String {
+        self.display_root.to_string_lossy().to_string()
+    }
+
+    /// `path` below the root, with `/` separators.
+    pub(crate) fn relative_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    /// Where to report `path`: under the root as the caller named it.
+    pub(crate) fn display_path(&self, path: &Path) -> String {
+        self.display_root.join(self.relative_path(path)).to_string_lossy().to_string()
+    }
+
+    /// Search the tree, passing each file with matches to `on_file`. Stops
+    /// early once `cancelled` is set.
+    pub fn run(&self, cancelled: &AtomicBool, mut on_file: impl FnMut(FileMatches)) -> SearchSummary {
+        let started = Instant::now();
+        let mut summary = SearchSummary {
+            search_id: self.id.clone(),
+            ..SearchSummary::default()
+        };
+        summary.cancelled = !self.walk(cancelled, |path| {
+            let budget = self.max_per_file.min(self.max_results - summary.matches);
+            let Some((matches, truncated)) = self.search_file(path, budget) else {
+                summary.files_skipped += 1;
+                return true;
+            };
+            summary.files_searched += 1;
+            if matches.is_empty() {
+                return true;
+            }
+            summary.files_matched += 1;
+            summary.matches += matches.len();
+            on_file(FileMatches {
+                search_id: self.id.clone(),
+                path: self.display_path(path),
+                relative_path: self.relative_path(path),
+                matches,
+                truncated,
+            });
+            if summary.matches >= self.max_results {
+                summary.limit_hit = true;
+                return false;
+            }
+            true
+        });
+        summary.elapsed_ms = started.elapsed().as_millis() as u64;
+        summary
+    }
+
+    /// Visit the files the search covers, in tree order, until `visit`
+    /// returns false. Returns false if `cancelled` was set first.
+    pub(crate) fn walk(&self, cancelled: &AtomicBool, mut visit: impl FnMut(&Path) -> bool) -> bool {
+        let mut ignore = IgnoreMatcher::new(&self.root);
+        let mut walker = WalkDir::new(&self.root)
+            .follow_links(false)
+            .sort_by(|a, b| {
+                let key = |e: &walkdir::DirEntry| (!e.file_type().is_dir(), e.file_name().to_string_lossy().to_lowercase());
+                key(a).cmp(&key(b))
+            })
+            .into_iter();
+        while let Some(entry) = walker.next() {
+            if cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            let e = match entry {
+                Ok(e) => e,
+                Err(err) => {
+                    log::warn!(target: "fs", "Failed to read entry: {}", err);
+                    continue;
+                }
+            };
+            if e.depth() == 0 || e.path_is_symlink() {
+                continue;
+            }
+            let path = e.path();
+            let is_dir = e.file_type().is_dir();
+            let skipped = (is_dir && is_hidden_system_dir(&e))
+                || ignore.is_ignored(path, is_dir)
+                || self.exclude.matches(path, is_dir)
+                || self.skip.as_ref().is_some_and(|skip| skip(path));
+            if skipped {
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+            if is_dir || !self.included(path) {
+                continue;
+            }
+            if !visit(path) {
+                break;
+            }
+        }
+        true
+    }
+
+    /// With include globs, whether they select the file or a directory
+    /// it's in.
+    fn included(&self, path: &Path) -> bool {
+        if self.include.is_empty() || self.include.matches(path, false) {
+            return true;
+        }
+        path.ancestors()
+            .skip(1)
+            .take_while(|dir| *dir != self.root)
+            .any(|dir| self.include.matches(dir, true))
+    }
+
+    /// Up to `budget` matching lines of a text file, and whether there were
+    /// more. `None` for files that were skipped.
+    fn search_file(&self, path: &Path, budget: usize) -> Option<(Vec<LineMatch>, bool)> {
+        let meta = std::fs::metadata(path).ok()?;
+        if meta.len() > MAX_FILE_SIZE {
+            return None;
+        }
+        let bytes = std::fs::read(path).ok()?;
+        let (encoding, bom_len) = detect_encoding(&bytes[..bytes.len().min(SAMPLE_LEN)])?;
+        let text = encoding.decode(&bytes[bom_len..]);
+
+        let mut matches = Vec::new();
+        for (index, line) in text.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let found: Vec<(usize, usize)> = self
+                .regex
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            if found.is_empty() {
+                continue;
+            }
+            if matches.len() == budget {
+                return Some((matches, true));
+            }
+            matches.push(line_match(index + 1, line, &found));
+        }
+        Some((matches, false))
+    }
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// A `LineMatch` for byte ranges `found` of `line`.
+fn line_match(number: usize, line: &str, found: &[(usize, usize)]) -> LineMatch {
+    let first = found[0].0;
+    // Cut long lines to a window starting a little before the first match
+    let (start, end) = if line.chars().count() <= MAX_PREVIEW_CHARS {
+        (0, line.len())
+    } else {
+        let lead = line[..first].char_indices().rev().nth(PREVIEW_LEAD_CHARS - 1).map_or(0, |(i, _)| i);
+        let end = line[lead..]
+            .char_indices()
+            .nth(MAX_PREVIEW_CHARS)
+            .map_or(line.len(), |(i, _)| lead + i);
+        (lead, end)
+    };
+    let preview = &line[start..end];
+    let ranges = found
+        .iter()
+        .filter(|&&(s, _)| s >= start && s < end)
+        .map(|&(s, e)| {
+            let from = utf16_len(&line[start..s]);
+            [from, from + utf16_len(&line[s..e.min(end)])]
+        })
+        .collect();
+    LineMatch {
+        line: number,
+        column: utf16_len(&line[..first]) + 1,
+        preview: preview.to_string(),
+        ranges,
+    }
+}
+
+/// Cancel flags of running searches, by search id.
+#[derive(Default)]
+pub struct Searches {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Searches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Run `searcher` on its own thread, emitting `search-result` per file and
+/// `search-done` at the end. Returns the search id.
+pub fn start(state: &AppState, searcher: Searcher, sink: SharedSink) -> AppResult<String> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let mut running = state.searches.running.lock().map_err(AppError::lock)?;
+        if running.len() >= MAX_SEARCHES {
+            return Err(AppError::new(
+                ErrorCode::InvalidInput,
+                format!("Too many searches running (max {}); cancel one first", MAX_SEARCHES),
+            ));
+        }
+        running.insert(searcher.id.clone(), cancelled.clone());
+    }
+
+    let id = searcher.id.clone();
+    let searches = state.searches.clone();
+    std::thread::spawn(move || {
+        let summary = searcher.run(&cancelled, |file| emit(&sink, "search-result", &file));
+        if let Ok(mut running) = searches.running.lock() {
+            running.remove(&searcher.id);
+        }
+        log::debug!(
+            target: "fs",
+            "Search {} in {}: {} matches in {} files ({} searched) in {} ms",
+            searcher.id,
+            searcher.root.display(),
+            summary.matches,
+            summary.files_matched,
+            summary.files_searched,
+            summary.elapsed_ms
+        );
+        emit(&sink, "search-done", &summary);
+    });
+    Ok(id)
+}
+
+/// Stop a running search. Its `search-done` event follows with
+/// `cancelled: true`.
+pub fn cancel(state: &AppState, search_id: &str) -> AppResult<()> {
+    let running = state.searches.running.lock().map_err(AppError::lock)?;
+    match running.get(search_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(())
+        }
+        None => Err(AppError::new(ErrorCode::NotFound, format!("No running search {}", search_id))),
+    }
+}
+
+fn emit<T: Serialize>(sink: &SharedSink, event: &str, payload: &T) {
+    match serde_json::to_value(payload) {
+        Ok(payload) => sink.emit_event(event, payload),
+        Err(e) => log::warn!(target: "fs", "Failed to serialize {}: {}", event, e),
+    }
+}
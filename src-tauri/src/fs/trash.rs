@@ -0,0 +1,208 @@
+//! Moving files to the trash, per the freedesktop.org Trash specification.
+//!
+//! Files on the same device as the home trash (`$XDG_DATA_HOME/Trash`) go
+//! there. Files on other mounts go to that mount's `.Trash/$uid` when the
+//! admin has set up a sticky, non-symlink `.Trash`, else `.Trash-$uid`.
+//! Each trashed entry gets a `.trashinfo` file with its original path and
+//! deletion time, so file managers can restore it.
+
+use std::path::{Path, PathBuf};
+use crate::error::{AppError, AppResult, ErrorCode};
+
+/// Move `path` to the trash and return where it ended up.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn trash(path: &Path) -> AppResult<PathBuf> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::os::unix::fs::MetadataExt;
+
+    let path = absolute(path)?;
+    let meta = std::fs::symlink_metadata(&path)
+        .map_err(|e| AppError::io("Cannot trash", &e).with_path(&path))?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| AppError::new(ErrorCode::InvalidInput, "Cannot trash this path").with_path(&path))?
+        .to_string_lossy()
+        .to_string();
+
+    let (trash_dir, topdir) = trash_dir_for(&path, meta.dev())?;
+    let files = trash_dir.join("files");
+    let info = trash_dir.join("info");
+    for dir in [&files, &info] {
+        create_private_dir(dir)?;
+    }
+
+    // Home trash entries record absolute paths, mount trashes relative ones
+    let original = match &topdir {
+        Some(top) => path.strip_prefix(top).unwrap_or(&path).to_path_buf(),
+        None => path.clone(),
+    };
+    let info_text = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(&original),
+        local_timestamp()
+    );
+
+    // Claim a free name by creating its info file exclusively
+    for n in 1.. {
+        let candidate = numbered_name(&name, n);
+        let info_path = info.join(format!("{}.trashinfo", candidate));
+        let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(AppError::io("Failed to write trash info", &e).with_path(&info_path)),
+        };
+        let target = files.join(&candidate);
+        if target.symlink_metadata().is_ok() {
+            // Stale entry without info; leave it alone
+            let _ = std::fs::remove_file(&info_path);
+            continue;
+        }
+        let written = info_file.write_all(info_text.as_bytes()).and_then(|_| info_file.sync_all());
+        let moved = written.and_then(|_| std::fs::rename(&path, &target));
+        if let Err(e) = moved {
+            let _ = std::fs::remove_file(&info_path);
+            return Err(AppError::io("Failed to move to trash", &e).with_path(&path));
+        }
+        log::info!(target: "fs", "Trashed {} -> {}", path.display(), target.display());
+        return Ok(target);
+    }
+    unreachable!()
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn trash(path: &Path) -> AppResult<PathBuf> {
+    Err(AppError::new(
+        ErrorCode::Unsupported,
+        "Moving to the trash is not supported on this platform; delete permanently instead",
+    )
+    .with_path(path))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn absolute(path: &Path) -> AppResult<PathBuf> {
+    // Resolve the parent only, so a symlink is trashed rather than its target
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let parent = parent
+        .canonicalize()
+        .map_err(|e| AppError::io("Cannot trash", &e).with_path(path))?;
+    match path.file_name() {
+        Some(name) if name != ".." => Ok(parent.join(name)),
+        _ => Err(AppError::new(ErrorCode::InvalidInput, "Cannot trash this path").with_path(path)),
+    }
+}
+
+/// The trash directory for a file on device `dev`, and the mount's top
+/// directory when it isn't the home trash.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash_dir_for(path: &Path, dev: u64) -> AppResult<(PathBuf, Option<PathBuf>)> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let home_trash = home_trash_dir()?;
+    let home_dev = home_trash
+        .ancestors()
+        .find_map(|dir| std::fs::metadata(dir).ok())
+        .map(|m| m.dev());
+    if home_dev == Some(dev) {
+        return Ok((home_trash, None));
+    }
+
+    // Highest ancestor still on the file's device
+    let mut topdir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+    while let Some(parent) = topdir.parent() {
+        match std::fs::metadata(parent) {
+            Ok(meta) if meta.dev() == dev => topdir = parent.to_path_buf(),
+            _ => break,
+        }
+    }
+
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    let shared = topdir.join(".Trash");
+    if let Ok(meta) = std::fs::symlink_metadata(&shared) {
+        let sticky = meta.permissions().mode() & 0o1000 != 0;
+        if meta.is_dir() && sticky {
+            let dir = shared.join(uid.to_string());
+            if create_private_dir(&dir).is_ok() {
+                return Ok((dir, Some(topdir)));
+            }
+        } else {
+            log::warn!(target: "fs", "Ignoring {}: not a sticky directory", shared.display());
+        }
+    }
+    let dir = topdir.join(format!(".Trash-{}", uid));
+    create_private_dir(&dir)?;
+    Ok((dir, Some(topdir)))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn home_trash_dir() -> AppResult<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .ok_or_else(|| AppError::new(ErrorCode::Unsupported, "No home directory for the trash"))?;
+    Ok(data_home.join("Trash"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn create_private_dir(dir: &Path) -> AppResult<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    if dir.is_dir() {
+        return Ok(());
+    }
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| AppError::io("Failed to create trash directory", &e).with_path(dir))
+}
+
+/// `name`, then `stem.2.ext`, `stem.3.ext`, ...
+#[cfg(all(unix, not(target_os = "macos")))]
+fn numbered_name(name: &str, n: usize) -> String {
+    if n == 1 {
+        return name.to_string();
+    }
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => format!("{}.{}{}", &name[..dot], n, &name[dot..]),
+        _ => format!("{}.{}", name, n),
+    }
+}
+
+/// Percent-encode a path as the spec's `Path=` key wants (RFC 2396 escaping,
+/// `/` kept).
+#[cfg(all(unix, not(target_os = "macos")))]
+fn percent_encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    let mut out = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.!~*'()".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// Current local time as `YYYY-MM-DDThh:mm:ss`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn local_timestamp() -> String {
+    // SAFETY: `time` and `localtime_r` only write to the locals passed in
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
@@ -0,0 +1,146 @@
+//! Lazy file tree: one directory level per call, paged.
+//!
+//! Expanding a node lists only that directory, sorted directories first and
+//! then by name. Large directories come back in pages; `next_cursor` names
+//! the last entry returned, so a page boundary stays put when entries are
+//! added or removed in between calls.
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::ignore::IgnoreMatcher;
+use crate::fs::metadata::{EntryMetadata, MetadataField, MetadataFields};
+
+const DEFAULT_PAGE_SIZE: usize = 500;
+const MAX_PAGE_SIZE: usize = 5000;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TreeEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    /// Whether a directory has any entries (ignored ones included), so the
+    /// UI can show an expander without listing it. Always false for files.
+    pub has_children: bool,
+    pub ignored: bool,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DirectoryPage {
+    pub path: String,
+    pub entries: Vec<TreeEntry>,
+    /// Pass back as `cursor` to get the next page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// More entries follow this page
+    pub truncated: bool,
+    /// Listed entries in the directory, across all pages
+    pub total: usize,
+    pub total_dirs: usize,
+    pub total_files: usize,
+    /// Entries hidden by ignore rules (included in `total` only when shown)
+    pub ignored_count: usize,
+}
+
+/// Directories first, then case-insensitive name, then exact name so the
+/// order is total.
+fn sort_key(is_dir: bool, name: &str) -> (bool, String, String) {
+    (!is_dir, name.to_lowercase(), name.to_string())
+}
+
+fn encode_cursor(entry: &TreeEntry) -> String {
+    format!("{}:{}", if entry.is_dir { 'd' } else { 'f' }, entry.name)
+}
+
+fn decode_cursor(cursor: &str) -> AppResult<(bool, String, String)> {
+    match cursor.split_once(':') {
+        Some(("d", name)) => Ok(sort_key(true, name)),
+        Some(("f", name)) => Ok(sort_key(false, name)),
+        _ => Err(AppError::new(ErrorCode::InvalidInput, format!("Invalid cursor: {}", cursor))),
+    }
+}
+
+fn has_entries(dir: &Path) -> bool {
+    fs::read_dir(dir).map(|mut it| it.next().is_some()).unwrap_or(false)
+}
+
+/// List one page of a directory's children. Symlinks are listed but never
+/// reported as directories, so the tree can't loop.
+pub fn read_directory_children(
+    path: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    show_ignored: Option<bool>,
+    fields: Option<Vec<MetadataField>>,
+) -> AppResult<DirectoryPage> {
+    let dir = PathBuf::from(&path);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let show_ignored = show_ignored.unwrap_or(false);
+    let fields = MetadataFields::new(fields);
+    let after = cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let read = fs::read_dir(&dir).map_err(|e| AppError::io("Failed to read directory", &e).with_path(&dir))?;
+    let mut ignore = IgnoreMatcher::new(&dir);
+    let dir_ignored = ignore.is_ignored_within(&dir, true);
+
+    let mut listed: Vec<(bool, String, PathBuf, bool)> = Vec::new();
+    let mut ignored_count = 0;
+    for entry in read {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!(target: "fs", "Failed to read entry in {}: {}", path, e);
+                continue;
+            }
+        };
+        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        let entry_path = entry.path();
+        let ignored = dir_ignored || ignore.is_ignored(&entry_path, is_dir);
+        if ignored {
+            ignored_count += 1;
+            if !show_ignored || entry.file_name() == ".git" {
+                continue;
+            }
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        listed.push((is_dir, name, entry_path, ignored));
+    }
+    listed.sort_by_key(|e| sort_key(e.0, &e.1));
+
+    let total = listed.len();
+    let total_dirs = listed.iter().filter(|e| e.0).count();
+    let start = match after {
+        Some(ref key) => listed.partition_point(|e| sort_key(e.0, &e.1).cmp(key) != Ordering::Greater),
+        None => 0,
+    };
+    let entries: Vec<TreeEntry> = listed
+        .into_iter()
+        .skip(start)
+        .take(limit)
+        .map(|(is_dir, name, entry_path, ignored)| TreeEntry {
+            has_children: is_dir && has_entries(&entry_path),
+            metadata: fields.read(&entry_path),
+            name,
+            path: entry_path.to_string_lossy().to_string(),
+            is_dir,
+            ignored,
+        })
+        .collect();
+    let truncated = start + entries.len() < total;
+    let next_cursor = if truncated { entries.last().map(encode_cursor) } else { None };
+
+    Ok(DirectoryPage {
+        path,
+        entries,
+        next_cursor,
+        truncated,
+        total,
+        total_dirs,
+        total_files: total - total_dirs,
+        ignored_count,
+    })
+}
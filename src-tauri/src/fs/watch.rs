@@ -0,0 +1,466 @@
+//! Directory subscriptions for the file tree.
+//!
+//! [`watch`] watches a tree recursively and emits `fs-changed`
+//! with the changes batched: events are collected until the tree has been
+//! quiet for [`QUIET_PERIOD`] (or [`MAX_DELAY`] has passed since the first
+//! one), coalesced per path, and filtered through the tree's ignore rules.
+//! Each call returns its own handle; [`unwatch`] drops it.
+//!
+//! Every directory the ignore rules don't hide gets its own watch, added as
+//! directories appear, so `node_modules`, `target` and `.git` don't use up
+//! the OS's watch limit.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+use crate::fs::directory::is_hidden_system_dir;
+use crate::fs::ignore::{IgnoreMatcher, TERMINAL_IGNORE_FILE};
+use crate::state::AppState;
+
+/// Watch handles open at once, across all windows and roots
+pub const MAX_DIRECTORY_WATCHES: usize = 16;
+const QUIET_PERIOD: Duration = Duration::from_millis(150);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+/// Paths kept per batch; past this the batch only reports `overflow`
+const MAX_BATCH_PATHS: usize = 5000;
+/// How often an idle watcher checks whether it was dropped
+const STOP_POLL: Duration = Duration::from_secs(1);
+
+/// A running watcher thread; dropping this stops it.
+pub(crate) struct WatchHandle {
+    _stop: Sender<()>,
+}
+
+/// Open `watch_directory` handles, by watch id.
+#[derive(Default)]
+pub struct DirectoryWatches {
+    watches: Mutex<HashMap<String, WatchHandle>>,
+}
+
+impl DirectoryWatches {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RenamedPath {
+    pub from: String,
+    pub to: String,
+}
+
+/// Payload of `fs-changed`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct FsChangeBatch {
+    pub watch_id: String,
+    pub root: String,
+    pub created: BTreeSet<String>,
+    pub removed: BTreeSet<String>,
+    pub modified: BTreeSet<String>,
+    pub renamed: Vec<RenamedPath>,
+    /// Changes were dropped (too many, or the OS queue overflowed); reload
+    /// the tree instead of applying the batch
+    pub overflow: bool,
+    /// Paths created and removed again within this batch
+    #[serde(skip)]
+    transient: BTreeSet<String>,
+}
+
+impl FsChangeBatch {
+    fn len(&self) -> usize {
+        self.created.len() + self.removed.len() + self.modified.len() + self.renamed.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0 && !self.overflow
+    }
+
+    fn full(&mut self) -> bool {
+        if self.len() >= MAX_BATCH_PATHS {
+            self.overflow = true;
+        }
+        self.overflow
+    }
+
+    fn created(&mut self, path: String) {
+        if self.full() {
+            return;
+        }
+        // Removed and recreated within one batch (editors saving by rename),
+        // or replaced right after being renamed into place
+        if self.removed.remove(&path) || self.renamed.iter().any(|r| r.to == path) {
+            self.modified.insert(path);
+        } else {
+            self.created.insert(path);
+        }
+    }
+
+    fn removed(&mut self, path: String) {
+        if self.full() {
+            return;
+        }
+        // Created and gone again before anyone saw it
+        if self.created.remove(&path) {
+            self.transient.insert(path);
+            return;
+        }
+        // A watched directory reports its own move or deletion after its
+        // parent did
+        if self.transient.contains(&path) || self.renamed.iter().any(|r| r.from == path) {
+            return;
+        }
+        self.modified.remove(&path);
+        self.removed.insert(path);
+    }
+
+    fn modified(&mut self, path: String) {
+        if self.full() || self.created.contains(&path) {
+            return;
+        }
+        self.modified.insert(path);
+    }
+
+    /// The batch with its paths moved from under `from` to under `to`.
+    fn rebase(self, from: &Path, to: &Path) -> Self {
+        let map = |path: &String| match Path::new(path).strip_prefix(from) {
+            Ok(rest) if rest.as_os_str().is_empty() => path_string(to),
+            Ok(rest) => path_string(&to.join(rest)),
+            Err(_) => path.clone(),
+        };
+        Self {
+            root: map(&self.root),
+            created: self.created.iter().map(map).collect(),
+            removed: self.removed.iter().map(map).collect(),
+            modified: self.modified.iter().map(map).collect(),
+            renamed: self
+                .renamed
+                .iter()
+                .map(|r| RenamedPath {
+                    from: map(&r.from),
+                    to: map(&r.to),
+                })
+                .collect(),
+            ..self
+        }
+    }
+
+    fn renamed(&mut self, from: String, to: String) {
+        if self.full() {
+            return;
+        }
+        // The two halves of the rename have been recorded already. A source
+        // nobody saw (an editor's temp file) leaves that as it is.
+        if self.transient.contains(&from) {
+            return;
+        }
+        self.removed.remove(&from);
+        self.created.remove(&to);
+        self.modified.remove(&to);
+        self.renamed.push(RenamedPath { from, to });
+    }
+}
+
+/// Start watching `path` recursively. Returns the watch id carried by every
+/// `fs-changed` event for it.
+pub fn watch(state: &AppState, path: &Path, sink: SharedSink) -> AppResult<String> {
+    watch_as(state, path, None, sink)
+}
+
+/// [`watch`] `path`, reporting paths under `display_root` rather than the
+/// canonical root: the root as the caller named it, which may lead through
+/// a symlink.
+pub(crate) fn watch_as(
+    state: &AppState,
+    path: &Path,
+    display_root: Option<&Path>,
+    sink: SharedSink,
+) -> AppResult<String> {
+    let root = path
+        .canonicalize()
+        .map_err(|e| AppError::io("Cannot watch directory", &e).with_path(path))?;
+    if !root.is_dir() {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Not a directory").with_path(&root));
+    }
+
+    check_room(&*state.dir_watches.watches.lock().map_err(AppError::lock)?)?;
+
+    // Setting up walks the tree, so it runs without the lock; another call
+    // may have taken the last slot meanwhile
+    let watch_id = Uuid::new_v4().to_string();
+    let handle = match display_root.filter(|shown| *shown != root) {
+        None => watch_batches(root, watch_id.clone(), move |batch| emit(&sink, batch))?,
+        Some(shown) => {
+            let (from, shown) = (root.clone(), shown.to_path_buf());
+            watch_batches(root, watch_id.clone(), move |batch| emit(&sink, batch.rebase(&from, &shown)))?
+        }
+    };
+    let mut watches = state.dir_watches.watches.lock().map_err(AppError::lock)?;
+    check_room(&watches)?;
+    watches.insert(watch_id.clone(), handle);
+    Ok(watch_id)
+}
+
+fn check_room(watches: &HashMap<String, WatchHandle>) -> AppResult<()> {
+    if watches.len() >= MAX_DIRECTORY_WATCHES {
+        return Err(AppError::new(
+            ErrorCode::InvalidInput,
+            format!("Too many directory watches (max {}); unwatch one first", MAX_DIRECTORY_WATCHES),
+        ));
+    }
+    Ok(())
+}
+
+/// One non-recursive watch per directory of a tree that isn't ignored.
+struct TreeWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+}
+
+impl TreeWatcher {
+    /// Watch `dir` and the directories below it that aren't ignored. `dir`
+    /// itself is watched regardless; callers check it first.
+    fn add(&mut self, dir: &Path, ignore: &mut IgnoreMatcher) -> notify::Result<()> {
+        let mut walker = WalkDir::new(dir).follow_links(false).into_iter();
+        while let Some(entry) = walker.next() {
+            let Ok(e) = entry else {
+                continue;
+            };
+            if !e.file_type().is_dir() {
+                continue;
+            }
+            if e.depth() > 0 && (is_hidden_system_dir(&e) || ignore.is_ignored(e.path(), true)) {
+                walker.skip_current_dir();
+                continue;
+            }
+            if self.watched.contains(e.path()) {
+                continue;
+            }
+            match self.watcher.watch(e.path(), RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.watched.insert(e.path().to_path_buf());
+                }
+                Err(err) if e.depth() == 0 => return Err(err),
+                Err(err) => log::warn!(target: "watcher", "Failed to watch {}: {}", e.path().display(), err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the watches on `path` and below it.
+    fn forget(&mut self, path: &Path) {
+        let gone: Vec<PathBuf> = self.watched.iter().filter(|p| p.starts_with(path)).cloned().collect();
+        for dir in gone {
+            let _ = self.watcher.unwatch(&dir);
+            self.watched.remove(&dir);
+        }
+    }
+
+    /// Follow directories the event created, removed or renamed. Old names
+    /// are dropped before new ones are added, because a moved directory
+    /// keeps its watch under the name it had.
+    fn follow(&mut self, root: &Path, ignore: &mut IgnoreMatcher, event: &Event) {
+        if event.need_rescan() {
+            let _ = self.add(root, ignore);
+            return;
+        }
+        let (gone, present): (Vec<&PathBuf>, Vec<&PathBuf>) = event
+            .paths
+            .iter()
+            .filter(|p| p.starts_with(root) && p.as_path() != root)
+            .partition(|p| p.symlink_metadata().is_err());
+        for path in gone {
+            self.forget(path);
+        }
+        for path in present {
+            if path.symlink_metadata().is_ok_and(|m| m.is_dir())
+                && !self.watched.contains(path.as_path())
+                && !ignore.is_ignored_within(path, true)
+            {
+                let _ = self.add(path, ignore);
+            }
+        }
+    }
+}
+
+/// Watch `root` (a canonical directory) and pass each non-empty batch to
+/// `on_batch` on the watcher thread, until the handle is dropped.
+pub(crate) fn watch_batches<F>(root: PathBuf, watch_id: String, on_batch: F) -> AppResult<WatchHandle>
+where
+    F: FnMut(FsChangeBatch) + Send + 'static,
+{
+    let (event_tx, event_rx) = channel();
+    let watcher = notify::recommended_watcher(move |res| {
+        let _ = event_tx.send(res);
+    })
+    .map_err(|e| AppError::new(ErrorCode::Io, format!("Failed to create watcher: {}", e)))?;
+    let mut tree = TreeWatcher {
+        watcher,
+        watched: HashSet::new(),
+    };
+    let mut ignore = IgnoreMatcher::new(&root);
+    tree.add(&root, &mut ignore)
+        .map_err(|e| AppError::new(ErrorCode::Io, format!("Failed to watch directory: {}", e)).with_path(&root))?;
+
+    let (stop_tx, stop_rx) = channel::<()>();
+    log::debug!(
+        target: "watcher",
+        "Watching {} ({}, {} directories)",
+        root.display(),
+        watch_id,
+        tree.watched.len()
+    );
+    std::thread::spawn(move || {
+        run(&watch_id, &root, &mut tree, ignore, event_rx, stop_rx, on_batch);
+        drop(tree);
+        log::debug!(target: "watcher", "Stopped watching {} ({})", root.display(), watch_id);
+    });
+    Ok(WatchHandle { _stop: stop_tx })
+}
+
+/// Stop a watch started by [`watch`].
+pub fn unwatch(state: &AppState, watch_id: &str) -> AppResult<()> {
+    let removed = state
+        .dir_watches
+        .watches
+        .lock()
+        .map_err(AppError::lock)?
+        .remove(watch_id);
+    match removed {
+        Some(_) => Ok(()),
+        None => Err(AppError::new(ErrorCode::NotFound, format!("No directory watch {}", watch_id))),
+    }
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Collect and hand off batches until the watch is dropped.
+fn run(
+    watch_id: &str,
+    root: &Path,
+    tree: &mut TreeWatcher,
+    mut ignore: IgnoreMatcher,
+    events: Receiver<notify::Result<Event>>,
+    stop: Receiver<()>,
+    mut on_batch: impl FnMut(FsChangeBatch),
+) {
+    let new_batch = || FsChangeBatch {
+        watch_id: watch_id.to_string(),
+        root: path_string(root),
+        ..FsChangeBatch::default()
+    };
+    let mut batch = new_batch();
+    // When the first and the latest event of the pending batch arrived
+    let mut pending: Option<(Instant, Instant)> = None;
+
+    loop {
+        if let Err(TryRecvError::Disconnected) = stop.try_recv() {
+            return;
+        }
+        let timeout = match pending {
+            Some((first, last)) => {
+                let deadline = (last + QUIET_PERIOD).min(first + MAX_DELAY);
+                let now = Instant::now();
+                if deadline <= now {
+                    // Flush even while events keep coming
+                    pending = None;
+                    let ready = std::mem::replace(&mut batch, new_batch());
+                    if !ready.is_empty() {
+                        on_batch(ready);
+                    }
+                    continue;
+                }
+                deadline - now
+            }
+            None => STOP_POLL,
+        };
+        match events.recv_timeout(timeout) {
+            Ok(Ok(event)) => {
+                let now = Instant::now();
+                pending = Some((pending.map_or(now, |(first, _)| first), now));
+                record(&mut batch, &mut ignore, root, &event);
+                tree.follow(root, &mut ignore, &event);
+            }
+            Ok(Err(e)) => log::warn!(target: "watcher", "Watch error on {}: {}", root.display(), e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+fn emit(sink: &SharedSink, batch: FsChangeBatch) {
+    match serde_json::to_value(&batch) {
+        Ok(payload) => sink.emit_event("fs-changed", payload),
+        Err(e) => log::warn!(target: "watcher", "Failed to serialize fs-changed: {}", e),
+    }
+}
+
+/// Add one notify event to the batch, skipping ignored paths.
+fn record(batch: &mut FsChangeBatch, ignore: &mut IgnoreMatcher, root: &Path, event: &Event) {
+    if event.need_rescan() {
+        batch.overflow = true;
+        return;
+    }
+    // Rules changed: reread them for what follows
+    if event.paths.iter().any(|p| {
+        p.file_name()
+            .is_some_and(|name| name == ".gitignore" || name == TERMINAL_IGNORE_FILE)
+    }) {
+        *ignore = IgnoreMatcher::new(root);
+    }
+    let mut visible = |path: &Path| {
+        path.starts_with(root) && path != root && !ignore.is_ignored_within(path, path.is_dir())
+    };
+
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            let (from, to) = (&event.paths[0], &event.paths[1]);
+            match (visible(from), visible(to)) {
+                (true, true) => batch.renamed(path_string(from), path_string(to)),
+                (true, false) => batch.removed(path_string(from)),
+                (false, true) => batch.created(path_string(to)),
+                (false, false) => {}
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(mode)) => {
+            for path in event.paths.iter().filter(|p| visible(p)) {
+                let exists = match mode {
+                    RenameMode::From => false,
+                    RenameMode::To => true,
+                    _ => path.symlink_metadata().is_ok(),
+                };
+                if exists {
+                    batch.created(path_string(path));
+                } else {
+                    batch.removed(path_string(path));
+                }
+            }
+        }
+        EventKind::Create(_) => {
+            for path in event.paths.iter().filter(|p| visible(p)) {
+                batch.created(path_string(path));
+            }
+        }
+        EventKind::Remove(_) => {
+            for path in event.paths.iter().filter(|p| visible(p)) {
+                batch.removed(path_string(path));
+            }
+        }
+        EventKind::Modify(_) | EventKind::Any => {
+            for path in event.paths.iter().filter(|p| visible(p)) {
+                batch.modified(path_string(path));
+            }
+        }
+        EventKind::Access(_) | EventKind::Other => {}
+    }
+}
@@ -24,6 +24,8 @@ pub struct GitStatsCache {
     watcher_stops: Arc<RwLock<HashMap<PathBuf, Sender<()>>>>,
     enabled: Arc<RwLock<bool>>,
     last_cleanup: Arc<RwLock<Option<Instant>>>,
+    // Terminals currently in each repo (see `retarget`)
+    terminal_roots: Arc<RwLock<HashMap<PathBuf, usize>>>,
 }
 
 impl GitStatsCache {
@@ -33,6 +35,7 @@ impl GitStatsCache {
             watcher_stops: Arc::new(RwLock::new(HashMap::new())),
             enabled: Arc::new(RwLock::new(true)),
             last_cleanup: Arc::new(RwLock::new(None)),
+            terminal_roots: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -85,8 +88,45 @@ impl GitStatsCache {
         }
     }
 
+    // Move a terminal from one repository to another: the new repo gets a
+    // fresh cache entry and a watcher, the old repo's watcher stops once no
+    // terminal is left in it
+    pub fn retarget(&self, old_root: Option<&Path>, new_root: Option<&Path>) {
+        if old_root == new_root {
+            return;
+        }
+
+        if let Some(new_root) = new_root {
+            if let Ok(mut roots) = self.terminal_roots.write() {
+                *roots.entry(new_root.to_path_buf()).or_insert(0) += 1;
+            }
+            self.invalidate(new_root);
+            if let Err(e) = self.setup_watcher(new_root.to_path_buf()) {
+                log::debug!(target: "watcher", "Not watching {}: {}", new_root.display(), e);
+            }
+        }
+
+        if let Some(old_root) = old_root {
+            let unused = match self.terminal_roots.write() {
+                Ok(mut roots) => match roots.get_mut(old_root) {
+                    Some(count) if *count > 1 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => {
+                        roots.remove(old_root);
+                        true
+                    }
+                },
+                Err(_) => false,
+            };
+            if unused {
+                self.stop_watcher(old_root);
+            }
+        }
+    }
+
     // Invalidate specific repository
-    #[allow(dead_code)]
     pub fn invalidate(&self, repo_path: &Path) {
         if let Ok(mut entries) = self.entries.write() {
             entries.remove(repo_path);
@@ -159,12 +199,12 @@ impl GitStatsCache {
                             }
                         }
                     }
-                    Err(e) => eprintln!("Watch error: {:?}", e),
+                    Err(e) => log::warn!(target: "watcher", "Watch error on {}: {:?}", repo_path_for_callback.display(), e),
                 }
             }) {
                 Ok(w) => w,
                 Err(e) => {
-                    eprintln!("Failed to create watcher: {}", e);
+                    log::error!(target: "watcher", "Failed to create watcher for {}: {}", repo_path_for_thread.display(), e);
                     // Clean up the stop sender since watcher failed
                     if let Ok(mut stops) = watcher_stops.write() {
                         stops.remove(&repo_path_for_thread);
@@ -175,7 +215,7 @@ impl GitStatsCache {
 
             // Watch .git directory
             if let Err(e) = watcher.watch(&git_dir_clone, RecursiveMode::NonRecursive) {
-                eprintln!("Failed to watch git directory: {}", e);
+                log::error!(target: "watcher", "Failed to watch git directory {}: {}", git_dir_clone.display(), e);
                 if let Ok(mut stops) = watcher_stops.write() {
                     stops.remove(&repo_path_for_thread);
                 }
@@ -201,7 +241,6 @@ impl GitStatsCache {
     }
 
     // Stop watcher for a specific repository
-    #[allow(dead_code)]
     pub fn stop_watcher(&self, repo_path: &Path) {
         if let Ok(mut stops) = self.watcher_stops.write() {
             stops.remove(repo_path);
@@ -2,10 +2,17 @@ mod state;
 mod pty;
 mod fs;
 mod git_cache;
+mod events;
+mod error;
+mod logging;
+mod config_store;
+pub mod api;
 
 use state::create_state;
-use pty::commands::{spawn_terminal, write_to_terminal, resize_terminal, close_terminal, spawn_hidden_terminal, run_git_command};
-use fs::{read_directory, get_terminal_cwd, read_file_content, read_directory_recursive, get_git_stats, get_current_branch, enable_file_watchers, disable_file_watchers, get_file_watchers_status, check_command_exists, get_git_diff};
+use pty::commands::{spawn_terminal, write_to_terminal, resize_terminal, close_terminal, spawn_hidden_terminal, run_git_command, get_command_history, subscribe_terminal_cwd, unsubscribe_terminal_cwd, get_terminal_screen, get_terminal_lines, search_terminal, wait_for_output, send_and_expect, create_terminal_group, add_to_terminal_group, remove_from_terminal_group, delete_terminal_group, list_terminal_groups, write_to_group, get_terminal_metrics, get_all_terminal_metrics, paste_to_terminal, list_snippets, save_snippet, delete_snippet, run_snippet, list_container_profiles, save_container_profile, delete_container_profile, get_devcontainer_config, list_ssh_profiles, save_ssh_profile, delete_ssh_profile, spawn_ssh_terminal};
+use logging::get_recent_logs;
+use fs::{get_terminal_cwd, enable_file_watchers, disable_file_watchers, get_file_watchers_status};
+use fs::commands::{read_directory, read_file_content, find_files, search_in_files, cancel_search, replace_in_files, apply_replace, undo_replace, write_file_content, create_file_entry, create_directory_entry, rename_entry, move_entry, copy_entry, delete_entry, read_directory_recursive, read_directory_children, watch_directory, unwatch_directory, get_git_stats, get_current_branch, check_command_exists, get_git_diff};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -15,6 +22,12 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(create_state())
+        .setup(|app| {
+            use tauri::Manager;
+            logging::init(app.path().app_log_dir().ok());
+            logging::set_event_sink(std::sync::Arc::new(app.handle().clone()));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             spawn_terminal,
             write_to_terminal,
@@ -24,7 +37,23 @@ pub fn run() {
             read_directory,
             get_terminal_cwd,
             read_file_content,
+            find_files,
+            search_in_files,
+            cancel_search,
+            replace_in_files,
+            apply_replace,
+            undo_replace,
+            write_file_content,
+            create_file_entry,
+            create_directory_entry,
+            rename_entry,
+            move_entry,
+            copy_entry,
+            delete_entry,
             read_directory_recursive,
+            read_directory_children,
+            watch_directory,
+            unwatch_directory,
             get_git_stats,
             enable_file_watchers,
             disable_file_watchers,
@@ -33,6 +62,36 @@ pub fn run() {
             get_git_diff,
             get_current_branch,
             run_git_command,
+            get_recent_logs,
+            get_command_history,
+            subscribe_terminal_cwd,
+            unsubscribe_terminal_cwd,
+            get_terminal_screen,
+            get_terminal_lines,
+            search_terminal,
+            wait_for_output,
+            send_and_expect,
+            create_terminal_group,
+            add_to_terminal_group,
+            remove_from_terminal_group,
+            delete_terminal_group,
+            list_terminal_groups,
+            write_to_group,
+            get_terminal_metrics,
+            get_all_terminal_metrics,
+            paste_to_terminal,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            run_snippet,
+            list_container_profiles,
+            save_container_profile,
+            delete_container_profile,
+            get_devcontainer_config,
+            list_ssh_profiles,
+            save_ssh_profile,
+            delete_ssh_profile,
+            spawn_ssh_terminal,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,279 @@
+//! Backend logging.
+//!
+//! Code logs through the `log` macros with a subsystem as the target, e.g.
+//! `log::warn!(target: "sandbox", "bwrap failed ...")`. The installed logger
+//! fans each record out to:
+//!
+//! - stderr, as before,
+//! - a size-rotated `backend.log` in the app log dir,
+//! - an in-memory ring buffer served by `get_recent_logs`,
+//! - a `backend-log` event once an event sink is attached.
+//!
+//! Records from dependencies (tauri, wry, notify, ...) only reach stderr and
+//! the log file, at warn level and above.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+
+/// Targets used by the backend's own log calls.
+pub const SUBSYSTEMS: &[&str] = &["pty", "sandbox", "hidden-terminal", "fs", "git", "watcher"];
+
+const RING_CAPACITY: usize = 2000;
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+const ROTATED_FILES: usize = 3;
+const LOG_FILE_NAME: &str = "backend.log";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub level: String,
+    pub subsystem: String,
+    pub message: String,
+}
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    written: u64,
+}
+
+struct BackendLogger {
+    level: LevelFilter,
+    recent: Mutex<VecDeque<LogEntry>>,
+    file: Mutex<Option<LogFile>>,
+    sink: RwLock<Option<SharedSink>>,
+}
+
+static LOGGER: OnceLock<BackendLogger> = OnceLock::new();
+
+/// Install the backend logger. `log_dir` enables the rotating log file; the
+/// level defaults to debug in dev builds and info in release, and can be
+/// overridden with `BACKEND_LOG=<level>`. Safe to call more than once.
+pub fn init(log_dir: Option<PathBuf>) {
+    let default_level = if cfg!(debug_assertions) { LevelFilter::Debug } else { LevelFilter::Info };
+    let level = std::env::var("BACKEND_LOG")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default_level);
+
+    let logger = LOGGER.get_or_init(|| BackendLogger {
+        level,
+        recent: Mutex::new(VecDeque::with_capacity(RING_CAPACITY)),
+        file: Mutex::new(None),
+        sink: RwLock::new(None),
+    });
+
+    if let Some(dir) = log_dir {
+        match open_log_file(dir) {
+            Ok(file) => {
+                if let Ok(mut slot) = logger.file.lock() {
+                    *slot = Some(file);
+                }
+            }
+            Err(e) => eprintln!("[logging] failed to open log file: {}", e),
+        }
+    }
+
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Forward backend records to `sink` as `backend-log` events.
+pub fn set_event_sink(sink: SharedSink) {
+    if let Some(logger) = LOGGER.get() {
+        if let Ok(mut slot) = logger.sink.write() {
+            *slot = Some(sink);
+        }
+    }
+}
+
+/// Buffered records at or above `min_level`, optionally for one subsystem,
+/// oldest first.
+pub fn recent_logs(min_level: Option<Level>, subsystem: Option<&str>) -> Vec<LogEntry> {
+    let Some(logger) = LOGGER.get() else {
+        return Vec::new();
+    };
+    let Ok(recent) = logger.recent.lock() else {
+        return Vec::new();
+    };
+    recent
+        .iter()
+        .filter(|entry| {
+            min_level.is_none_or(|min| {
+                entry.level.parse::<Level>().map(|l| l <= min).unwrap_or(true)
+            })
+        })
+        .filter(|entry| subsystem.is_none_or(|s| entry.subsystem == s))
+        .cloned()
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_recent_logs(level: Option<String>, subsystem: Option<String>) -> AppResult<Vec<LogEntry>> {
+    let min_level = level
+        .map(|l| {
+            l.parse::<Level>()
+                .map_err(|_| AppError::new(ErrorCode::InvalidInput, format!("Unknown log level: {}", l)))
+        })
+        .transpose()?;
+    Ok(recent_logs(min_level, subsystem.as_deref()))
+}
+
+fn is_backend_target(target: &str) -> bool {
+    SUBSYSTEMS.contains(&target)
+}
+
+impl Log for BackendLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if is_backend_target(metadata.target()) {
+            metadata.level() <= self.level
+        } else {
+            metadata.level() <= Level::Warn
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let own = is_backend_target(record.target());
+        let subsystem = if own {
+            record.target().to_string()
+        } else {
+            // Collapse `tauri::manager` etc. to the crate name
+            record.target().split("::").next().unwrap_or("").to_string()
+        };
+        let entry = LogEntry {
+            timestamp: now_millis(),
+            level: record.level().as_str().to_lowercase(),
+            subsystem,
+            message: record.args().to_string(),
+        };
+        let line = format!(
+            "{} {:<5} [{}] {}",
+            format_timestamp(entry.timestamp),
+            record.level(),
+            entry.subsystem,
+            entry.message
+        );
+
+        eprintln!("{}", line);
+        self.write_file(&line);
+
+        if !own {
+            // Emitting from inside a dependency's log call could recurse back
+            // into tauri, so foreign records stop here.
+            return;
+        }
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RING_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(entry.clone());
+        }
+
+        let sink = self.sink.read().ok().and_then(|s| s.clone());
+        if let Some(sink) = sink {
+            if let Ok(payload) = serde_json::to_value(&entry) {
+                sink.emit_event("backend-log", payload);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut slot) = self.file.lock() {
+            if let Some(log_file) = slot.as_mut() {
+                let _ = log_file.file.flush();
+            }
+        }
+    }
+}
+
+impl BackendLogger {
+    fn write_file(&self, line: &str) {
+        let Ok(mut slot) = self.file.lock() else {
+            return;
+        };
+        let Some(log_file) = slot.as_mut() else {
+            return;
+        };
+
+        if log_file.written >= MAX_FILE_BYTES {
+            match rotate(&log_file.dir) {
+                Ok(file) => *log_file = file,
+                Err(_) => return,
+            }
+        }
+        if writeln!(log_file.file, "{}", line).is_ok() {
+            log_file.written += line.len() as u64 + 1;
+        }
+    }
+}
+
+fn open_log_file(dir: PathBuf) -> std::io::Result<LogFile> {
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(LOG_FILE_NAME);
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+    Ok(LogFile { dir, file, written })
+}
+
+/// Shift `backend.log.N` up by one, dropping the oldest, and start a fresh file.
+fn rotate(dir: &std::path::Path) -> std::io::Result<LogFile> {
+    for i in (1..ROTATED_FILES).rev() {
+        let from = dir.join(format!("{}.{}", LOG_FILE_NAME, i));
+        if from.exists() {
+            fs::rename(&from, dir.join(format!("{}.{}", LOG_FILE_NAME, i + 1)))?;
+        }
+    }
+    fs::rename(dir.join(LOG_FILE_NAME), dir.join(format!("{}.1", LOG_FILE_NAME)))?;
+    open_log_file(dir.to_path_buf())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Format epoch milliseconds as `YYYY-MM-DDTHH:MM:SS.mmmZ`.
+fn format_timestamp(millis: u64) -> String {
+    let secs = millis / 1000;
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days (Howard Hinnant), valid for the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        millis % 1000
+    )
+}
@@ -1,11 +1,25 @@
-use tauri::{AppHandle, Emitter};
-use std::io::Read;
-use std::sync::atomic::Ordering;
-use std::thread;
-use uuid::Uuid;
+use tauri::{AppHandle, Emitter, Manager};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::{Access, AccessPolicy};
 use crate::state::AppState;
-use crate::pty::manager;
+use crate::pty::container::{self, ContainerProfile};
+use crate::pty::cwd_watcher;
+use crate::pty::devcontainer::{self, DevcontainerReport};
+use crate::pty::expect::{self, ExpectMatch};
+use crate::pty::groups::{self, GroupWriteResult, TerminalGroup};
+use crate::pty::metrics::{self, AggregateMetrics, TerminalMetrics};
+use crate::pty::paste::{self, PasteResult};
+use crate::pty::snippets::{self, Snippet, SnippetRun};
+use crate::pty::ssh::{self, SshProfile};
+use crate::pty::manager::{self, SessionKind, SpawnOptions};
+use crate::pty::screen::{ScreenMatch, ScreenSnapshot, TerminalLines};
+use crate::pty::shell_integration::CommandRecord;
 
+// Tauri commands take their arguments flat from the JS call
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub fn spawn_terminal(
     rows: u16,
@@ -13,66 +27,42 @@ pub fn spawn_terminal(
     sandbox: bool,
     sandbox_no_net: bool,
     project_dir: Option<String>,
+    shell_integration: Option<bool>,
+    container: Option<String>,
+    devcontainer: Option<bool>,
     app: AppHandle,
     state: tauri::State<AppState>,
-) -> Result<serde_json::Value, String> {
-    let session_id = Uuid::new_v4().to_string();
-    let session = manager::spawn_pty(rows, cols, sandbox, sandbox_no_net, project_dir)?;
-    let actually_sandboxed = session.sandboxed;
-
-    let mut reader = session
-        .master
-        .try_clone_reader()
-        .map_err(|e| format!("Failed to clone reader: {}", e))?;
-
-    let shutdown_flag = session.shutdown.clone();
-    let session_id_clone = session_id.clone();
-    let app_clone = app.clone();
-    thread::spawn(move || {
-        let mut buf = [0u8; 8192];
-        loop {
-            if shutdown_flag.load(Ordering::SeqCst) {
-                break;
-            }
-            match reader.read(&mut buf) {
-                Ok(n) if n > 0 => {
-                    if shutdown_flag.load(Ordering::SeqCst) {
-                        break;
-                    }
-                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = app_clone.emit("terminal-output", serde_json::json!({
-                        "session_id": session_id_clone,
-                        "data": data,
-                    }));
-                }
-                Ok(_) => {
-                    if !shutdown_flag.load(Ordering::SeqCst) {
-                        let _ = app_clone.emit("terminal-output", serde_json::json!({
-                            "session_id": session_id_clone,
-                            "data": "\r\n[Process exited]\r\n",
-                        }));
-                    }
-                    break;
-                }
-                Err(e) => {
-                    if !shutdown_flag.load(Ordering::SeqCst) {
-                        eprintln!("Error reading from PTY: {}", e);
-                    }
-                    break;
-                }
-            }
+) -> AppResult<serde_json::Value> {
+    let container = match (container, devcontainer.unwrap_or(false)) {
+        (Some(_), true) => {
+            return Err(AppError::new(ErrorCode::InvalidInput, "Use either a container profile or the devcontainer"));
         }
-    });
-
-    state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?
-        .pty_sessions
-        .insert(session_id.clone(), session);
+        (Some(name), false) => Some(container::find_profile(&config_dir(&app)?, &name)?),
+        (None, true) => {
+            let dir = project_dir.as_deref().ok_or_else(|| {
+                AppError::new(ErrorCode::InvalidInput, "Devcontainer mode needs a project_dir")
+            })?;
+            Some(devcontainer::profile_for(&devcontainer::load_config(Path::new(dir))?))
+        }
+        (None, false) => None,
+    };
+    let opts = SpawnOptions {
+        rows,
+        cols,
+        sandbox,
+        sandbox_no_net,
+        project_dir,
+        command: None,
+        shell_integration: shell_integration.unwrap_or(true),
+        container,
+        config_dir: config_dir(&app).ok(),
+    };
+    let spawned = manager::start_session(&state, &opts, SessionKind::Interactive, Arc::new(app))?;
 
     Ok(serde_json::json!({
-        "session_id": session_id,
-        "sandboxed": actually_sandboxed,
+        "session_id": spawned.session_id,
+        "sandboxed": spawned.sandboxed,
+        "isolation": spawned.isolation,
     }))
 }
 
@@ -81,15 +71,271 @@ pub fn write_to_terminal(
     session_id: String,
     data: String,
     state: tauri::State<AppState>,
-) -> Result<(), String> {
-    let mut state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-    let session = state_lock
-        .pty_sessions
-        .get_mut(&session_id)
-        .ok_or_else(|| format!("Session not found: {}", session_id))?;
-    manager::write_to_pty(session, &data)
+) -> AppResult<()> {
+    manager::write_session(&state, &session_id, &data)
+}
+
+/// Commands the shell reported through OSC 133, oldest first.
+#[tauri::command]
+pub fn get_command_history(
+    session_id: String,
+    state: tauri::State<AppState>,
+) -> AppResult<Vec<CommandRecord>> {
+    manager::command_history(&state, &session_id)
+}
+
+/// Push `terminal-cwd-changed` events for a session instead of polling
+/// `get_terminal_cwd`. Returns the current cwd.
+#[tauri::command]
+pub fn subscribe_terminal_cwd(
+    session_id: String,
+    app: AppHandle,
+    state: tauri::State<AppState>,
+) -> AppResult<String> {
+    cwd_watcher::subscribe(&state, &session_id, Arc::new(app))
+}
+
+#[tauri::command]
+pub fn unsubscribe_terminal_cwd(
+    session_id: String,
+    state: tauri::State<AppState>,
+) -> AppResult<()> {
+    cwd_watcher::unsubscribe(&state, &session_id)
+}
+
+/// Visible rows of a session as text, with the cursor position.
+#[tauri::command]
+pub fn get_terminal_screen(
+    session_id: String,
+    state: tauri::State<AppState>,
+) -> AppResult<ScreenSnapshot> {
+    manager::terminal_screen(&state, &session_id)
+}
+
+/// Lines `start..end` of scrollback followed by the screen; `end` defaults
+/// to the last line.
+#[tauri::command]
+pub fn get_terminal_lines(
+    session_id: String,
+    start: usize,
+    end: Option<usize>,
+    state: tauri::State<AppState>,
+) -> AppResult<TerminalLines> {
+    manager::terminal_lines(&state, &session_id, start, end)
+}
+
+#[tauri::command]
+pub fn search_terminal(
+    session_id: String,
+    pattern: String,
+    state: tauri::State<AppState>,
+) -> AppResult<Vec<ScreenMatch>> {
+    manager::search_terminal(&state, &session_id, &pattern)
+}
+
+/// Resolve once `pattern` (a regex) appears in output produced after the
+/// call. Escape sequences are stripped before matching.
+#[tauri::command]
+pub async fn wait_for_output(
+    session_id: String,
+    pattern: String,
+    timeout_ms: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<ExpectMatch> {
+    let state = state.inner().clone();
+    // Waiting blocks, so keep it off the command thread
+    tauri::async_runtime::spawn_blocking(move || {
+        expect::wait_for_output(&state, &session_id, &pattern, timeout_ms.map(Duration::from_millis))
+    })
+    .await
+    .map_err(|e| AppError::new(ErrorCode::Pty, format!("Wait failed: {}", e)))?
+}
+
+/// Write `input`, then wait for `pattern` in the output that follows.
+#[tauri::command]
+pub async fn send_and_expect(
+    session_id: String,
+    input: String,
+    pattern: String,
+    timeout_ms: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<ExpectMatch> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        expect::send_and_expect(&state, &session_id, &input, &pattern, timeout_ms.map(Duration::from_millis))
+    })
+    .await
+    .map_err(|e| AppError::new(ErrorCode::Pty, format!("Wait failed: {}", e)))?
+}
+
+#[tauri::command]
+pub fn create_terminal_group(
+    name: Option<String>,
+    session_ids: Option<Vec<String>>,
+    state: tauri::State<AppState>,
+) -> AppResult<TerminalGroup> {
+    groups::create_group(&state, name, session_ids.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn add_to_terminal_group(
+    group_id: String,
+    session_ids: Vec<String>,
+    state: tauri::State<AppState>,
+) -> AppResult<TerminalGroup> {
+    groups::add_members(&state, &group_id, session_ids)
+}
+
+#[tauri::command]
+pub fn remove_from_terminal_group(
+    group_id: String,
+    session_ids: Vec<String>,
+    state: tauri::State<AppState>,
+) -> AppResult<TerminalGroup> {
+    groups::remove_members(&state, &group_id, &session_ids)
+}
+
+#[tauri::command]
+pub fn delete_terminal_group(group_id: String, state: tauri::State<AppState>) -> AppResult<()> {
+    groups::delete_group(&state, &group_id)
+}
+
+#[tauri::command]
+pub fn list_terminal_groups(state: tauri::State<AppState>) -> Vec<TerminalGroup> {
+    groups::list_groups(&state)
+}
+
+/// Send the same input to every live member of a group.
+#[tauri::command]
+pub fn write_to_group(
+    group_id: String,
+    data: String,
+    state: tauri::State<AppState>,
+) -> AppResult<Vec<GroupWriteResult>> {
+    groups::write_to_group(&state, &group_id, &data)
+}
+
+#[tauri::command]
+pub fn get_terminal_metrics(
+    session_id: String,
+    state: tauri::State<AppState>,
+) -> AppResult<TerminalMetrics> {
+    metrics::terminal_metrics(&state, &session_id)
+}
+
+/// Totals and per-session metrics for all live sessions.
+#[tauri::command]
+pub fn get_all_terminal_metrics(state: tauri::State<AppState>) -> AggregateMetrics {
+    metrics::aggregate_metrics(&state)
+}
+
+/// Paste text safely. Multi-line text comes back with
+/// `needs_confirmation` unless `confirmed` is set.
+#[tauri::command]
+pub fn paste_to_terminal(
+    session_id: String,
+    text: String,
+    confirmed: Option<bool>,
+    state: tauri::State<AppState>,
+) -> AppResult<PasteResult> {
+    paste::paste_to_session(&state, &session_id, &text, confirmed.unwrap_or(false))
+}
+
+fn config_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| AppError::new(ErrorCode::Io, format!("Failed to resolve config dir: {}", e)))
+}
+
+#[tauri::command]
+pub fn list_snippets(app: AppHandle) -> AppResult<Vec<Snippet>> {
+    snippets::load_snippets(&config_dir(&app)?)
+}
+
+/// Add or replace (by name) a snippet. Returns the updated list.
+#[tauri::command]
+pub fn save_snippet(snippet: Snippet, app: AppHandle) -> AppResult<Vec<Snippet>> {
+    snippets::save_snippet(&config_dir(&app)?, snippet)
+}
+
+#[tauri::command]
+pub fn delete_snippet(name: String, app: AppHandle) -> AppResult<Vec<Snippet>> {
+    snippets::delete_snippet(&config_dir(&app)?, &name)
+}
+
+/// Replay a snippet's steps into a session.
+#[tauri::command]
+pub async fn run_snippet(
+    session_id: String,
+    name: String,
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> AppResult<SnippetRun> {
+    let dir = config_dir(&app)?;
+    let state = state.inner().clone();
+    // Step delays block, so keep them off the command thread
+    tauri::async_runtime::spawn_blocking(move || snippets::run_snippet(&dir, &state, &session_id, &name))
+        .await
+        .map_err(|e| AppError::new(ErrorCode::Pty, format!("Snippet failed: {}", e)))?
+}
+
+#[tauri::command]
+pub fn list_container_profiles(app: AppHandle) -> AppResult<Vec<ContainerProfile>> {
+    container::load_profiles(&config_dir(&app)?)
+}
+
+/// Add or replace (by name) a container profile. Returns the updated list.
+#[tauri::command]
+pub fn save_container_profile(profile: ContainerProfile, app: AppHandle) -> AppResult<Vec<ContainerProfile>> {
+    container::save_profile(&config_dir(&app)?, profile)
+}
+
+#[tauri::command]
+pub fn delete_container_profile(name: String, app: AppHandle) -> AppResult<Vec<ContainerProfile>> {
+    container::delete_profile(&config_dir(&app)?, &name)
+}
+
+/// The project's parsed devcontainer config, the unsupported keys in it, and
+/// the command `spawn_terminal` would run for it.
+#[tauri::command]
+pub fn get_devcontainer_config(project_dir: String, sandbox_no_net: Option<bool>) -> AppResult<DevcontainerReport> {
+    devcontainer::inspect(Path::new(&project_dir), sandbox_no_net.unwrap_or(false))
+}
+
+#[tauri::command]
+pub fn list_ssh_profiles(app: AppHandle) -> AppResult<Vec<SshProfile>> {
+    ssh::load_profiles(&config_dir(&app)?)
+}
+
+/// Add or replace (by name) an SSH profile. Returns the updated list.
+#[tauri::command]
+pub fn save_ssh_profile(profile: SshProfile, app: AppHandle) -> AppResult<Vec<SshProfile>> {
+    ssh::save_profile(&config_dir(&app)?, profile)
+}
+
+#[tauri::command]
+pub fn delete_ssh_profile(name: String, app: AppHandle) -> AppResult<Vec<SshProfile>> {
+    ssh::delete_profile(&config_dir(&app)?, &name)
+}
+
+/// Open a terminal on the host of a saved SSH profile. Connection progress
+/// arrives as `ssh-connection-state` events.
+#[tauri::command]
+pub fn spawn_ssh_terminal(
+    profile: String,
+    rows: u16,
+    cols: u16,
+    app: AppHandle,
+    state: tauri::State<AppState>,
+) -> AppResult<serde_json::Value> {
+    let dir = config_dir(&app)?;
+    let spawned = ssh::start_profile_session(&dir, &state, &profile, rows, cols, Arc::new(app))?;
+
+    Ok(serde_json::json!({
+        "session_id": spawned.session_id,
+        "sandboxed": spawned.sandboxed,
+        "isolation": spawned.isolation,
+    }))
 }
 
 #[tauri::command]
@@ -98,15 +344,8 @@ pub fn resize_terminal(
     rows: u16,
     cols: u16,
     state: tauri::State<AppState>,
-) -> Result<(), String> {
-    let mut state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-    let session = state_lock
-        .pty_sessions
-        .get_mut(&session_id)
-        .ok_or_else(|| format!("Session not found: {}", session_id))?;
-    manager::resize_pty(session, rows, cols)
+) -> AppResult<()> {
+    manager::resize_session(&state, &session_id, rows, cols)
 }
 
 #[tauri::command]
@@ -114,13 +353,8 @@ pub fn close_terminal(
     session_id: String,
     app: AppHandle,
     state: tauri::State<AppState>,
-) -> Result<(), String> {
-    let mut state_lock = state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?;
-    if let Some(mut session) = state_lock.pty_sessions.remove(&session_id) {
-        session.shutdown.store(true, Ordering::SeqCst);
-        let _ = session.child.kill();
+) -> AppResult<()> {
+    if manager::close_session(&state, &session_id)? {
         let _ = app.emit(
             "terminal-closed",
             serde_json::json!({"session_id": session_id}),
@@ -135,124 +369,137 @@ pub fn spawn_hidden_terminal(
     command: String,
     app: AppHandle,
     state: tauri::State<AppState>,
-) -> Result<String, String> {
-    use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-    use std::sync::atomic::AtomicBool;
-    use std::sync::Arc;
-
-    let session_id = Uuid::new_v4().to_string();
-    eprintln!("[hidden-terminal] Spawning: {} in {}", command, project_dir);
-
-    let pty_system = NativePtySystem::default();
-    let pty_pair = pty_system
-        .openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| format!("Failed to open PTY: {}", e))?;
-
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-    let mut cmd = CommandBuilder::new(&shell);
-    cmd.args(&["-lc", &command]);
-    cmd.env("TERM", "xterm-256color");
-    cmd.cwd(&project_dir);
-
-    let child = pty_pair
-        .slave
-        .spawn_command(cmd)
-        .map_err(|e| format!("Failed to spawn hidden terminal: {}", e))?;
-
-    let master = pty_pair.master;
-    let writer = master
-        .take_writer()
-        .map_err(|e| format!("Failed to take writer: {}", e))?;
-
-    let mut reader = master
-        .try_clone_reader()
-        .map_err(|e| format!("Failed to clone reader: {}", e))?;
-
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_flag = shutdown.clone();
-
-    let session_id_clone = session_id.clone();
-    let app_clone = app.clone();
-    let state_inner = state.inner().clone();
-
-    thread::spawn(move || {
-        let mut buf = [0u8; 8192];
-        loop {
-            if shutdown_flag.load(Ordering::SeqCst) {
-                break;
-            }
-            match reader.read(&mut buf) {
-                Ok(n) if n > 0 => {
-                    if shutdown_flag.load(Ordering::SeqCst) {
-                        break;
-                    }
-                    let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                    let _ = app_clone.emit(
-                        "hidden-terminal-output",
-                        serde_json::json!({
-                            "session_id": session_id_clone,
-                            "data": data,
-                        }),
-                    );
-                }
-                Ok(_) => {
-                    let _ = app_clone.emit(
-                        "hidden-terminal-closed",
-                        serde_json::json!({"session_id": session_id_clone}),
-                    );
-                    if let Ok(mut st) = state_inner.lock() {
-                        st.pty_sessions.remove(&session_id_clone);
-                    }
-                    break;
-                }
-                Err(_) => {
-                    let _ = app_clone.emit(
-                        "hidden-terminal-closed",
-                        serde_json::json!({"session_id": session_id_clone, "error": true}),
-                    );
-                    if let Ok(mut st) = state_inner.lock() {
-                        st.pty_sessions.remove(&session_id_clone);
-                    }
-                    break;
-                }
-            }
-        }
-    });
-
-    let session = crate::state::PtySession {
-        master,
-        child,
-        writer,
-        shutdown,
-        sandboxed: false,
+) -> AppResult<String> {
+    log::info!(target: "hidden-terminal", "Spawning: {} in {}", command, project_dir);
+
+    let opts = SpawnOptions {
+        project_dir: Some(project_dir),
+        command: Some(command),
+        ..SpawnOptions::default()
     };
+    let spawned = manager::start_session(&state, &opts, SessionKind::Hidden, Arc::new(app))?;
+
+    Ok(spawned.session_id)
+}
 
-    state
-        .lock()
-        .map_err(|e| format!("Failed to lock state: {}", e))?
-        .pty_sessions
-        .insert(session_id.clone(), session);
+/// Subcommands `run_git_command` runs. All of them only read the
+/// repository; global options before the subcommand aren't accepted.
+const GIT_SUBCOMMANDS: &[&str] = &["status", "log", "show", "diff", "rev-parse", "ls-files", "blame"];
 
-    Ok(session_id)
+/// Options that write files, read from outside the repository or turn the
+/// configured diff programs back on.
+const BLOCKED_GIT_OPTIONS: &[&str] = &["--output", "--no-index", "--ext-diff", "--textconv", "--show-signature"];
+
+/// Config overrides for every `run_git_command`. A sandboxed shell can
+/// write `.git/config`, so nothing it names may run: no fsmonitor hook and
+/// no signature verification program.
+const GIT_SAFE_CONFIG: &[&str] = &[
+    "core.fsmonitor=false",
+    "log.showSignature=false",
+    "gpg.program=false",
+    "gpg.ssh.program=false",
+    "gpg.x509.program=false",
+];
+
+/// `args` with the overrides that keep repository config from running
+/// programs: [`GIT_SAFE_CONFIG`], every configured filter driver cleared,
+/// and external diff and textconv drivers turned off.
+fn hardened_git_args(repo: &Path, args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    for setting in GIT_SAFE_CONFIG {
+        out.extend(["-c".to_string(), setting.to_string()]);
+    }
+    // Clean and process filters run on worktree files for status and diff
+    let filters = std::process::Command::new("git")
+        .args(["config", "--name-only", "--get-regexp", r"^filter\."])
+        .current_dir(repo)
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).to_string())
+        .unwrap_or_default();
+    let mut drivers: Vec<&str> = filters.lines().filter_map(|key| key.rsplit_once('.')).map(|(driver, _)| driver).collect();
+    drivers.sort_unstable();
+    drivers.dedup();
+    for driver in drivers {
+        for key in ["clean", "smudge", "process"] {
+            out.extend(["-c".to_string(), format!("{}.{}=", driver, key)]);
+        }
+    }
+    let Some((subcommand, rest)) = args.split_first() else {
+        return out;
+    };
+    out.push(subcommand.clone());
+    let off: &[&str] = match subcommand.as_str() {
+        "diff" | "log" | "show" => &["--no-ext-diff", "--no-textconv"],
+        "blame" => &["--no-textconv"],
+        _ => &[],
+    };
+    out.extend(off.iter().map(|opt| opt.to_string()));
+    out.extend(rest.iter().cloned());
+    out
+}
+
+/// Check `args` for `run_git_command`: an allowed subcommand, no blocked
+/// option, and every argument or `--option=value` that could name a path
+/// inside a root.
+fn check_git_args(policy: &AccessPolicy, repo: &Path, args: &[String]) -> AppResult<()> {
+    let blocked = |arg: &str, reason: &str| {
+        log::warn!(target: "fs", "Blocked git {} in {}: {}", arg, repo.display(), reason);
+        AppError::new(ErrorCode::PermissionDenied, format!("git {} not allowed: {}", reason, arg))
+    };
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Err(AppError::new(ErrorCode::InvalidInput, "No git subcommand"));
+    };
+    if !GIT_SUBCOMMANDS.contains(&subcommand.as_str()) {
+        return Err(blocked(subcommand, "subcommand"));
+    }
+    let mut options = true;
+    for arg in rest {
+        let value = if options && arg == "--" {
+            options = false;
+            continue;
+        } else if options && arg.starts_with('-') {
+            let (name, value) = arg.split_once('=').map_or((arg.as_str(), None), |(n, v)| (n, Some(v)));
+            if BLOCKED_GIT_OPTIONS.contains(&name) {
+                return Err(blocked(arg, "option"));
+            }
+            match value {
+                Some(value) => value,
+                None => continue,
+            }
+        } else {
+            arg.as_str()
+        };
+        policy.check(&repo.join(value), Access::Read)?;
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub fn run_git_command(repo_path: String, args: Vec<String>) -> Result<String, String> {
+pub fn run_git_command(repo_path: String, args: Vec<String>, state: tauri::State<AppState>) -> AppResult<String> {
+    let policy = AccessPolicy::from_state(&state);
+    let repo = policy.check(Path::new(&repo_path), Access::Read)?;
+    check_git_args(&policy, &repo, &args)?;
+    let args = hardened_git_args(&repo, &args);
+    git_command(repo.to_string_lossy().to_string(), args)
+}
+
+/// Run git in `repo_path` and return its stdout.
+pub fn git_command(repo_path: String, args: Vec<String>) -> AppResult<String> {
     let output = std::process::Command::new("git")
         .args(&args)
         .current_dir(&repo_path)
         .output()
-        .map_err(|e| format!("Failed to run git: {}", e))?;
+        .map_err(|e| AppError::io("Failed to run git", &e).with_path(&repo_path))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
         if !stderr.is_empty() {
-            return Err(stderr);
+            let code = if stderr.contains("not a git repository") {
+                ErrorCode::NotAGitRepository
+            } else {
+                ErrorCode::Git
+            };
+            return Err(AppError::new(code, stderr).with_path(&repo_path));
         }
     }
 
@@ -0,0 +1,356 @@
+//! Container-backed sessions: the shell runs under `podman` or `docker`
+//! instead of bwrap.
+//!
+//! A profile either starts a fresh container from `image` (`run -it --rm`,
+//! with the project dir mounted and networking off when `sandbox_no_net` is
+//! set), builds that image first from a Dockerfile (`build`; the build runs
+//! in the terminal so its output is visible), or attaches to a running
+//! container by name (`exec -it`). Profiles live in
+//! `container_profiles.json` in the app config dir. The session reports
+//! `sandboxed: true` with isolation `container`. Containers a session started
+//! are removed when it closes.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config_store;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::pty::cwd_watcher::CwdWatch;
+use crate::pty::manager::{new_session, spawn_in_pty, Isolation, SpawnOptions};
+use crate::state::PtySession;
+
+const STORE_FILE: &str = "container_profiles.json";
+/// Start bash if the image has it, else sh.
+pub(crate) const DEFAULT_SHELL: &str = "if command -v bash >/dev/null 2>&1; then exec bash -l; else exec sh -l; fi";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerRuntime {
+    Podman,
+    Docker,
+}
+
+impl ContainerRuntime {
+    fn program(self) -> &'static str {
+        match self {
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::Docker => "docker",
+        }
+    }
+
+    /// The first runtime found on `PATH`, preferring podman.
+    fn detect() -> Option<Self> {
+        [ContainerRuntime::Podman, ContainerRuntime::Docker]
+            .into_iter()
+            .find(|runtime| find_in_path(runtime.program()).is_some())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ContainerProfile {
+    pub name: String,
+    /// Defaults to podman if installed, else docker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<ContainerRuntime>,
+    /// Image to start a new container from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Dockerfile to build the image from, instead of `image`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<ContainerBuild>,
+    /// Running container to `exec` into instead of starting one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// Extra mounts, `src:dst[:opts]` (passed as `-v`) or `key=value,...`
+    /// (passed as `--mount`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Where the project dir is mounted and the shell starts. Defaults to
+    /// the project dir's host path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<String>,
+    /// Passed as `--user`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Shell command line to run instead of the default login shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ContainerBuild {
+    /// Absolute path of the Dockerfile
+    pub dockerfile: String,
+    /// Absolute path of the build context
+    pub context: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl ContainerBuild {
+    /// Tag for the built image, stable per Dockerfile and context so
+    /// rebuilds reuse the runtime's layer cache.
+    fn tag(&self) -> String {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (&self.dockerfile, &self.context).hash(&mut hasher);
+        format!("tauri-terminal-build-{:016x}", hasher.finish())
+    }
+
+    fn command(&self, runtime: ContainerRuntime) -> Vec<String> {
+        let mut argv = vec![runtime.program().to_string(), "build".to_string()];
+        argv.extend(["-f".to_string(), self.dockerfile.clone(), "-t".to_string(), self.tag()]);
+        for (key, val) in &self.args {
+            argv.extend(["--build-arg".to_string(), format!("{}={}", key, val)]);
+        }
+        if let Some(ref target) = self.target {
+            argv.extend(["--target".to_string(), target.clone()]);
+        }
+        argv.extend(["--".to_string(), self.context.clone()]);
+        argv
+    }
+}
+
+/// A container started for a session, removed when the session closes.
+#[derive(Clone, Debug)]
+pub struct ContainerRef {
+    pub runtime: ContainerRuntime,
+    pub name: String,
+}
+
+/// All saved profiles, in the order they were first saved.
+pub fn load_profiles(config_dir: &Path) -> AppResult<Vec<ContainerProfile>> {
+    config_store::load(config_dir, STORE_FILE, "container profiles")
+}
+
+fn store_profiles(config_dir: &Path, profiles: &[ContainerProfile]) -> AppResult<()> {
+    config_store::store(config_dir, STORE_FILE, "container profiles", profiles)
+}
+
+/// Add a profile, or replace the one with the same name.
+pub fn save_profile(config_dir: &Path, profile: ContainerProfile) -> AppResult<Vec<ContainerProfile>> {
+    validate(&profile)?;
+    let mut profiles = load_profiles(config_dir)?;
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    store_profiles(config_dir, &profiles)?;
+    Ok(profiles)
+}
+
+pub fn delete_profile(config_dir: &Path, name: &str) -> AppResult<Vec<ContainerProfile>> {
+    let mut profiles = load_profiles(config_dir)?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+    if profiles.len() == before {
+        return Err(profile_not_found(name));
+    }
+    store_profiles(config_dir, &profiles)?;
+    Ok(profiles)
+}
+
+/// The saved profile called `name`.
+pub fn find_profile(config_dir: &Path, name: &str) -> AppResult<ContainerProfile> {
+    load_profiles(config_dir)?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| profile_not_found(name))
+}
+
+fn profile_not_found(name: &str) -> AppError {
+    AppError::new(ErrorCode::NotFound, format!("Container profile not found: {}", name))
+}
+
+/// Reject profiles that name no container, or values the runtime would
+/// parse as options.
+fn validate(profile: &ContainerProfile) -> AppResult<()> {
+    let invalid = |message: &str| Err(AppError::new(ErrorCode::InvalidInput, message.to_string()));
+    let bad_word = |s: &str| s.is_empty() || s.starts_with('-') || s.chars().any(char::is_whitespace);
+    if profile.name.trim().is_empty() {
+        return invalid("Container profile name must not be empty");
+    }
+    let sources = [profile.image.is_some(), profile.build.is_some(), profile.container.is_some()];
+    match sources.iter().filter(|set| **set).count() {
+        0 => return invalid("Container profile needs an image, a build or a container"),
+        1 => {}
+        _ => return invalid("Set only one of image, build and container"),
+    }
+    if profile.image.iter().chain(&profile.container).any(|name| bad_word(name)) {
+        return invalid("Invalid image or container name");
+    }
+    if let Some(ref build) = profile.build {
+        if !build.dockerfile.starts_with('/') || !build.context.starts_with('/') {
+            return invalid("Build Dockerfile and context must be absolute paths");
+        }
+        if build.args.keys().any(|k| k.is_empty() || k.contains('=')) {
+            return invalid("Invalid build argument name");
+        }
+        if build.target.as_deref().is_some_and(bad_word) {
+            return invalid("Invalid build target");
+        }
+    }
+    if profile.container.is_some() && !profile.mounts.is_empty() {
+        return invalid("Mounts only apply when starting a container from an image");
+    }
+    if profile.env.keys().any(|k| k.is_empty() || k.contains('=')) {
+        return invalid("Invalid environment variable name");
+    }
+    if profile.user.as_deref().is_some_and(bad_word) {
+        return invalid("Invalid container user");
+    }
+    if profile.workdir.as_deref().is_some_and(|w| !w.starts_with('/')) {
+        return invalid("Container workdir must be an absolute path");
+    }
+    Ok(())
+}
+
+/// `name`'s path in one of the `PATH` directories.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The runtime command line for a session, and the container it creates (if
+/// any).
+pub fn container_command(
+    profile: &ContainerProfile,
+    opts: &SpawnOptions,
+) -> AppResult<(CommandBuilder, Option<ContainerRef>)> {
+    validate(profile)?;
+    let runtime = match profile.runtime {
+        Some(runtime) => runtime,
+        None => ContainerRuntime::detect().ok_or_else(|| {
+            AppError::new(ErrorCode::Unsupported, "Neither podman nor docker was found on PATH")
+        })?,
+    };
+    let project_dir = opts.project_dir.as_deref().filter(|p| Path::new(p).is_dir());
+    let workdir = profile.workdir.as_deref().or(project_dir);
+
+    let built = profile.build.as_ref().map(ContainerBuild::tag);
+    let mut cmd = CommandBuilder::new(runtime.program());
+    let created = match (profile.image.as_ref().or(built.as_ref()), &profile.container) {
+        (Some(image), _) => {
+            let name = format!("tauri-terminal-{}", &Uuid::new_v4().simple().to_string()[..12]);
+            cmd.args(["run", "-it", "--rm", "--init", "--name", &name]);
+            if runtime == ContainerRuntime::Podman {
+                // Files written to the mounts stay owned by the user
+                cmd.arg("--userns=keep-id");
+            }
+            if opts.sandbox_no_net {
+                cmd.args(["--network", "none"]);
+            }
+            if let (Some(host), Some(target)) = (project_dir, workdir) {
+                cmd.args(["-v", &format!("{}:{}", host, target)]);
+            }
+            for mount in &profile.mounts {
+                let flag = if mount.contains('=') { "--mount" } else { "-v" };
+                cmd.args([flag, mount]);
+            }
+            push_common_args(&mut cmd, profile, workdir);
+            cmd.args(["--", image]);
+            Some(ContainerRef { runtime, name })
+        }
+        (None, Some(container)) => {
+            // A running container keeps whatever network it has
+            if opts.sandbox_no_net {
+                return Err(AppError::new(
+                    ErrorCode::InvalidInput,
+                    "Network isolation needs a new container; this profile execs into a running one",
+                ));
+            }
+            cmd.args(["exec", "-it"]);
+            push_common_args(&mut cmd, profile, workdir);
+            cmd.args(["--", container]);
+            None
+        }
+        (None, None) => unreachable!("validated above"),
+    };
+
+    match opts.command {
+        Some(ref command) => cmd.args(["sh", "-lc", command]),
+        None => cmd.args(["sh", "-c", profile.shell.as_deref().unwrap_or(DEFAULT_SHELL)]),
+    }
+    // Build first, in the same PTY, and only start the shell if it worked
+    if let Some(ref build) = profile.build {
+        let script = format!(
+            "{} && exec {}",
+            shell_join(build.command(runtime)),
+            shell_join(cmd.get_argv().iter().map(|a| a.to_string_lossy().to_string())),
+        );
+        cmd = CommandBuilder::new("sh");
+        cmd.args(["-c", &script]);
+    }
+    cmd.env("TERM", "xterm-256color");
+    if let Some(home) = std::env::var_os("HOME") {
+        cmd.cwd(home);
+    }
+    Ok((cmd, created))
+}
+
+/// Quote arguments for `sh -c` so they reach the program unchanged.
+pub(crate) fn shell_join(args: impl IntoIterator<Item = String>) -> String {
+    args.into_iter()
+        .map(|a| {
+            if !a.is_empty() && a.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c)) {
+                a
+            } else {
+                format!("'{}'", a.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Options shared by `run` and `exec`: environment, user and workdir.
+fn push_common_args(cmd: &mut CommandBuilder, profile: &ContainerProfile, workdir: Option<&str>) {
+    cmd.args(["-e", "TERM=xterm-256color"]);
+    for (key, val) in &profile.env {
+        cmd.args(["-e", &format!("{}={}", key, val)]);
+    }
+    if let Some(ref user) = profile.user {
+        cmd.args(["--user", user]);
+    }
+    if let Some(workdir) = workdir {
+        cmd.args(["-w", workdir]);
+    }
+}
+
+/// Spawn a session whose shell runs in a container described by `profile`.
+pub fn spawn_container(opts: &SpawnOptions, profile: &ContainerProfile) -> AppResult<PtySession> {
+    let (cmd, created) = container_command(profile, opts)?;
+    log::info!(target: "sandbox", "container profile={}, argv={:?}", profile.name, cmd.get_argv());
+    let process = spawn_in_pty(cmd, opts.rows, opts.cols)?;
+    let mut session = new_session(process, opts.rows, opts.cols, Isolation::Container, opts.project_dir.clone());
+
+    // Cwds are container paths; they only mean something on the host when
+    // the project is mounted at its own path
+    let workdir = profile.workdir.as_deref();
+    if workdir.is_some() && workdir != opts.project_dir.as_deref() {
+        session.cwd_watch = Arc::new(Mutex::new(CwdWatch::remote()));
+    }
+    session.container = created;
+    Ok(session)
+}
+
+/// Remove a container a session started. Runs in the background; the
+/// container may already be gone through `--rm`.
+pub fn remove_container(container: ContainerRef) {
+    std::thread::spawn(move || {
+        let result = std::process::Command::new(container.runtime.program())
+            .args(["rm", "-f", &container.name])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+        log::debug!(target: "sandbox", "removed container {}: {:?}", container.name, result);
+    });
+}
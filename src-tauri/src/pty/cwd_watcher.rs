@@ -0,0 +1,192 @@
+//! Push-based cwd tracking.
+//!
+//! Every cwd change a session reports goes through [`report_cwd`], which
+//! drops duplicates, emits `terminal-cwd-changed` and, while someone is
+//! subscribed, points the git cache's watcher at the repository the terminal
+//! is now in. Shells with integration report through OSC 7 from the reader
+//! thread; [`subscribe`] adds a sampling thread that picks up changes from
+//! `/proc/<pid>/cwd` (or sysinfo) for shells that don't.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+use crate::fs::{find_git_root, terminal_cwd};
+use crate::state::{lock_session, AppState};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(300);
+/// Consecutive identical samples required before a sampled change is
+/// reported, so a `cd a && cd b` burst only reports `b`.
+const DEBOUNCE_SAMPLES: u32 = 2;
+
+/// Per-session cwd reporting state.
+#[derive(Default)]
+pub struct CwdWatch {
+    /// Last cwd announced through `terminal-cwd-changed`
+    reported: Option<String>,
+    /// Repository the git cache is watching on this session's behalf
+    git_root: Option<PathBuf>,
+    /// Dropping this stops the sampling thread
+    stop: Option<Sender<()>>,
+    /// Cwds name paths on another host, so they never steer the git cache
+    remote: bool,
+}
+
+impl CwdWatch {
+    /// State for a session whose paths aren't host paths (SSH, containers
+    /// with the project mounted elsewhere).
+    pub fn remote() -> Self {
+        Self {
+            remote: true,
+            ..Self::default()
+        }
+    }
+}
+
+pub type SharedCwdWatch = Arc<Mutex<CwdWatch>>;
+
+/// Where a cwd came from, reported as the event's `source`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CwdSource {
+    Osc7,
+    Process,
+}
+
+impl CwdSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            CwdSource::Osc7 => "osc7",
+            CwdSource::Process => "proc",
+        }
+    }
+}
+
+/// Record `cwd` for a session and emit `terminal-cwd-changed` if it differs
+/// from the last reported value.
+pub fn report_cwd(
+    state: &AppState,
+    session_id: &str,
+    watch: &SharedCwdWatch,
+    cwd: String,
+    source: CwdSource,
+    sink: &SharedSink,
+) {
+    let Ok(mut watch) = watch.lock() else {
+        return;
+    };
+    if watch.reported.as_deref() == Some(cwd.as_str()) {
+        return;
+    }
+
+    // Only subscribed sessions steer the git cache; an unobserved terminal
+    // shouldn't keep watchers alive.
+    if watch.stop.is_some() && !watch.remote {
+        let root = find_git_root(std::path::Path::new(&cwd))
+            .map(|r| r.canonicalize().unwrap_or(r));
+        if root != watch.git_root {
+            state.git_cache.retarget(watch.git_root.as_deref(), root.as_deref());
+            watch.git_root = root;
+        }
+    }
+
+    log::debug!(target: "pty", "Session {} cwd -> {} ({})", session_id, cwd, source.as_str());
+    sink.emit_event("terminal-cwd-changed", serde_json::json!({
+        "session_id": session_id,
+        "cwd": cwd,
+        "source": source.as_str(),
+        "git_root": watch.git_root,
+    }));
+    watch.reported = Some(cwd);
+}
+
+/// Start pushing cwd changes for a session. Returns the current cwd, which
+/// is also emitted if it hasn't been reported yet. Subscribing twice is a
+/// no-op.
+pub fn subscribe(state: &AppState, session_id: &str, sink: SharedSink) -> AppResult<String> {
+    let watch = lock_session(&state.session(session_id)?)?.cwd_watch.clone();
+    let cwd = terminal_cwd(state, session_id)?;
+
+    let stop_rx = {
+        let mut guard = watch.lock().map_err(AppError::lock)?;
+        if guard.stop.is_some() {
+            None
+        } else {
+            let (stop_tx, stop_rx) = channel::<()>();
+            guard.stop = Some(stop_tx);
+            // Force the initial report so the git cache gets targeted
+            guard.reported = None;
+            Some(stop_rx)
+        }
+    };
+
+    let source = current_source(state, session_id);
+    report_cwd(state, session_id, &watch, cwd.clone(), source, &sink);
+
+    if let Some(stop_rx) = stop_rx {
+        let state = state.clone();
+        let session_id = session_id.to_string();
+        std::thread::spawn(move || {
+            let mut pending: Option<String> = None;
+            let mut stable = 0;
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(SAMPLE_INTERVAL) {
+                let sample = match terminal_cwd(&state, &session_id) {
+                    Ok(cwd) => cwd,
+                    Err(e) if e.code == ErrorCode::SessionNotFound => break,
+                    // The process may have exited; keep waiting for close
+                    Err(_) => continue,
+                };
+                if pending.as_deref() == Some(sample.as_str()) {
+                    stable += 1;
+                } else {
+                    pending = Some(sample);
+                    stable = 1;
+                }
+                if stable == DEBOUNCE_SAMPLES {
+                    if let Some(cwd) = pending.clone() {
+                        let source = current_source(&state, &session_id);
+                        report_cwd(&state, &session_id, &watch, cwd, source, &sink);
+                    }
+                }
+            }
+        });
+    }
+
+    Ok(cwd)
+}
+
+/// Stop pushing cwd changes for a session and release its git watcher.
+pub fn unsubscribe(state: &AppState, session_id: &str) -> AppResult<()> {
+    let watch = lock_session(&state.session(session_id)?)?.cwd_watch.clone();
+    release(state, &watch);
+    Ok(())
+}
+
+/// Stop sampling and drop the session's claim on its git root. Called on
+/// unsubscribe and when the session goes away.
+pub fn release(state: &AppState, watch: &SharedCwdWatch) {
+    if let Ok(mut watch) = watch.lock() {
+        watch.stop = None;
+        if let Some(root) = watch.git_root.take() {
+            state.git_cache.retarget(Some(&root), None);
+        }
+    }
+}
+
+fn current_source(state: &AppState, session_id: &str) -> CwdSource {
+    let osc7 = state
+        .session(session_id)
+        .ok()
+        .and_then(|handle| {
+            let shell = lock_session(&handle).ok()?.shell.clone();
+            let has_cwd = shell.lock().ok()?.cwd.is_some();
+            Some(has_cwd)
+        })
+        .unwrap_or(false);
+    if osc7 {
+        CwdSource::Osc7
+    } else {
+        CwdSource::Process
+    }
+}
@@ -0,0 +1,648 @@
+//! Devcontainer support: run a project's terminal in the container its
+//! `.devcontainer/devcontainer.json` describes.
+//!
+//! The config is turned into a [`ContainerProfile`] and started through the
+//! container backend, with the project mounted at `workspaceFolder`.
+//! Supported keys are `image`, `build` (`dockerfile`, `context`, `args`,
+//! `target`, plus the older top-level `dockerFile`/`context`), `remoteUser`
+//! and `containerUser`, `containerEnv` and `remoteEnv`, `mounts`,
+//! `workspaceFolder` and `postCreateCommand`. Each terminal gets a fresh
+//! container, so `postCreateCommand` runs before every shell. Environment
+//! values that refer to `${containerEnv:NAME}` are exported by the shell
+//! wrapper inside the container, where that variable has its value. Any other
+//! key is reported in [`DevcontainerConfig::unsupported`] and otherwise
+//! ignored.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::{home_dir, Access, AccessPolicy};
+use crate::pty::container::{self, shell_join, ContainerBuild, ContainerProfile, DEFAULT_SHELL};
+use crate::pty::manager::SpawnOptions;
+
+/// Where a devcontainer config may live, relative to the project dir.
+const CONFIG_PATHS: [&str; 2] = [".devcontainer/devcontainer.json", ".devcontainer.json"];
+
+const SUPPORTED_KEYS: [&str; 12] = [
+    "name",
+    "image",
+    "build",
+    "dockerFile",
+    "context",
+    "remoteUser",
+    "containerUser",
+    "containerEnv",
+    "remoteEnv",
+    "mounts",
+    "workspaceFolder",
+    "postCreateCommand",
+];
+const SUPPORTED_BUILD_KEYS: [&str; 4] = ["dockerfile", "context", "args", "target"];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DevcontainerConfig {
+    pub config_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<ContainerBuild>,
+    /// `remoteUser`, falling back to `containerUser`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_user: Option<String>,
+    /// `containerEnv` with `remoteEnv` on top, for values resolved on the host
+    pub container_env: BTreeMap<String, String>,
+    /// Entries whose values use `${containerEnv:...}`, as shell words that
+    /// the container's shell expands
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub shell_env: BTreeMap<String, String>,
+    pub mounts: Vec<String>,
+    pub workspace_folder: String,
+    /// As a single shell command line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_create_command: Option<String>,
+    /// Ignored keys, e.g. `features` or `build.cacheFrom`
+    pub unsupported: Vec<String>,
+    /// Values that were only partly applied, such as unresolved variables
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DevcontainerReport {
+    #[serde(flatten)]
+    pub config: DevcontainerConfig,
+    /// Profile the terminal is started with
+    pub profile: ContainerProfile,
+    /// Command line the terminal runs, or `None` if neither podman nor docker
+    /// is installed
+    pub command: Option<Vec<String>>,
+}
+
+/// The devcontainer config in `project_dir`, if there is one.
+pub fn find_config(project_dir: &Path) -> Option<PathBuf> {
+    CONFIG_PATHS
+        .iter()
+        .map(|rel| project_dir.join(rel))
+        .find(|path| path.is_file())
+}
+
+/// Parse the devcontainer config in `project_dir`.
+pub fn load_config(project_dir: &Path) -> AppResult<DevcontainerConfig> {
+    let project_dir = project_dir
+        .canonicalize()
+        .map_err(|e| AppError::io("Failed to resolve project dir", &e).with_path(project_dir))?;
+    let path = find_config(&project_dir).ok_or_else(|| {
+        AppError::new(ErrorCode::NotFound, "No .devcontainer/devcontainer.json in project").with_path(&project_dir)
+    })?;
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::io("Failed to read devcontainer config", &e).with_path(&path))?;
+    let value: Value = serde_json::from_str(&strip_jsonc(&text)).map_err(|e| {
+        AppError::new(ErrorCode::InvalidInput, format!("Invalid devcontainer config: {}", e)).with_path(&path)
+    })?;
+    let Value::Object(obj) = value else {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Devcontainer config must be an object").with_path(&path));
+    };
+    parse(&obj, &project_dir, &path)
+}
+
+fn parse(obj: &Map<String, Value>, project_dir: &Path, config_path: &Path) -> AppResult<DevcontainerConfig> {
+    let config_dir = config_path.parent().unwrap_or(project_dir);
+    let mut warnings = Vec::new();
+    let mut unsupported: Vec<String> = obj
+        .keys()
+        .filter(|k| !SUPPORTED_KEYS.contains(&k.as_str()))
+        .cloned()
+        .collect();
+
+    if obj.contains_key("dockerComposeFile") {
+        return Err(AppError::new(ErrorCode::Unsupported, "Docker Compose devcontainers are not supported")
+            .with_path(config_path));
+    }
+
+    let local = project_dir.to_string_lossy().to_string();
+    let mut vars = Variables {
+        local_workspace: local.clone(),
+        container_workspace: String::new(),
+    };
+    let default_folder = format!("/workspaces/{}", basename(&local));
+    let workspace_folder = match string(obj, "workspaceFolder", &mut warnings) {
+        Some(folder) => vars.expand(&folder, "workspaceFolder", &mut warnings),
+        None => default_folder,
+    };
+    vars.container_workspace = workspace_folder.clone();
+
+    let image = string(obj, "image", &mut warnings).map(|i| vars.expand(&i, "image", &mut warnings));
+    let build = build(obj, config_dir, &vars, &mut unsupported, &mut warnings);
+    let (image, build) = match (image, build) {
+        (Some(image), Some(_)) => {
+            warnings.push("Both image and build are set; using image".to_string());
+            (Some(image), None)
+        }
+        (None, None) => {
+            return Err(AppError::new(ErrorCode::InvalidInput, "Devcontainer config has no image or build")
+                .with_path(config_path));
+        }
+        other => other,
+    };
+
+    let remote_user = string(obj, "remoteUser", &mut warnings).or_else(|| string(obj, "containerUser", &mut warnings));
+    let mut container_env = BTreeMap::new();
+    let mut shell_env = BTreeMap::new();
+    for key in ["containerEnv", "remoteEnv"] {
+        for (name, val) in string_map(obj, key, &mut warnings) {
+            let field = format!("{}.{}", key, name);
+            if !is_env_name(&name) {
+                warnings.push(format!("Ignored {}: not a valid variable name", field));
+                continue;
+            }
+            match vars.expand_env(&val, &field, &mut warnings) {
+                Some(EnvValue::Host(val)) => {
+                    shell_env.remove(&name);
+                    container_env.insert(name, val);
+                }
+                Some(EnvValue::Shell(word)) => {
+                    container_env.remove(&name);
+                    shell_env.insert(name, word);
+                }
+                None => {}
+            }
+        }
+    }
+
+    let mut mounts = Vec::new();
+    if let Some(list) = obj.get("mounts") {
+        match list {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let field = format!("mounts[{}]", i);
+                    let Some(mount) = mount_string(item) else {
+                        warnings.push(format!("{} is not a string or mount object", field));
+                        continue;
+                    };
+                    let mount = vars.expand(&mount, &field, &mut warnings);
+                    match check_mount(&mount, project_dir) {
+                        Ok(()) => mounts.push(mount),
+                        Err(reason) => warnings.push(format!("Ignored {}: {}", field, reason)),
+                    }
+                }
+            }
+            _ => warnings.push("mounts must be an array".to_string()),
+        }
+    }
+
+    let post_create_command = obj
+        .get("postCreateCommand")
+        .and_then(|cmd| command_line(cmd, &mut warnings))
+        .map(|cmd| vars.expand(&cmd, "postCreateCommand", &mut warnings));
+
+    unsupported.sort();
+    Ok(DevcontainerConfig {
+        config_path: config_path.to_string_lossy().to_string(),
+        name: string(obj, "name", &mut warnings),
+        image,
+        build,
+        remote_user,
+        container_env,
+        shell_env,
+        mounts,
+        workspace_folder,
+        post_create_command,
+        unsupported,
+        warnings,
+    })
+}
+
+/// `build`, or the older top-level `dockerFile`/`context`, with paths made
+/// absolute against the config's directory.
+fn build(
+    obj: &Map<String, Value>,
+    config_dir: &Path,
+    vars: &Variables,
+    unsupported: &mut Vec<String>,
+    warnings: &mut Vec<String>,
+) -> Option<ContainerBuild> {
+    let (section, legacy) = match obj.get("build") {
+        Some(Value::Object(build)) => (build.clone(), false),
+        Some(_) => {
+            warnings.push("build must be an object".to_string());
+            return None;
+        }
+        None if obj.contains_key("dockerFile") => {
+            let mut build = Map::new();
+            build.insert("dockerfile".to_string(), obj["dockerFile"].clone());
+            if let Some(context) = obj.get("context") {
+                build.insert("context".to_string(), context.clone());
+            }
+            (build, true)
+        }
+        None => return None,
+    };
+    if !legacy {
+        unsupported.extend(
+            section
+                .keys()
+                .filter(|k| !SUPPORTED_BUILD_KEYS.contains(&k.as_str()))
+                .map(|k| format!("build.{}", k)),
+        );
+    }
+
+    let resolve = |rel: String, field: &str, warnings: &mut Vec<String>| {
+        let rel = vars.expand(&rel, field, warnings);
+        let path = config_dir.join(rel);
+        path.canonicalize().unwrap_or(path).to_string_lossy().to_string()
+    };
+    let dockerfile = string(&section, "dockerfile", warnings).unwrap_or_else(|| "Dockerfile".to_string());
+    let context = string(&section, "context", warnings).unwrap_or_else(|| ".".to_string());
+    let args = string_map(&section, "args", warnings)
+        .into_iter()
+        .map(|(k, v)| {
+            let v = vars.expand(&v, &format!("build.args.{}", k), warnings);
+            (k, v)
+        })
+        .collect();
+    Some(ContainerBuild {
+        dockerfile: resolve(dockerfile, "build.dockerfile", warnings),
+        context: resolve(context, "build.context", warnings),
+        args,
+        target: string(&section, "target", warnings),
+    })
+}
+
+fn string(obj: &Map<String, Value>, key: &str, warnings: &mut Vec<String>) -> Option<String> {
+    match obj.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        _ => {
+            warnings.push(format!("{} must be a string", key));
+            None
+        }
+    }
+}
+
+/// An object of strings; numbers and booleans are accepted as their text.
+fn string_map(obj: &Map<String, Value>, key: &str, warnings: &mut Vec<String>) -> Vec<(String, String)> {
+    let Some(value) = obj.get(key) else {
+        return Vec::new();
+    };
+    let Value::Object(map) = value else {
+        warnings.push(format!("{} must be an object", key));
+        return Vec::new();
+    };
+    map.iter()
+        .filter_map(|(name, val)| match val {
+            Value::String(s) => Some((name.clone(), s.clone())),
+            Value::Number(_) | Value::Bool(_) => Some((name.clone(), val.to_string())),
+            _ => {
+                warnings.push(format!("{}.{} must be a string", key, name));
+                None
+            }
+        })
+        .collect()
+}
+
+/// A mount as the runtime's `--mount` syntax. Strings are taken as written.
+fn mount_string(item: &Value) -> Option<String> {
+    match item {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(fields) => {
+            let parts: Vec<String> = fields
+                .iter()
+                .filter_map(|(k, v)| match v {
+                    Value::String(s) => Some(format!("{}={}", k, s)),
+                    Value::Bool(true) => Some(k.clone()),
+                    _ => None,
+                })
+                .collect();
+            (!parts.is_empty()).then(|| parts.join(","))
+        }
+        _ => None,
+    }
+}
+
+/// Why `mount` can't be used, if it can't. The repository picks its own
+/// mounts, so a bind mount must come from inside the project and stay clear
+/// of the paths the sandbox protects, and never be a socket such as the
+/// container runtime's. Named volumes and tmpfs are fine.
+fn check_mount(mount: &str, project_dir: &Path) -> Result<(), String> {
+    let source = if mount.contains('=') {
+        // `--mount` syntax, which makes a volume unless told otherwise
+        let (mut kind, mut source) = ("volume", None);
+        for field in mount.split(',') {
+            let (key, value) = field.split_once('=').unwrap_or((field, ""));
+            match key.trim() {
+                "type" => kind = value.trim(),
+                "source" | "src" => source = Some(value.trim()),
+                // The local driver can bind any host path through these
+                key if key.starts_with("volume-") => return Err(format!("{} isn't allowed", key)),
+                _ => {}
+            }
+        }
+        match kind {
+            "bind" => source.ok_or("bind mount has no source")?,
+            "volume" | "tmpfs" => return Ok(()),
+            other => return Err(format!("{} mounts aren't supported", other)),
+        }
+    } else {
+        // `-v src:dst`, where a source without a slash names a volume
+        let source = mount.split(':').next().unwrap_or_default();
+        if !source.contains('/') && !source.starts_with('.') {
+            return Ok(());
+        }
+        source
+    };
+    let policy = AccessPolicy::new(vec![project_dir.to_path_buf()], home_dir().as_deref());
+    let resolved = policy
+        .check(&project_dir.join(source), Access::Read)
+        .map_err(|e| format!("{} can't be mounted: {}", source, e.message))?;
+    if is_socket(&resolved) {
+        return Err(format!("{} is a socket", source));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_socket(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    path.metadata().is_ok_and(|m| m.file_type().is_socket())
+}
+
+#[cfg(not(unix))]
+fn is_socket(_path: &Path) -> bool {
+    false
+}
+
+/// A lifecycle command (string, argv array, or object of named commands) as
+/// one shell command line.
+fn command_line(value: &Value, warnings: &mut Vec<String>) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(argv) => {
+            let args: Option<Vec<String>> = argv.iter().map(|a| a.as_str().map(str::to_string)).collect();
+            args.map(shell_join)
+        }
+        Value::Object(commands) => {
+            warnings.push("postCreateCommand's named commands run one after another".to_string());
+            let lines: Vec<String> = commands.values().filter_map(|c| command_line(c, warnings)).collect();
+            (!lines.is_empty()).then(|| lines.join(" && "))
+        }
+        _ => {
+            warnings.push("postCreateCommand must be a string, array or object".to_string());
+            None
+        }
+    }
+}
+
+fn basename(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("workspace")
+}
+
+/// An environment value after variable expansion.
+enum EnvValue {
+    /// Fully resolved on the host
+    Host(String),
+    /// A shell word that expands `${containerEnv:...}` references inside the
+    /// container
+    Shell(String),
+}
+
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Values for `${...}` variables in config strings.
+struct Variables {
+    local_workspace: String,
+    container_workspace: String,
+}
+
+impl Variables {
+    /// Replace the variables we can resolve on the host; anything else (like
+    /// `${containerEnv:PATH}`) is kept and noted in `warnings`.
+    fn expand(&self, input: &str, field: &str, warnings: &mut Vec<String>) -> String {
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let var = &rest[start + 2..start + len];
+            match self.lookup(var) {
+                Some(val) => out.push_str(&val),
+                None => {
+                    warnings.push(format!("Unresolved ${{{}}} in {}", var, field));
+                    out.push_str(&rest[start..=start + len]);
+                }
+            }
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Expand an environment value. `${containerEnv:NAME}` and
+    /// `${containerEnv:NAME:default}` become shell expansions of `NAME`;
+    /// any other unresolved variable makes the value `None`, noted in
+    /// `warnings`, since applying it literally would be wrong.
+    fn expand_env(&self, input: &str, field: &str, warnings: &mut Vec<String>) -> Option<EnvValue> {
+        let mut literal = String::new();
+        let mut word = String::new();
+        let mut in_container = false;
+        let mut rest = input;
+        while let Some(start) = rest.find("${") {
+            literal.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let var = &rest[start + 2..start + len];
+            rest = &rest[start + len + 1..];
+            if let Some(spec) = var.strip_prefix("containerEnv:") {
+                let (name, default) = match spec.split_once(':') {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (spec, None),
+                };
+                if !is_env_name(name) {
+                    warnings.push(format!("Unresolved ${{{}}} in {}; not applied", var, field));
+                    return None;
+                }
+                if !literal.is_empty() {
+                    word.push_str(&shell_join([std::mem::take(&mut literal)]));
+                }
+                match default {
+                    Some(default) => word.push_str(&format!("\"${{{}:-{}}}\"", name, double_quoted(default))),
+                    None => word.push_str(&format!("\"${{{}}}\"", name)),
+                }
+                in_container = true;
+                continue;
+            }
+            match self.lookup(var) {
+                Some(val) => literal.push_str(&val),
+                None => {
+                    warnings.push(format!("Unresolved ${{{}}} in {}; not applied", var, field));
+                    return None;
+                }
+            }
+        }
+        literal.push_str(rest);
+        if !in_container {
+            return Some(EnvValue::Host(literal));
+        }
+        if !literal.is_empty() {
+            word.push_str(&shell_join([literal]));
+        }
+        Some(EnvValue::Shell(word))
+    }
+
+    fn lookup(&self, var: &str) -> Option<String> {
+        match var {
+            "localWorkspaceFolder" => Some(self.local_workspace.clone()),
+            "localWorkspaceFolderBasename" => Some(basename(&self.local_workspace).to_string()),
+            "containerWorkspaceFolder" => Some(self.container_workspace.clone()),
+            "containerWorkspaceFolderBasename" => Some(basename(&self.container_workspace).to_string()),
+            _ => {
+                let spec = var.strip_prefix("localEnv:").or_else(|| var.strip_prefix("env:"))?;
+                let (name, default) = spec.split_once(':').unwrap_or((spec, ""));
+                Some(std::env::var(name).unwrap_or_else(|_| default.to_string()))
+            }
+        }
+    }
+}
+
+/// Drop `//` and `/* */` comments and trailing commas, which
+/// devcontainer.json allows but JSON doesn't.
+fn strip_jsonc(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut code = String::with_capacity(text.len());
+    let mut i = 0;
+    let mut in_string = false;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            code.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                code.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+            i += 1;
+            continue;
+        }
+        match (c, chars.get(i + 1)) {
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+                code.push(' ');
+            }
+            _ => {
+                in_string = c == '"';
+                code.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    // Comments are gone, so a comma is trailing if only whitespace separates
+    // it from a closing bracket
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::with_capacity(code.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|n| !n.is_whitespace());
+            if matches!(next, Some('}' | ']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// `text` escaped for use between double quotes in a shell.
+fn double_quoted(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' | '$' | '`' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
+
+/// The shell command line a devcontainer terminal runs: exports of the
+/// values that need the container's environment, `postCreateCommand`, then
+/// the login shell. `None` when neither is set.
+fn shell_script(config: &DevcontainerConfig) -> Option<String> {
+    if config.shell_env.is_empty() && config.post_create_command.is_none() {
+        return None;
+    }
+    let mut script = String::new();
+    for (name, word) in &config.shell_env {
+        script.push_str(&format!("export {}={}; ", name, word));
+    }
+    // A failing postCreateCommand still leaves the user a shell
+    if let Some(cmd) = &config.post_create_command {
+        script.push_str(&format!("( {} ); ", cmd));
+    }
+    script.push_str(DEFAULT_SHELL);
+    Some(script)
+}
+
+/// The container profile a devcontainer config starts.
+pub fn profile_for(config: &DevcontainerConfig) -> ContainerProfile {
+    ContainerProfile {
+        name: config.name.clone().unwrap_or_else(|| "devcontainer".to_string()),
+        runtime: None,
+        image: config.image.clone(),
+        build: config.build.clone(),
+        container: None,
+        mounts: config.mounts.clone(),
+        env: config.container_env.clone(),
+        workdir: Some(config.workspace_folder.clone()),
+        user: config.remote_user.clone(),
+        shell: shell_script(config),
+    }
+}
+
+/// Parse the project's devcontainer config and describe the terminal it
+/// would start.
+pub fn inspect(project_dir: &Path, sandbox_no_net: bool) -> AppResult<DevcontainerReport> {
+    let mut config = load_config(project_dir)?;
+    let profile = profile_for(&config);
+    let opts = SpawnOptions {
+        sandbox_no_net,
+        project_dir: Some(project_dir.to_string_lossy().to_string()),
+        ..SpawnOptions::default()
+    };
+    let command = match container::container_command(&profile, &opts) {
+        Ok((cmd, _)) => Some(cmd.get_argv().iter().map(|a| a.to_string_lossy().to_string()).collect()),
+        Err(e) => {
+            config.warnings.push(e.message);
+            None
+        }
+    };
+    Ok(DevcontainerReport { config, profile, command })
+}
@@ -0,0 +1,238 @@
+//! Expect-style waiting on session output.
+//!
+//! The reader thread appends each chunk, with escape sequences stripped, to
+//! the session's [`ExpectBuffer`] and wakes any waiters. A wait matches a
+//! regex against the text that arrived after a given stream offset, so
+//! `send_and_expect` only ever sees output produced after its input was
+//! written.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use regex::Regex;
+use serde::Serialize;
+use vte::Perform;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::pty::manager::write_to_pty;
+use crate::state::{lock_session, AppState};
+
+/// Text kept for matching. Older output is dropped from the front.
+const MAX_BUFFER_BYTES: usize = 64 * 1024;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Default)]
+struct BufferState {
+    text: String,
+    /// Stream offset of `text[0]`
+    base: usize,
+    closed: bool,
+}
+
+impl BufferState {
+    fn end(&self) -> usize {
+        self.base + self.text.len()
+    }
+}
+
+/// Plain-text output of one session, with a condvar for waiters.
+#[derive(Default)]
+pub struct ExpectBuffer {
+    state: Mutex<BufferState>,
+    changed: Condvar,
+}
+
+pub type SharedExpectBuffer = Arc<ExpectBuffer>;
+
+/// A successful wait.
+#[derive(Serialize, Clone, Debug)]
+pub struct ExpectMatch {
+    /// Text matched by the whole pattern
+    pub matched: String,
+    /// Capture groups 1.., `None` for groups that did not participate
+    pub captures: Vec<Option<String>>,
+    pub named_captures: HashMap<String, String>,
+    /// Output between the start of the wait and the match
+    pub before: String,
+}
+
+impl ExpectBuffer {
+    /// Current end of the stream; output after this offset is "new".
+    pub fn offset(&self) -> usize {
+        self.state.lock().map(|s| s.end()).unwrap_or(0)
+    }
+
+    fn append(&self, chunk: &str) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.text.push_str(chunk);
+        if state.text.len() > MAX_BUFFER_BYTES {
+            let mut cut = state.text.len() - MAX_BUFFER_BYTES;
+            while !state.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            state.text.drain(..cut);
+            state.base += cut;
+        }
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// Mark the stream finished so waiters stop early.
+    pub fn close(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+        self.changed.notify_all();
+    }
+
+    /// Block until `pattern` matches output after stream offset `from`.
+    pub fn wait(&self, pattern: &Regex, from: usize, timeout: Duration) -> AppResult<ExpectMatch> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().map_err(AppError::lock)?;
+        loop {
+            let mut start = from.saturating_sub(state.base).min(state.text.len());
+            while !state.text.is_char_boundary(start) {
+                start += 1;
+            }
+            let haystack = &state.text[start..];
+            if let Some(caps) = pattern.captures(haystack) {
+                let whole = caps.get(0).expect("group 0 always participates");
+                return Ok(ExpectMatch {
+                    matched: whole.as_str().to_string(),
+                    captures: caps
+                        .iter()
+                        .skip(1)
+                        .map(|c| c.map(|m| m.as_str().to_string()))
+                        .collect(),
+                    named_captures: pattern
+                        .capture_names()
+                        .flatten()
+                        .filter_map(|name| Some((name.to_string(), caps.name(name)?.as_str().to_string())))
+                        .collect(),
+                    before: haystack[..whole.start()].to_string(),
+                });
+            }
+            if state.closed {
+                return Err(AppError::new(
+                    ErrorCode::Pty,
+                    format!("Session exited before output matched /{}/", pattern.as_str()),
+                ));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(AppError::new(
+                    ErrorCode::Timeout,
+                    format!("Timed out after {} ms waiting for /{}/", timeout.as_millis(), pattern.as_str()),
+                ));
+            }
+            state = self
+                .changed
+                .wait_timeout(state, deadline - now)
+                .map_err(AppError::lock)?
+                .0;
+        }
+    }
+}
+
+/// Collects printable text and line breaks, dropping escape sequences.
+#[derive(Default)]
+struct TextCollector {
+    text: String,
+}
+
+impl Perform for TextCollector {
+    fn print(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.text.push('\n'),
+            b'\t' => self.text.push('\t'),
+            _ => {}
+        }
+    }
+}
+
+/// Reader-thread side: strips escape sequences and appends to the buffer.
+pub struct ExpectFeeder {
+    parser: vte::Parser,
+    collector: TextCollector,
+    buffer: SharedExpectBuffer,
+}
+
+impl ExpectFeeder {
+    pub fn new(buffer: SharedExpectBuffer) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            collector: TextCollector::default(),
+            buffer,
+        }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.collector, bytes);
+        if !self.collector.text.is_empty() {
+            self.buffer.append(&self.collector.text);
+            self.collector.text.clear();
+        }
+    }
+
+    pub fn close(&self) {
+        self.buffer.close();
+    }
+}
+
+fn compile(pattern: &str) -> AppResult<Regex> {
+    Regex::new(pattern).map_err(|e| AppError::new(ErrorCode::InvalidInput, format!("Invalid pattern: {}", e)))
+}
+
+fn clamp_timeout(timeout: Option<Duration>) -> Duration {
+    timeout.unwrap_or(DEFAULT_TIMEOUT).min(MAX_TIMEOUT)
+}
+
+fn session_buffer(state: &AppState, session_id: &str) -> AppResult<SharedExpectBuffer> {
+    Ok(lock_session(&state.session(session_id)?)?.expect.clone())
+}
+
+/// Wait for `pattern` to appear in output produced from now on.
+pub fn wait_for_output(
+    state: &AppState,
+    session_id: &str,
+    pattern: &str,
+    timeout: Option<Duration>,
+) -> AppResult<ExpectMatch> {
+    let pattern = compile(pattern)?;
+    let buffer = session_buffer(state, session_id)?;
+    let from = buffer.offset();
+    buffer
+        .wait(&pattern, from, clamp_timeout(timeout))
+        .map_err(|e| e.with_session(session_id))
+}
+
+/// Write `input`, then wait for `pattern` in the output that follows it.
+pub fn send_and_expect(
+    state: &AppState,
+    session_id: &str,
+    input: &str,
+    pattern: &str,
+    timeout: Option<Duration>,
+) -> AppResult<ExpectMatch> {
+    let pattern = compile(pattern)?;
+    let handle = state.session(session_id)?;
+    let (buffer, from) = {
+        let mut session = lock_session(&handle)?;
+        // Take the offset under the session lock, right before writing, so
+        // the echo and reply are never missed
+        let buffer = session.expect.clone();
+        let from = buffer.offset();
+        write_to_pty(&mut session, input).map_err(|e| e.with_session(session_id))?;
+        (buffer, from)
+    };
+    buffer
+        .wait(&pattern, from, clamp_timeout(timeout))
+        .map_err(|e| e.with_session(session_id))
+}
@@ -0,0 +1,140 @@
+//! Session groups: broadcast one input to several terminals.
+//!
+//! A group is an ordered list of session ids. `write_to_group` takes each
+//! member's own lock in turn, so a slow or dead member never blocks the rest,
+//! and reports the outcome per session.
+
+use serde::Serialize;
+use uuid::Uuid;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::pty::manager::write_to_pty;
+use crate::state::{lock_session, AppState};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TerminalGroup {
+    pub group_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupWriteStatus {
+    Written,
+    /// The session's process has exited; nothing was written
+    Exited,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GroupWriteResult {
+    pub session_id: String,
+    pub status: GroupWriteStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+fn group_not_found(group_id: &str) -> AppError {
+    AppError::new(ErrorCode::NotFound, format!("Terminal group not found: {}", group_id))
+}
+
+/// Check every id refers to a live session before it joins a group.
+fn ensure_sessions(state: &AppState, session_ids: &[String]) -> AppResult<()> {
+    for id in session_ids {
+        state.session(id)?;
+    }
+    Ok(())
+}
+
+pub fn create_group(state: &AppState, name: Option<String>, session_ids: Vec<String>) -> AppResult<TerminalGroup> {
+    ensure_sessions(state, &session_ids)?;
+    let mut members: Vec<String> = Vec::with_capacity(session_ids.len());
+    for id in session_ids {
+        if !members.contains(&id) {
+            members.push(id);
+        }
+    }
+    let group = TerminalGroup {
+        group_id: Uuid::new_v4().to_string(),
+        name,
+        members,
+    };
+    state.terminal_groups.insert(group.group_id.clone(), group.clone());
+    Ok(group)
+}
+
+pub fn add_members(state: &AppState, group_id: &str, session_ids: Vec<String>) -> AppResult<TerminalGroup> {
+    ensure_sessions(state, &session_ids)?;
+    let mut group = state
+        .terminal_groups
+        .get_mut(group_id)
+        .ok_or_else(|| group_not_found(group_id))?;
+    for id in session_ids {
+        if !group.members.contains(&id) {
+            group.members.push(id);
+        }
+    }
+    Ok(group.clone())
+}
+
+pub fn remove_members(state: &AppState, group_id: &str, session_ids: &[String]) -> AppResult<TerminalGroup> {
+    let mut group = state
+        .terminal_groups
+        .get_mut(group_id)
+        .ok_or_else(|| group_not_found(group_id))?;
+    group.members.retain(|id| !session_ids.contains(id));
+    Ok(group.clone())
+}
+
+pub fn delete_group(state: &AppState, group_id: &str) -> AppResult<()> {
+    state
+        .terminal_groups
+        .remove(group_id)
+        .map(|_| ())
+        .ok_or_else(|| group_not_found(group_id))
+}
+
+pub fn list_groups(state: &AppState) -> Vec<TerminalGroup> {
+    state.terminal_groups.iter().map(|g| g.value().clone()).collect()
+}
+
+/// Write `data` to every member, skipping sessions whose process has exited.
+pub fn write_to_group(state: &AppState, group_id: &str, data: &str) -> AppResult<Vec<GroupWriteResult>> {
+    // Copy the member list so the group entry isn't held during I/O
+    let members = state
+        .terminal_groups
+        .get(group_id)
+        .map(|g| g.members.clone())
+        .ok_or_else(|| group_not_found(group_id))?;
+
+    Ok(members
+        .into_iter()
+        .map(|session_id| {
+            let (status, error) = match write_member(state, &session_id, data) {
+                Ok(true) => (GroupWriteStatus::Written, None),
+                Ok(false) => (GroupWriteStatus::Exited, None),
+                Err(e) => (GroupWriteStatus::Failed, Some(e.with_session(&session_id))),
+            };
+            GroupWriteResult { session_id, status, error }
+        })
+        .collect())
+}
+
+/// Returns `Ok(false)` when the member has exited.
+fn write_member(state: &AppState, session_id: &str, data: &str) -> AppResult<bool> {
+    let handle = state.session(session_id)?;
+    let mut session = lock_session(&handle)?;
+    if matches!(session.child.try_wait(), Ok(Some(_))) {
+        return Ok(false);
+    }
+    write_to_pty(&mut session, data)?;
+    Ok(true)
+}
+
+/// Drop a closed session from every group.
+pub fn forget_session(state: &AppState, session_id: &str) {
+    for mut group in state.terminal_groups.iter_mut() {
+        group.members.retain(|id| id != session_id);
+    }
+}
@@ -1,9 +1,156 @@
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
-use crate::state::PtySession;
+use serde::Serialize;
+use portable_pty::{Child, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use uuid::Uuid;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+use crate::pty::container::{self, ContainerProfile};
+use crate::pty::groups;
+use crate::pty::cwd_watcher::{self, CwdSource, SharedCwdWatch};
+use crate::pty::expect::{ExpectFeeder, SharedExpectBuffer};
+use crate::pty::metrics::SharedMetrics;
+use crate::pty::screen::{Screen, ScreenFeeder, SharedScreen, ScreenMatch, ScreenSnapshot, TerminalLines};
+use crate::pty::shell_integration::{self, CommandRecord, SharedShellState, ShellTracker};
+use crate::pty::ssh;
+use crate::state::{lock_session, AppState, PtySession};
 
-pub fn spawn_pty(rows: u16, cols: u16, sandbox: bool, sandbox_no_net: bool, project_dir: Option<String>) -> Result<PtySession, String> {
+/// Everything needed to start a PTY session.
+#[derive(Clone, Debug)]
+pub struct SpawnOptions {
+    pub rows: u16,
+    pub cols: u16,
+    pub sandbox: bool,
+    pub sandbox_no_net: bool,
+    pub project_dir: Option<String>,
+    /// Run this command through the login shell (`-lc`) instead of starting
+    /// an interactive shell.
+    pub command: Option<String>,
+    /// Inject the OSC 7/133 snippet into interactive bash, zsh and fish.
+    pub shell_integration: bool,
+    /// Run the shell in a container instead of on the host. `sandbox` is
+    /// ignored; `sandbox_no_net` still applies.
+    pub container: Option<ContainerProfile>,
+    /// The app config dir (snippets, SSH and container profiles), kept
+    /// read-only inside the sandbox so a sandboxed shell can't change what
+    /// later runs outside it.
+    pub config_dir: Option<PathBuf>,
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self {
+            rows: 24,
+            cols: 80,
+            sandbox: false,
+            sandbox_no_net: false,
+            project_dir: None,
+            command: None,
+            shell_integration: false,
+            container: None,
+            config_dir: None,
+        }
+    }
+}
+
+/// Interactive sessions back a visible terminal; hidden sessions run a single
+/// command in the background and clean themselves up when it exits. Remote
+/// sessions are visible terminals running `ssh` that reconnect when the
+/// connection drops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionKind {
+    Interactive,
+    Hidden,
+    Remote,
+}
+
+impl SessionKind {
+    fn output_event(self) -> &'static str {
+        match self {
+            SessionKind::Interactive | SessionKind::Remote => "terminal-output",
+            SessionKind::Hidden => "hidden-terminal-output",
+        }
+    }
+}
+
+/// A process running under a PTY, before it becomes a session.
+pub(crate) struct PtyProcess {
+    pub master: Box<dyn MasterPty + Send>,
+    pub child: Box<dyn Child + Send>,
+    pub writer: Box<dyn Write + Send>,
+}
+
+/// Open a PTY of the given size and run `cmd` in it.
+pub(crate) fn spawn_in_pty(cmd: CommandBuilder, rows: u16, cols: u16) -> AppResult<PtyProcess> {
+    let pty_pair = NativePtySystem::default()
+        .openpty(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| AppError::pty("Failed to open PTY", e))?;
+    let child = pty_pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| AppError::pty("Failed to spawn process", e))?;
+    let master = pty_pair.master;
+    let writer = master
+        .take_writer()
+        .map_err(|e| AppError::pty("Failed to take writer", e))?;
+    Ok(PtyProcess { master, child, writer })
+}
+
+/// Wrap a spawned process in a fresh session with empty screen, history and
+/// counters.
+pub(crate) fn new_session(
+    process: PtyProcess,
+    rows: u16,
+    cols: u16,
+    isolation: Isolation,
+    project_dir: Option<String>,
+) -> PtySession {
+    PtySession {
+        master: process.master,
+        child: process.child,
+        writer: process.writer,
+        shutdown: Arc::new(AtomicBool::new(false)),
+        isolation,
+        project_dir,
+        remote: None,
+        container: None,
+        shell: SharedShellState::default(),
+        cwd_watch: SharedCwdWatch::default(),
+        screen: Arc::new(Mutex::new(Screen::new(rows, cols))),
+        expect: SharedExpectBuffer::default(),
+        metrics: SharedMetrics::default(),
+    }
+}
+
+/// How a session's shell is isolated from the host.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Isolation {
+    None,
+    Bwrap,
+    Container,
+}
+
+pub struct SpawnedSession {
+    pub session_id: String,
+    pub sandboxed: bool,
+    pub isolation: Isolation,
+}
+
+pub fn spawn_pty(opts: &SpawnOptions) -> AppResult<PtySession> {
+    if let Some(ref profile) = opts.container {
+        return container::spawn_container(opts, profile);
+    }
+    let SpawnOptions { rows, cols, sandbox, sandbox_no_net, ref project_dir, ref command, shell_integration, .. } = *opts;
     let pty_system = NativePtySystem::default();
 
     // Create a new PTY with the specified size
@@ -14,71 +161,69 @@ pub fn spawn_pty(rows: u16, cols: u16, sandbox: bool, sandbox_no_net: bool, proj
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|e| format!("Failed to open PTY: {}", e))?;
+        .map_err(|e| AppError::pty("Failed to open PTY", e))?;
 
     // Determine the shell to use based on the platform
     let shell = get_shell();
+    let integration = if shell_integration && command.is_none() {
+        shell_integration::integrated_launch(&shell)
+    } else {
+        None
+    };
+    let (shell_args, shell_env) = match integration {
+        Some(launch) => (launch.args, launch.env),
+        None => (get_shell_args(command.as_deref()), Vec::new()),
+    };
 
     // Create command: wrap in bwrap sandbox on Unix if requested
     #[cfg(target_os = "linux")]
     let mut cmd = if sandbox {
         let mut c = CommandBuilder::new("/usr/bin/bwrap");
         // Clear all inherited env vars to prevent leaking secrets
-        c.args(&["--clearenv"]);
-        c.args(&[
+        c.args(["--clearenv"]);
+        c.args([
             "--ro-bind", "/", "/",
             "--dev", "/dev",
             "--tmpfs", "/tmp",
         ]);
         // Home directory: writable by default, with sensitive paths read-only
         if let Some(ref home) = home_dir() {
-            c.args(&["--bind", home, home]);
+            c.args(["--bind", home, home]);
 
             // Sensitive paths that should be read-only inside the sandbox
-            let protected_paths = [
-                ".ssh",
-                ".gnupg",
-                ".bashrc",
-                ".bash_profile",
-                ".profile",
-                ".zshrc",
-                ".zprofile",
-                ".pam_environment",
-                ".gitconfig",
-                ".config/git",
-                ".config/autostart",
-                ".config/systemd/user",
-                ".config/environment.d",
-                ".local/bin",
-                // Shell configs
-                ".config/fish",
-                ".config/nushell",
-                // Desktop environment startup scripts
-                ".config/plasma-workspace/env",
-                ".config/hypr",
-                ".xinitrc",
-                ".xprofile",
-                // File handler and .desktop registration
-                ".config/mimeapps.list",
-                ".local/share/applications",
-            ];
-            for subpath in &protected_paths {
+            for subpath in crate::fs::PROTECTED_PATHS {
                 let full = format!("{}/{}", home, subpath);
                 if std::path::Path::new(&full).exists() {
-                    c.args(&["--ro-bind", &full, &full]);
+                    c.args(["--ro-bind", &full, &full]);
                 }
             }
         }
         // Writable: project directory (may be outside home)
         if let Some(ref proj) = project_dir {
             if std::path::Path::new(proj).is_dir() {
-                c.args(&["--bind", proj, proj]);
+                c.args(["--bind", proj, proj]);
+            }
+        }
+        // Unsandboxed shells source these snippets; created now so the
+        // sandbox can't plant them before the first integrated launch
+        if let Some(dir) = shell_integration::snippet_dir() {
+            let dir = dir.to_string_lossy();
+            c.args(["--ro-bind", &dir, &dir]);
+        }
+        // Likewise saved snippets and profiles, which run outside the sandbox
+        if let Some(dir) = &opts.config_dir {
+            match std::fs::create_dir_all(dir) {
+                Ok(()) => {
+                    let dir = dir.to_string_lossy();
+                    c.args(["--ro-bind", &dir, &dir]);
+                }
+                Err(e) => return Err(AppError::io("Failed to create config dir", &e).with_path(dir)),
             }
         }
         if sandbox_no_net {
-            c.args(&["--unshare-net"]);
+            c.args(["--unshare-net"]);
         }
-        c.args(&[
+        c.args([
             "--unshare-uts",
             "--unshare-ipc",
             "--unshare-pid",
@@ -100,39 +245,33 @@ pub fn spawn_pty(rows: u16, cols: u16, sandbox: bool, sandbox_no_net: bool, proj
         ];
         for var in &passthrough_vars {
             if let Ok(val) = std::env::var(var) {
-                c.args(&["--setenv", var, &val]);
+                c.args(["--setenv", var, &val]);
             }
         }
-        c.args(&["--", &shell, "-l"]);
+        for (key, val) in &shell_env {
+            c.args(["--setenv", key, val]);
+        }
+        c.args(["--", &shell]);
+        c.args(&shell_args);
         c
     } else {
         let mut c = CommandBuilder::new(&shell);
-        c.arg("-l");
+        c.args(&shell_args);
         c
     };
 
-    #[cfg(all(unix, not(target_os = "linux")))]
+    #[cfg(not(target_os = "linux"))]
     let mut cmd = {
         let mut c = CommandBuilder::new(&shell);
-        c.arg("-l");
+        c.args(&shell_args);
         c
     };
 
-    #[cfg(not(unix))]
-    let mut cmd = CommandBuilder::new(&shell);
-
-    // On Windows, PowerShell's Set-Location (cd) doesn't call Win32 SetCurrentDirectory,
-    // so the OS-level CWD never updates. Override the prompt function to sync them,
-    // allowing sysinfo to read the actual CWD.
-    #[cfg(windows)]
-    {
-        cmd.arg("-NoExit");
-        cmd.arg("-Command");
-        cmd.arg("function prompt { [System.IO.Directory]::SetCurrentDirectory($PWD.Path); \"PS $($PWD.Path)> \" }");
-    }
-
     // Set TERM so the shell knows terminal capabilities (line wrapping, cursor movement, etc.)
     cmd.env("TERM", "xterm-256color");
+    for (key, val) in &shell_env {
+        cmd.env(key, val);
+    }
 
     let start_dir = project_dir
         .as_ref()
@@ -147,7 +286,7 @@ pub fn spawn_pty(rows: u16, cols: u16, sandbox: bool, sandbox_no_net: bool, proj
     cmd.cwd(&start_dir);
 
     // Spawn the child process
-    eprintln!("[sandbox] sandbox={}, project_dir={:?}", sandbox, project_dir);
+    log::info!(target: "sandbox", "sandbox={}, project_dir={:?}", sandbox, project_dir);
     #[cfg(target_os = "linux")]
     let (child, actually_sandboxed) = if sandbox {
         // Ensure bwrap AppArmor profile exists on Ubuntu before first attempt
@@ -155,57 +294,322 @@ pub fn spawn_pty(rows: u16, cols: u16, sandbox: bool, sandbox_no_net: bool, proj
         match pty_pair.slave.spawn_command(cmd) {
             Ok(child) => (child, true),
             Err(e) => {
-                eprintln!("[sandbox] bwrap failed ({}), falling back to unsandboxed", e);
+                log::warn!(target: "sandbox", "bwrap failed ({}), falling back to unsandboxed", e);
                 let mut fallback = CommandBuilder::new(&shell);
-                fallback.arg("-l");
+                fallback.args(&shell_args);
                 fallback.env("TERM", "xterm-256color");
+                for (key, val) in &shell_env {
+                    fallback.env(key, val);
+                }
                 fallback.cwd(&start_dir);
                 let child = pty_pair.slave.spawn_command(fallback)
-                    .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+                    .map_err(|e| AppError::pty("Failed to spawn shell", e))?;
                 (child, false)
             }
         }
     } else {
         let child = pty_pair.slave.spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+            .map_err(|e| AppError::pty("Failed to spawn shell", e))?;
         (child, false)
     };
     #[cfg(not(target_os = "linux"))]
     let (child, actually_sandboxed) = {
         let child = pty_pair.slave.spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+            .map_err(|e| AppError::pty("Failed to spawn shell", e))?;
         (child, false)
     };
-    eprintln!("[sandbox] spawned pid={:?}, sandboxed={}", child.process_id(), actually_sandboxed);
+    log::info!(target: "sandbox", "spawned pid={:?}, sandboxed={}", child.process_id(), actually_sandboxed);
 
     // Take writer from master before moving it
     let master = pty_pair.master;
     let writer = master
         .take_writer()
-        .map_err(|e| format!("Failed to take writer: {}", e))?;
+        .map_err(|e| AppError::pty("Failed to take writer", e))?;
 
-    Ok(PtySession {
-        master,
-        child,
-        writer,
-        shutdown: Arc::new(AtomicBool::new(false)),
-        sandboxed: actually_sandboxed,
+    let process = PtyProcess { master, child, writer };
+    let isolation = if actually_sandboxed { Isolation::Bwrap } else { Isolation::None };
+    Ok(new_session(process, rows, cols, isolation, project_dir.clone()))
+}
+
+/// Spawn a PTY, register it in `state` and start the reader thread that
+/// forwards its output to `sink`.
+pub fn start_session(
+    state: &AppState,
+    opts: &SpawnOptions,
+    kind: SessionKind,
+    sink: SharedSink,
+) -> AppResult<SpawnedSession> {
+    let session = spawn_pty(opts)?;
+    launch(state, Uuid::new_v4().to_string(), session, kind, sink)
+}
+
+/// Register an already spawned session under `session_id` and start its
+/// reader thread.
+pub(crate) fn launch(
+    state: &AppState,
+    session_id: String,
+    session: PtySession,
+    kind: SessionKind,
+    sink: SharedSink,
+) -> AppResult<SpawnedSession> {
+    let isolation = session.isolation;
+
+    let reader = session
+        .master
+        .try_clone_reader()
+        .map_err(|e| AppError::pty("Failed to clone reader", e))?;
+    let shutdown = session.shutdown.clone();
+    let tracker = ShellTracker::new(&session_id, session.shell.clone(), sink.clone());
+    let cwd_watch = session.cwd_watch.clone();
+    let screen = ScreenFeeder::new(session.screen.clone());
+    let expect = ExpectFeeder::new(session.expect.clone());
+    let metrics = session.metrics.clone();
+
+    // Register before the reader starts so a command that exits immediately
+    // can still find (and remove) its own session.
+    state.insert_session(session_id.clone(), session);
+
+    let thread_session_id = session_id.clone();
+    let thread_state = state.clone();
+    thread::spawn(move || {
+        let session = ReaderSession {
+            id: &thread_session_id,
+            kind,
+            tracker,
+            cwd_watch,
+            screen,
+            expect,
+            metrics,
+        };
+        read_loop(reader, session, &shutdown, &sink, &thread_state);
+    });
+
+    Ok(SpawnedSession {
+        session_id,
+        sandboxed: isolation != Isolation::None,
+        isolation,
     })
 }
 
-pub fn write_to_pty(session: &mut PtySession, data: &str) -> Result<(), String> {
-    use std::io::Write;
+/// What the reader thread knows about its session.
+struct ReaderSession<'a> {
+    id: &'a str,
+    kind: SessionKind,
+    tracker: ShellTracker,
+    cwd_watch: SharedCwdWatch,
+    screen: ScreenFeeder,
+    expect: ExpectFeeder,
+    metrics: SharedMetrics,
+}
+
+fn read_loop(
+    mut reader: Box<dyn Read + Send>,
+    session: ReaderSession,
+    shutdown: &AtomicBool,
+    sink: &SharedSink,
+    state: &AppState,
+) {
+    let ReaderSession { id: session_id, kind, mut tracker, cwd_watch, mut screen, mut expect, metrics } = session;
+    let mut link = (kind == SessionKind::Remote).then(|| ssh::Link::for_session(state, session_id));
+    let mut buf = [0u8; 8192];
+    loop {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        match reader.read(&mut buf) {
+            Ok(n) if n > 0 => {
+                let read_at = Instant::now();
+                if shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                screen.feed(&buf[..n]);
+                let data = String::from_utf8_lossy(&buf[..n]).to_string();
+                let event_bytes = data.len();
+                sink.emit_event(kind.output_event(), serde_json::json!({
+                    "session_id": session_id,
+                    "data": data,
+                }));
+                metrics.record_output(n, event_bytes, read_at.elapsed());
+                // After the output, so a command-finished event never
+                // arrives before the command's last bytes
+                let marks_before = tracker.marks_seen();
+                if let Some(cwd) = tracker.feed(&buf[..n]) {
+                    cwd_watcher::report_cwd(state, session_id, &cwd_watch, cwd, CwdSource::Osc7, sink);
+                }
+                if let Some(link) = link.as_mut() {
+                    link.on_output(session_id, sink, tracker.marks_seen() > marks_before);
+                }
+                expect.feed(&buf[..n]);
+            }
+            Ok(_) => {
+                match kind {
+                    SessionKind::Interactive => {
+                        if !shutdown.load(Ordering::SeqCst) {
+                            sink.emit_event("terminal-output", serde_json::json!({
+                                "session_id": session_id,
+                                "data": "\r\n[Process exited]\r\n",
+                            }));
+                        }
+                    }
+                    SessionKind::Hidden => {
+                        let exit_code = reap_session(state, session_id);
+                        sink.emit_event(
+                            "hidden-terminal-closed",
+                            serde_json::json!({"session_id": session_id, "exit_code": exit_code}),
+                        );
+                    }
+                    SessionKind::Remote => {
+                        if let Some(next) = link.as_mut().and_then(|l| l.on_drop(state, session_id, shutdown, sink)) {
+                            reader = next;
+                            continue;
+                        }
+                    }
+                }
+                break;
+            }
+            Err(e) => {
+                match kind {
+                    SessionKind::Interactive => {
+                        if !shutdown.load(Ordering::SeqCst) {
+                            log::error!(target: "pty", "Error reading from PTY: {}", e);
+                        }
+                    }
+                    SessionKind::Hidden => {
+                        reap_session(state, session_id);
+                        sink.emit_event(
+                            "hidden-terminal-closed",
+                            serde_json::json!({"session_id": session_id, "error": true}),
+                        );
+                    }
+                    SessionKind::Remote => {
+                        // EIO on the master is how Linux reports the child
+                        // closing the PTY, so treat it like EOF
+                        log::debug!(target: "pty", "Remote session {} read ended: {}", session_id, e);
+                        if let Some(next) = link.as_mut().and_then(|l| l.on_drop(state, session_id, shutdown, sink)) {
+                            reader = next;
+                            continue;
+                        }
+                    }
+                }
+                break;
+            }
+        }
+    }
+    expect.close();
+}
+
+/// Drop a finished session and reap its process, returning the exit code.
+fn reap_session(state: &AppState, session_id: &str) -> Option<u32> {
+    let handle = state.remove_session(session_id)?;
+    groups::forget_session(state, session_id);
+    let mut session = handle.lock().ok()?;
+    cwd_watcher::release(state, &session.cwd_watch);
+    let exit_code = session.child.wait().ok().map(|status| status.exit_code());
+    if let Some(container) = session.container.take() {
+        container::remove_container(container);
+    }
+    exit_code
+}
+
+/// Write input to a registered session. Only that session's lock is taken.
+pub fn write_session(state: &AppState, session_id: &str, data: &str) -> AppResult<()> {
+    let waiting = Instant::now();
+    let handle = state.session(session_id)?;
+    let mut session = lock_session(&handle)?;
+    session.metrics.record_lock_wait(waiting.elapsed());
+    write_to_pty(&mut session, data).map_err(|e| e.with_session(session_id))
+}
+
+/// Resize a registered session.
+pub fn resize_session(state: &AppState, session_id: &str, rows: u16, cols: u16) -> AppResult<()> {
+    let handle = state.session(session_id)?;
+    let mut session = lock_session(&handle)?;
+    resize_pty(&mut session, rows, cols).map_err(|e| e.with_session(session_id))
+}
+
+/// Kill a session's process and drop it from `state`. Returns whether a
+/// session with that id existed.
+pub fn close_session(state: &AppState, session_id: &str) -> AppResult<bool> {
+    match state.remove_session(session_id) {
+        Some(handle) => {
+            groups::forget_session(state, session_id);
+            let mut session = lock_session(&handle)?;
+            cwd_watcher::release(state, &session.cwd_watch);
+            session.shutdown.store(true, Ordering::SeqCst);
+            let _ = session.child.kill();
+            if let Some(container) = session.container.take() {
+                container::remove_container(container);
+            }
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Exit code of the session's process, or `None` while it is still running.
+pub fn session_exit_code(state: &AppState, session_id: &str) -> AppResult<Option<u32>> {
+    let handle = state.session(session_id)?;
+    let mut session = lock_session(&handle)?;
+    session
+        .child
+        .try_wait()
+        .map(|status| status.map(|s| s.exit_code()))
+        .map_err(|e| AppError::io("Failed to poll process", &e).with_session(session_id))
+}
+
+/// Commands the shell reported through OSC 133, oldest first. Empty when the
+/// shell has no integration.
+pub fn command_history(state: &AppState, session_id: &str) -> AppResult<Vec<CommandRecord>> {
+    let shell = lock_session(&state.session(session_id)?)?.shell.clone();
+    let shell = shell.lock().map_err(AppError::lock)?;
+    Ok(shell.history.iter().cloned().collect())
+}
+
+fn session_screen(state: &AppState, session_id: &str) -> AppResult<SharedScreen> {
+    Ok(lock_session(&state.session(session_id)?)?.screen.clone())
+}
+
+/// Visible rows of the session's screen model plus the cursor.
+pub fn terminal_screen(state: &AppState, session_id: &str) -> AppResult<ScreenSnapshot> {
+    let screen = session_screen(state, session_id)?;
+    let screen = screen.lock().map_err(AppError::lock)?;
+    Ok(screen.snapshot())
+}
+
+/// Lines `start..end` of scrollback followed by the screen.
+pub fn terminal_lines(
+    state: &AppState,
+    session_id: &str,
+    start: usize,
+    end: Option<usize>,
+) -> AppResult<TerminalLines> {
+    let screen = session_screen(state, session_id)?;
+    let screen = screen.lock().map_err(AppError::lock)?;
+    Ok(screen.lines(start..end.unwrap_or(usize::MAX)))
+}
+
+/// Regex matches in the session's scrollback and screen.
+pub fn search_terminal(state: &AppState, session_id: &str, pattern: &str) -> AppResult<Vec<ScreenMatch>> {
+    let regex = regex::Regex::new(pattern)
+        .map_err(|e| AppError::new(ErrorCode::InvalidInput, format!("Invalid pattern: {}", e)))?;
+    let screen = session_screen(state, session_id)?;
+    let screen = screen.lock().map_err(AppError::lock)?;
+    Ok(screen.search(&regex))
+}
+
+pub fn write_to_pty(session: &mut PtySession, data: &str) -> AppResult<()> {
     session
         .writer
         .write_all(data.as_bytes())
-        .map_err(|e| format!("Failed to write to PTY: {}", e))?;
+        .map_err(|e| AppError::io("Failed to write to PTY", &e))?;
+    session.metrics.record_write(data.len());
     session
         .writer
         .flush()
-        .map_err(|e| format!("Failed to flush PTY: {}", e))
+        .map_err(|e| AppError::io("Failed to flush PTY", &e))
 }
 
-pub fn resize_pty(session: &mut PtySession, rows: u16, cols: u16) -> Result<(), String> {
+pub fn resize_pty(session: &mut PtySession, rows: u16, cols: u16) -> AppResult<()> {
     session
         .master
         .resize(PtySize {
@@ -214,7 +618,11 @@ pub fn resize_pty(session: &mut PtySession, rows: u16, cols: u16) -> Result<(),
             pixel_width: 0,
             pixel_height: 0,
         })
-        .map_err(|e| format!("Failed to resize PTY: {}", e))
+        .map_err(|e| AppError::pty("Failed to resize PTY", e))?;
+    if let Ok(mut screen) = session.screen.lock() {
+        screen.resize(rows, cols);
+    }
+    Ok(())
 }
 
 /// On Ubuntu 24.04+, AppArmor restricts unprivileged user namespaces.
@@ -233,17 +641,17 @@ fn ensure_bwrap_apparmor() {
             .map(|v| v.trim() == "1")
             .unwrap_or(false);
         if !restricted {
-            eprintln!("[sandbox] AppArmor userns restriction not active, skipping profile setup");
+            log::debug!(target: "sandbox", "AppArmor userns restriction not active, skipping profile setup");
             return;
         }
 
         // Check if profile already exists
         if std::path::Path::new(profile_path).exists() {
-            eprintln!("[sandbox] bwrap AppArmor profile already installed");
+            log::debug!(target: "sandbox", "bwrap AppArmor profile already installed");
             return;
         }
 
-        eprintln!("[sandbox] AppArmor restricts userns; installing bwrap profile via pkexec...");
+        log::info!(target: "sandbox", "AppArmor restricts userns; installing bwrap profile via pkexec...");
 
         let profile_content = r#"abi <abi/4.0>,
 include <tunables/global>
@@ -264,13 +672,13 @@ profile bwrap /usr/bin/bwrap flags=(unconfined) {
 
         match status {
             Ok(s) if s.success() => {
-                eprintln!("[sandbox] bwrap AppArmor profile installed successfully");
+                log::info!(target: "sandbox", "bwrap AppArmor profile installed successfully");
             }
             Ok(s) => {
-                eprintln!("[sandbox] pkexec exited with {}, sandbox may not work", s);
+                log::warn!(target: "sandbox", "pkexec exited with {}, sandbox may not work", s);
             }
             Err(e) => {
-                eprintln!("[sandbox] failed to run pkexec: {}, sandbox may not work", e);
+                log::warn!(target: "sandbox", "failed to run pkexec: {}, sandbox may not work", e);
             }
         }
     });
@@ -283,6 +691,31 @@ fn home_dir() -> Option<String> {
     { std::env::var("USERPROFILE").ok() }
 }
 
+/// Arguments passed to the shell: a login shell, or `-lc <command>` when
+/// running a single command.
+#[cfg(unix)]
+fn get_shell_args(command: Option<&str>) -> Vec<String> {
+    match command {
+        Some(command) => vec!["-lc".to_string(), command.to_string()],
+        None => vec!["-l".to_string()],
+    }
+}
+
+// On Windows, PowerShell's Set-Location (cd) doesn't call Win32 SetCurrentDirectory,
+// so the OS-level CWD never updates. Override the prompt function to sync them,
+// allowing sysinfo to read the actual CWD.
+#[cfg(windows)]
+fn get_shell_args(command: Option<&str>) -> Vec<String> {
+    match command {
+        Some(command) => vec!["-Command".to_string(), command.to_string()],
+        None => vec![
+            "-NoExit".to_string(),
+            "-Command".to_string(),
+            "function prompt { [System.IO.Directory]::SetCurrentDirectory($PWD.Path); \"PS $($PWD.Path)> \" }".to_string(),
+        ],
+    }
+}
+
 #[cfg(unix)]
 fn get_shell() -> String {
     // Use the user's configured shell, fall back to /bin/bash
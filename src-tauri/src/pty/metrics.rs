@@ -0,0 +1,225 @@
+//! Per-session I/O counters and latency histograms.
+//!
+//! Counters are atomics shared between the reader thread and commands, so
+//! recording never takes a lock. They answer "is it the PTY, the lock or the
+//! IPC": bytes and events describe the PTY traffic, lock wait covers
+//! `write_to_terminal` from lookup to holding the session lock, and emit
+//! latency covers a chunk from `read()` returning to the event being handed
+//! to the sink.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::error::AppResult;
+use crate::state::{lock_session, AppState};
+
+/// Upper bounds (inclusive, microseconds) of the histogram buckets; a final
+/// bucket catches everything slower.
+const BUCKET_BOUNDS_US: [u64; 10] = [50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000];
+
+#[derive(Default)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_US.len() + 1],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn record(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        let bucket = BUCKET_BOUNDS_US
+            .iter()
+            .position(|bound| us <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_US.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(i, count)| HistogramBucket {
+                    le_us: BUCKET_BOUNDS_US.get(i).copied(),
+                    count: count.load(Ordering::Relaxed),
+                })
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum_us: self.sum_us.load(Ordering::Relaxed),
+            max_us: self.max_us.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HistogramBucket {
+    /// Inclusive upper bound; `None` for the overflow bucket
+    pub le_us: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LatencySnapshot {
+    pub buckets: Vec<HistogramBucket>,
+    pub count: u64,
+    pub sum_us: u64,
+    pub max_us: u64,
+}
+
+impl LatencySnapshot {
+    fn empty() -> Self {
+        LatencyHistogram::default().snapshot()
+    }
+
+    fn merge(&mut self, other: &LatencySnapshot) {
+        for (mine, theirs) in self.buckets.iter_mut().zip(&other.buckets) {
+            mine.count += theirs.count;
+        }
+        self.count += other.count;
+        self.sum_us += other.sum_us;
+        self.max_us = self.max_us.max(other.max_us);
+    }
+}
+
+/// Live counters for one session.
+pub struct SessionMetrics {
+    started: Instant,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    writes: AtomicU64,
+    events_emitted: AtomicU64,
+    peak_event_bytes: AtomicU64,
+    lock_wait: LatencyHistogram,
+    emit_latency: LatencyHistogram,
+}
+
+pub type SharedMetrics = Arc<SessionMetrics>;
+
+impl Default for SessionMetrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            events_emitted: AtomicU64::new(0),
+            peak_event_bytes: AtomicU64::new(0),
+            lock_wait: LatencyHistogram::default(),
+            emit_latency: LatencyHistogram::default(),
+        }
+    }
+}
+
+impl SessionMetrics {
+    /// One chunk read from the PTY and emitted as an event of `event_bytes`.
+    pub fn record_output(&self, bytes_read: usize, event_bytes: usize, read_to_emit: Duration) {
+        self.bytes_read.fetch_add(bytes_read as u64, Ordering::Relaxed);
+        self.events_emitted.fetch_add(1, Ordering::Relaxed);
+        self.peak_event_bytes.fetch_max(event_bytes as u64, Ordering::Relaxed);
+        self.emit_latency.record(read_to_emit);
+    }
+
+    /// Input written to the PTY, from any command.
+    pub fn record_write(&self, bytes: usize) {
+        self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Time a `write_to_terminal` waited before holding the session.
+    pub fn record_lock_wait(&self, waited: Duration) {
+        self.lock_wait.record(waited);
+    }
+
+    pub fn snapshot(&self, session_id: &str) -> TerminalMetrics {
+        TerminalMetrics {
+            session_id: session_id.to_string(),
+            uptime_ms: self.started.elapsed().as_millis() as u64,
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            events_emitted: self.events_emitted.load(Ordering::Relaxed),
+            peak_event_bytes: self.peak_event_bytes.load(Ordering::Relaxed),
+            lock_wait: self.lock_wait.snapshot(),
+            emit_latency: self.emit_latency.snapshot(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TerminalMetrics {
+    pub session_id: String,
+    pub uptime_ms: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub writes: u64,
+    pub events_emitted: u64,
+    pub peak_event_bytes: u64,
+    /// `write_to_terminal`: session lookup until the session lock is held
+    pub lock_wait: LatencySnapshot,
+    /// Reader thread: `read()` returning until the output event is emitted
+    pub emit_latency: LatencySnapshot,
+}
+
+/// Totals over all live sessions, for a performance overlay.
+#[derive(Serialize, Clone, Debug)]
+pub struct AggregateMetrics {
+    pub session_count: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub writes: u64,
+    pub events_emitted: u64,
+    pub peak_event_bytes: u64,
+    pub lock_wait: LatencySnapshot,
+    pub emit_latency: LatencySnapshot,
+    pub sessions: Vec<TerminalMetrics>,
+}
+
+pub fn terminal_metrics(state: &AppState, session_id: &str) -> AppResult<TerminalMetrics> {
+    let metrics = lock_session(&state.session(session_id)?)?.metrics.clone();
+    Ok(metrics.snapshot(session_id))
+}
+
+pub fn aggregate_metrics(state: &AppState) -> AggregateMetrics {
+    // Collect handles first so no map shard is held while locking sessions
+    let handles: Vec<_> = state
+        .pty_sessions
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.value().clone()))
+        .collect();
+
+    let mut aggregate = AggregateMetrics {
+        session_count: 0,
+        bytes_read: 0,
+        bytes_written: 0,
+        writes: 0,
+        events_emitted: 0,
+        peak_event_bytes: 0,
+        lock_wait: LatencySnapshot::empty(),
+        emit_latency: LatencySnapshot::empty(),
+        sessions: Vec::with_capacity(handles.len()),
+    };
+    for (session_id, handle) in handles {
+        let Ok(session) = lock_session(&handle) else {
+            continue;
+        };
+        let metrics = session.metrics.snapshot(&session_id);
+        drop(session);
+
+        aggregate.session_count += 1;
+        aggregate.bytes_read += metrics.bytes_read;
+        aggregate.bytes_written += metrics.bytes_written;
+        aggregate.writes += metrics.writes;
+        aggregate.events_emitted += metrics.events_emitted;
+        aggregate.peak_event_bytes = aggregate.peak_event_bytes.max(metrics.peak_event_bytes);
+        aggregate.lock_wait.merge(&metrics.lock_wait);
+        aggregate.emit_latency.merge(&metrics.emit_latency);
+        aggregate.sessions.push(metrics);
+    }
+    aggregate
+}
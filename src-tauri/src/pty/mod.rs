@@ -3,10 +3,32 @@
 //! This module provides cross-platform PTY management for the terminal emulator:
 //!
 //! - `manager`: Core PTY operations (spawn, write, resize)
+//! - `container`: Shells in podman/docker containers
+//! - `devcontainer`: Start a project's devcontainer.json as a container
+//! - `cwd_watcher`: Push `terminal-cwd-changed` to subscribers
+//! - `screen`: VT screen model and scrollback per session
+//! - `expect`: Wait for output patterns (expect-style automation)
+//! - `groups`: Broadcast input to a group of sessions
+//! - `metrics`: Per-session byte counters and latency histograms
+//! - `paste`: Sanitized, bracketed paste
+//! - `snippets`: Saved input sequences with placeholders
+//! - `ssh`: SSH-backed remote sessions with reconnect
+//! - `shell_integration`: OSC 7/133 parsing and shell init snippets
 //! - `commands`: Tauri commands exposed to the frontend
 //!
 //! Each terminal session is identified by a unique UUID and manages its own
 //! shell process with bidirectional I/O through the PTY.
 
 pub mod manager;
+pub mod container;
+pub mod devcontainer;
+pub mod cwd_watcher;
+pub mod screen;
+pub mod expect;
+pub mod groups;
+pub mod metrics;
+pub mod paste;
+pub mod snippets;
+pub mod ssh;
+pub mod shell_integration;
 pub mod commands;
@@ -0,0 +1,95 @@
+//! Pasting text into a session.
+//!
+//! Pasted text is never trusted: control characters are stripped so the text
+//! can't carry its own escape sequences (including a fake end-of-paste
+//! marker), line breaks are normalized to CR as a keyboard would send them,
+//! and the result is wrapped in `ESC[200~ ... ESC[201~` when the foreground
+//! app has turned on bracketed paste. Multi-line pastes are held back until
+//! the caller confirms them.
+
+use serde::Serialize;
+use crate::error::AppResult;
+use crate::pty::manager::write_to_pty;
+use crate::state::{lock_session, AppState};
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PasteResult {
+    /// Whether anything was written. `false` when confirmation is needed.
+    pub sent: bool,
+    pub needs_confirmation: bool,
+    pub multi_line: bool,
+    pub line_count: usize,
+    /// Wrapped in bracketed-paste markers
+    pub bracketed: bool,
+    /// Control characters removed from the text
+    pub stripped: usize,
+}
+
+/// Drop control characters other than tab and line breaks, and turn every
+/// line break into CR. Returns the text and the number of removed characters.
+fn sanitize(text: &str) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut stripped = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                out.push('\r');
+            }
+            '\n' => out.push('\r'),
+            '\t' => out.push('\t'),
+            c if c.is_control() => stripped += 1,
+            c => out.push(c),
+        }
+    }
+    (out, stripped)
+}
+
+/// Paste `text` into a session. Multi-line text is only written when
+/// `confirmed` is set; otherwise the result asks for confirmation.
+pub fn paste_to_session(state: &AppState, session_id: &str, text: &str, confirmed: bool) -> AppResult<PasteResult> {
+    let (clean, stripped) = sanitize(text);
+    // A single trailing newline still runs the line, but it's one command
+    let line_count = clean.trim_end_matches('\r').split('\r').count();
+    let multi_line = line_count > 1;
+
+    let handle = state.session(session_id)?;
+    let mut session = lock_session(&handle)?;
+    let bracketed = session.screen.lock().map(|s| s.bracketed_paste()).unwrap_or(false);
+
+    if multi_line && !confirmed {
+        return Ok(PasteResult {
+            sent: false,
+            needs_confirmation: true,
+            multi_line,
+            line_count,
+            bracketed,
+            stripped,
+        });
+    }
+
+    if stripped > 0 {
+        log::info!(target: "pty", "Stripped {} control characters from paste into {}", stripped, session_id);
+    }
+    let data = if bracketed {
+        format!("{}{}{}", PASTE_START, clean, PASTE_END)
+    } else {
+        clean
+    };
+    write_to_pty(&mut session, &data).map_err(|e| e.with_session(session_id))?;
+
+    Ok(PasteResult {
+        sent: true,
+        needs_confirmation: false,
+        multi_line,
+        line_count,
+        bracketed,
+        stripped,
+    })
+}
@@ -0,0 +1,555 @@
+//! Backend screen model.
+//!
+//! Each session keeps a character grid plus scrollback, fed by the reader
+//! thread through a VT parser. It tracks text and cursor only (no colors or
+//! attributes), which is enough to answer "what is on screen" for tooling
+//! without scraping the xterm.js DOM.
+//!
+//! Supported: printing with autowrap and wide characters, C0 controls, cursor
+//! movement, erase/insert/delete, scroll regions, index/reverse index, save
+//! and restore cursor, the alternate screen (1049/1047/47) and bracketed
+//! paste mode (2004).
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use serde::Serialize;
+use vte::{Params, Perform};
+
+const MAX_SCROLLBACK: usize = 5000;
+const MAX_SEARCH_MATCHES: usize = 1000;
+const TAB_WIDTH: usize = 8;
+/// Fills the cell to the right of a double-width character.
+const WIDE_SPACER: char = '\0';
+
+#[derive(Clone)]
+struct Line {
+    cells: Vec<char>,
+}
+
+impl Line {
+    fn blank(cols: usize) -> Self {
+        Self { cells: vec![' '; cols] }
+    }
+
+    fn text(&self) -> String {
+        let text: String = self.cells.iter().filter(|c| **c != WIDE_SPACER).collect();
+        text.trim_end().to_string()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Cursor {
+    row: usize,
+    col: usize,
+}
+
+/// Visible rows as text plus cursor position.
+#[derive(Serialize, Clone, Debug)]
+pub struct ScreenSnapshot {
+    pub rows: usize,
+    pub cols: usize,
+    pub lines: Vec<String>,
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    pub cursor_visible: bool,
+    pub alternate_screen: bool,
+    pub bracketed_paste: bool,
+    /// Lines above the screen; screen row 0 is line `scrollback_len`
+    pub scrollback_len: usize,
+}
+
+/// A slice of scrollback followed by the screen, addressed by line index.
+#[derive(Serialize, Clone, Debug)]
+pub struct TerminalLines {
+    pub start: usize,
+    pub total: usize,
+    pub lines: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScreenMatch {
+    /// Index into scrollback + screen, as in `TerminalLines`
+    pub line: usize,
+    /// Character columns of the match within the line text
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+pub struct Screen {
+    rows: usize,
+    cols: usize,
+    lines: Vec<Line>,
+    scrollback: VecDeque<Line>,
+    /// Primary screen lines and cursor while the alternate screen is shown
+    saved_primary: Option<(Vec<Line>, Cursor)>,
+    cursor: Cursor,
+    saved_cursor: Option<Cursor>,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    wrap_pending: bool,
+    autowrap: bool,
+    cursor_visible: bool,
+    /// DECSET 2004: the foreground app wants pastes bracketed
+    bracketed_paste: bool,
+}
+
+pub type SharedScreen = Arc<Mutex<Screen>>;
+
+impl Screen {
+    pub fn new(rows: u16, cols: u16) -> Self {
+        let rows = usize::from(rows).max(1);
+        let cols = usize::from(cols).max(1);
+        Self {
+            rows,
+            cols,
+            lines: vec![Line::blank(cols); rows],
+            scrollback: VecDeque::new(),
+            saved_primary: None,
+            cursor: Cursor::default(),
+            saved_cursor: None,
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            wrap_pending: false,
+            autowrap: true,
+            cursor_visible: true,
+            bracketed_paste: false,
+        }
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// Current `(rows, cols)`.
+    pub fn size(&self) -> (u16, u16) {
+        (self.rows as u16, self.cols as u16)
+    }
+
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        ScreenSnapshot {
+            rows: self.rows,
+            cols: self.cols,
+            lines: self.lines.iter().map(Line::text).collect(),
+            cursor_row: self.cursor.row,
+            cursor_col: self.cursor.col,
+            cursor_visible: self.cursor_visible,
+            alternate_screen: self.saved_primary.is_some(),
+            bracketed_paste: self.bracketed_paste,
+            scrollback_len: self.scrollback.len(),
+        }
+    }
+
+    /// Lines in `range` (clamped) of scrollback followed by the screen.
+    pub fn lines(&self, range: Range<usize>) -> TerminalLines {
+        let total = self.scrollback.len() + self.rows;
+        let end = range.end.min(total);
+        let start = range.start.min(end);
+        TerminalLines {
+            start,
+            total,
+            lines: (start..end).map(|i| self.line(i).text()).collect(),
+        }
+    }
+
+    /// Regex matches over scrollback and screen, oldest line first.
+    pub fn search(&self, pattern: &regex::Regex) -> Vec<ScreenMatch> {
+        let mut matches = Vec::new();
+        for i in 0..self.scrollback.len() + self.rows {
+            let text = self.line(i).text();
+            for m in pattern.find_iter(&text) {
+                matches.push(ScreenMatch {
+                    line: i,
+                    start: text[..m.start()].chars().count(),
+                    end: text[..m.end()].chars().count(),
+                    text: m.as_str().to_string(),
+                });
+                if matches.len() == MAX_SEARCH_MATCHES {
+                    return matches;
+                }
+            }
+        }
+        matches
+    }
+
+    /// Match the PTY size. Lines are truncated or padded, not reflowed.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        let rows = usize::from(rows).max(1);
+        let cols = usize::from(cols).max(1);
+        let on_primary = self.saved_primary.is_none();
+
+        // Shrinking drops blank rows below the cursor first, then scrolls
+        // the top rows off so the cursor stays on screen.
+        while self.lines.len() > rows {
+            if self.lines.len() - 1 > self.cursor.row {
+                self.lines.pop();
+            } else {
+                let line = self.lines.remove(0);
+                if on_primary {
+                    self.push_scrollback(line);
+                }
+                self.cursor.row = self.cursor.row.saturating_sub(1);
+            }
+        }
+        while self.lines.len() < rows {
+            self.lines.push(Line::blank(cols));
+        }
+        for line in &mut self.lines {
+            line.cells.resize(cols, ' ');
+        }
+        if let Some((primary, cursor)) = &mut self.saved_primary {
+            primary.resize(rows, Line::blank(cols));
+            for line in primary.iter_mut() {
+                line.cells.resize(cols, ' ');
+            }
+            cursor.row = cursor.row.min(rows - 1);
+            cursor.col = cursor.col.min(cols - 1);
+        }
+
+        self.rows = rows;
+        self.cols = cols;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.col = self.cursor.col.min(cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn line(&self, index: usize) -> &Line {
+        if index < self.scrollback.len() {
+            &self.scrollback[index]
+        } else {
+            &self.lines[index - self.scrollback.len()]
+        }
+    }
+
+    fn push_scrollback(&mut self, line: Line) {
+        if self.scrollback.len() == MAX_SCROLLBACK {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        for _ in 0..n.min(bottom - top + 1) {
+            let line = self.lines.remove(top);
+            self.lines.insert(bottom, Line::blank(self.cols));
+            // Only full-screen scrolling on the primary screen feeds history
+            if top == 0 && self.saved_primary.is_none() {
+                self.push_scrollback(line);
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        for _ in 0..n.min(bottom - top + 1) {
+            self.lines.remove(bottom);
+            self.lines.insert(top, Line::blank(self.cols));
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_top {
+            self.scroll_down(1);
+        } else {
+            self.cursor.row = self.cursor.row.saturating_sub(1);
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.col = col.min(self.cols - 1);
+        self.wrap_pending = false;
+    }
+
+    /// Blank `cols` of the cursor's row.
+    fn erase_in_row(&mut self, row: usize, cols: Range<usize>) {
+        let line = &mut self.lines[row];
+        let end = cols.end.min(self.cols);
+        for cell in &mut line.cells[cols.start.min(end)..end] {
+            *cell = ' ';
+        }
+    }
+
+    fn erase_rows(&mut self, rows: Range<usize>) {
+        for row in rows {
+            self.lines[row] = Line::blank(self.cols);
+        }
+    }
+
+    fn set_alternate_screen(&mut self, on: bool) {
+        if on && self.saved_primary.is_none() {
+            let primary = std::mem::replace(&mut self.lines, vec![Line::blank(self.cols); self.rows]);
+            self.saved_primary = Some((primary, self.cursor));
+        } else if !on {
+            if let Some((primary, cursor)) = self.saved_primary.take() {
+                self.lines = primary;
+                self.cursor = cursor;
+            }
+        }
+        self.scroll_top = 0;
+        self.scroll_bottom = self.rows - 1;
+        self.wrap_pending = false;
+    }
+
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            7 => self.autowrap = on,
+            25 => self.cursor_visible = on,
+            47 | 1047 | 1049 => self.set_alternate_screen(on),
+            2004 => self.bracketed_paste = on,
+            _ => {}
+        }
+    }
+}
+
+/// Parameter `index`, with 0 or missing meaning `default`.
+fn param(params: &Params, index: usize, default: usize) -> usize {
+    params
+        .iter()
+        .nth(index)
+        .and_then(|p| p.first().copied())
+        .filter(|v| *v != 0)
+        .map(usize::from)
+        .unwrap_or(default)
+}
+
+impl Perform for Screen {
+    fn print(&mut self, c: char) {
+        let width = char_width(c);
+        if width == 0 {
+            return;
+        }
+        if self.wrap_pending && self.autowrap {
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+        if self.cursor.col + width > self.cols {
+            if self.autowrap && width <= self.cols {
+                self.cursor.col = 0;
+                self.linefeed();
+            } else {
+                self.cursor.col = self.cols.saturating_sub(width);
+            }
+        }
+
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let cells = &mut self.lines[row].cells;
+        cells[col] = c;
+        if width == 2 && col + 1 < self.cols {
+            cells[col + 1] = WIDE_SPACER;
+        }
+
+        if col + width >= self.cols {
+            self.cursor.col = self.cols - 1;
+            self.wrap_pending = true;
+        } else {
+            self.cursor.col = col + width;
+            self.wrap_pending = false;
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // BS
+            0x08 => {
+                self.cursor.col = self.cursor.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            // HT
+            0x09 => {
+                let next = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor.col = next.min(self.cols - 1);
+            }
+            // LF, VT, FF
+            0x0A..=0x0C => self.linefeed(),
+            // CR
+            0x0D => {
+                self.cursor.col = 0;
+                self.wrap_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let private = intermediates.first() == Some(&b'?');
+        let n = param(params, 0, 1);
+        let Cursor { row, col } = self.cursor;
+
+        match (private, action) {
+            (true, 'h') | (true, 'l') => {
+                for mode in params.iter().filter_map(|p| p.first().copied()) {
+                    self.set_private_mode(mode, action == 'h');
+                }
+            }
+            (true, _) => {}
+            (false, 'A') => {
+                let floor = if row >= self.scroll_top { self.scroll_top } else { 0 };
+                self.move_to(row.saturating_sub(n).max(floor), col);
+            }
+            (false, 'B') | (false, 'e') => {
+                let ceiling = if row <= self.scroll_bottom { self.scroll_bottom } else { self.rows - 1 };
+                self.move_to((row + n).min(ceiling), col);
+            }
+            (false, 'C') | (false, 'a') => self.move_to(row, col + n),
+            (false, 'D') => self.move_to(row, col.saturating_sub(n)),
+            (false, 'E') => self.move_to(row + n, 0),
+            (false, 'F') => self.move_to(row.saturating_sub(n), 0),
+            (false, 'G') | (false, '`') => self.move_to(row, n - 1),
+            (false, 'd') => self.move_to(n - 1, col),
+            (false, 'H') | (false, 'f') => self.move_to(n - 1, param(params, 1, 1) - 1),
+            (false, 'J') => match param(params, 0, 0) {
+                0 => {
+                    self.erase_in_row(row, col..self.cols);
+                    self.erase_rows(row + 1..self.rows);
+                }
+                1 => {
+                    self.erase_rows(0..row);
+                    self.erase_in_row(row, 0..col + 1);
+                }
+                2 => self.erase_rows(0..self.rows),
+                3 => self.scrollback.clear(),
+                _ => {}
+            },
+            (false, 'K') => match param(params, 0, 0) {
+                0 => self.erase_in_row(row, col..self.cols),
+                1 => self.erase_in_row(row, 0..col + 1),
+                2 => self.erase_in_row(row, 0..self.cols),
+                _ => {}
+            },
+            (false, '@') => {
+                let cells = &mut self.lines[row].cells;
+                for _ in 0..n.min(self.cols - col) {
+                    cells.pop();
+                    cells.insert(col, ' ');
+                }
+            }
+            (false, 'P') => {
+                let cells = &mut self.lines[row].cells;
+                for _ in 0..n.min(self.cols - col) {
+                    cells.remove(col);
+                    cells.push(' ');
+                }
+            }
+            (false, 'X') => self.erase_in_row(row, col..col + n),
+            (false, 'L') | (false, 'M') if (self.scroll_top..=self.scroll_bottom).contains(&row) => {
+                // Insert/delete lines shift the region below the cursor and
+                // never feed scrollback
+                let bottom = self.scroll_bottom;
+                for _ in 0..n.min(bottom - row + 1) {
+                    if action == 'L' {
+                        self.lines.remove(bottom);
+                        self.lines.insert(row, Line::blank(self.cols));
+                    } else {
+                        self.lines.remove(row);
+                        self.lines.insert(bottom, Line::blank(self.cols));
+                    }
+                }
+                self.move_to(row, 0);
+            }
+            (false, 'S') => self.scroll_up(n),
+            (false, 'T') => self.scroll_down(n),
+            (false, 'r') => {
+                let top = param(params, 0, 1) - 1;
+                let bottom = param(params, 1, self.rows).min(self.rows) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            (false, 's') => self.saved_cursor = Some(self.cursor),
+            (false, 'u') => {
+                let saved = self.saved_cursor.unwrap_or_default();
+                self.move_to(saved.row, saved.col);
+            }
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        if !intermediates.is_empty() {
+            // Charset designations and the like
+            return;
+        }
+        match byte {
+            b'7' => self.saved_cursor = Some(self.cursor),
+            b'8' => {
+                let saved = self.saved_cursor.unwrap_or_default();
+                self.move_to(saved.row, saved.col);
+            }
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor.col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => {
+                let scrollback = std::mem::take(&mut self.scrollback);
+                *self = Screen::new(self.rows as u16, self.cols as u16);
+                self.scrollback = scrollback;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reader-thread side: a parser feeding the session's screen.
+pub struct ScreenFeeder {
+    parser: vte::Parser,
+    screen: SharedScreen,
+}
+
+impl ScreenFeeder {
+    pub fn new(screen: SharedScreen) -> Self {
+        Self { parser: vte::Parser::new(), screen }
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        if let Ok(mut screen) = self.screen.lock() {
+            self.parser.advance(&mut *screen, bytes);
+        }
+    }
+}
+
+/// Cell width of `c`: 0 for combining marks and controls, 2 for East Asian
+/// wide characters and most emoji, 1 otherwise.
+fn char_width(c: char) -> usize {
+    let cp = c as u32;
+    match cp {
+        0..=0x1F | 0x7F..=0x9F => 0,
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
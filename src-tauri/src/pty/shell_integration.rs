@@ -0,0 +1,468 @@
+//! Shell integration: OSC 7 (cwd) and OSC 133 (prompt/command marks).
+//!
+//! Interactive bash, zsh and fish sessions get a small init snippet that makes
+//! the shell report its working directory and command boundaries:
+//!
+//! - `OSC 7 ; file://host/path` after every command
+//! - `OSC 133 ; A` prompt start, `B` prompt end, `C` command start,
+//!   `D ; <exit>` command end
+//! - `OSC 633 ; E ; <command line>` (the VS Code extension) when the shell
+//!   can tell us what is about to run
+//!
+//! The reader thread feeds all output through a [`ShellTracker`], which keeps
+//! the per-session [`ShellState`] current and emits `terminal-command-finished`.
+//! OSC 7 cwds are handed back to the reader, which reports them through
+//! `cwd_watcher`. Shells that emit these sequences themselves
+//! (or remote shells over ssh) are tracked the same way.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use crate::events::SharedSink;
+
+const MAX_HISTORY: usize = 500;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Milliseconds since the Unix epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+struct RunningCommand {
+    started: Instant,
+    started_at: u64,
+}
+
+/// What the shell has told us about itself, shared between the reader thread
+/// and commands.
+#[derive(Default)]
+pub struct ShellState {
+    /// Last cwd reported through OSC 7
+    pub cwd: Option<String>,
+    /// Set once any OSC 133 mark has been seen
+    pub integration_active: bool,
+    pub history: VecDeque<CommandRecord>,
+    running: Option<RunningCommand>,
+    command_line: Option<String>,
+}
+
+pub type SharedShellState = Arc<Mutex<ShellState>>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum ShellMark {
+    Cwd(String),
+    PromptStart,
+    PromptEnd,
+    CommandStart,
+    CommandEnd(Option<i32>),
+    CommandLine(String),
+}
+
+#[derive(Default)]
+struct OscCollector {
+    marks: Vec<ShellMark>,
+}
+
+impl vte::Perform for OscCollector {
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        let Some(kind) = params.first() else {
+            return;
+        };
+        match *kind {
+            b"7" if params.len() > 1 => {
+                let uri = params[1..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                if let Some(path) = path_from_file_uri(&uri) {
+                    self.marks.push(ShellMark::Cwd(path));
+                }
+            }
+            b"133" => match params.get(1).copied() {
+                Some(b"A") => self.marks.push(ShellMark::PromptStart),
+                Some(b"B") => self.marks.push(ShellMark::PromptEnd),
+                Some(b"C") => self.marks.push(ShellMark::CommandStart),
+                Some(b"D") => {
+                    let code = params
+                        .get(2)
+                        .and_then(|c| std::str::from_utf8(c).ok())
+                        .and_then(|c| c.parse().ok());
+                    self.marks.push(ShellMark::CommandEnd(code));
+                }
+                _ => {}
+            },
+            b"633" if params.get(1).copied() == Some(b"E") => {
+                let line = params[2..]
+                    .iter()
+                    .map(|p| String::from_utf8_lossy(p))
+                    .collect::<Vec<_>>()
+                    .join(";");
+                self.marks.push(ShellMark::CommandLine(unescape_command_line(&line)));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Per-session OSC parser living on the reader thread.
+pub struct ShellTracker {
+    parser: vte::Parser,
+    collector: OscCollector,
+    state: SharedShellState,
+    session_id: String,
+    sink: SharedSink,
+    /// OSC 7 and 133 sequences parsed so far
+    marks_seen: u64,
+}
+
+impl ShellTracker {
+    pub fn new(session_id: &str, state: SharedShellState, sink: SharedSink) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            collector: OscCollector::default(),
+            state,
+            session_id: session_id.to_string(),
+            sink,
+            marks_seen: 0,
+        }
+    }
+
+    /// How many shell integration sequences the output has carried.
+    pub fn marks_seen(&self) -> u64 {
+        self.marks_seen
+    }
+
+    /// Parse a chunk of PTY output. Sequences split across chunks are
+    /// completed on a later call. Returns the new cwd if the chunk carried an
+    /// OSC 7 that changed it.
+    pub fn feed(&mut self, bytes: &[u8]) -> Option<String> {
+        self.parser.advance(&mut self.collector, bytes);
+        if self.collector.marks.is_empty() {
+            return None;
+        }
+
+        let marks = std::mem::take(&mut self.collector.marks);
+        self.marks_seen += marks.len() as u64;
+        let mut events = Vec::new();
+        let mut new_cwd = None;
+        if let Ok(mut state) = self.state.lock() {
+            for mark in marks {
+                if let Some(cwd) = apply_mark(&mut state, mark, &self.session_id, &mut events) {
+                    new_cwd = Some(cwd);
+                }
+            }
+        }
+        for (event, payload) in events {
+            self.sink.emit_event(event, payload);
+        }
+        new_cwd
+    }
+}
+
+fn apply_mark(
+    state: &mut ShellState,
+    mark: ShellMark,
+    session_id: &str,
+    events: &mut Vec<(&'static str, serde_json::Value)>,
+) -> Option<String> {
+    match mark {
+        ShellMark::Cwd(cwd) => {
+            if state.cwd.as_deref() != Some(cwd.as_str()) {
+                state.cwd = Some(cwd.clone());
+                return Some(cwd);
+            }
+        }
+        ShellMark::PromptStart | ShellMark::PromptEnd => {
+            state.integration_active = true;
+        }
+        ShellMark::CommandLine(line) => {
+            state.command_line = Some(line);
+        }
+        ShellMark::CommandStart => {
+            state.integration_active = true;
+            state.running = Some(RunningCommand {
+                started: Instant::now(),
+                started_at: now_millis(),
+            });
+        }
+        ShellMark::CommandEnd(exit_code) => {
+            state.integration_active = true;
+            let command = state.command_line.take();
+            // A `D` without a preceding `C` is an empty prompt line
+            let running = state.running.take()?;
+            let record = CommandRecord {
+                command,
+                exit_code,
+                started_at: running.started_at,
+                duration_ms: running.started.elapsed().as_millis() as u64,
+                cwd: state.cwd.clone(),
+            };
+            events.push(("terminal-command-finished", serde_json::json!({
+                "session_id": session_id,
+                "command": record.command,
+                "exit_code": record.exit_code,
+                "started_at": record.started_at,
+                "duration_ms": record.duration_ms,
+                "cwd": record.cwd,
+            })));
+            if state.history.len() == MAX_HISTORY {
+                state.history.pop_front();
+            }
+            state.history.push_back(record);
+        }
+    }
+    None
+}
+
+/// `file://host/some%20path` -> `/some path`
+fn path_from_file_uri(uri: &str) -> Option<String> {
+    let rest = uri.strip_prefix("file://")?;
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = |b: u8| (b as char).to_digit(16);
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Undo the OSC 633 escaping: `\\` -> `\`, `\xNN` -> byte.
+fn unescape_command_line(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('\\') => {
+                chars.next();
+                out.push('\\');
+            }
+            Some('x') => {
+                chars.next();
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) => out.push(byte as char),
+                    Err(_) => {
+                        out.push_str("\\x");
+                        out.push_str(&hex);
+                    }
+                }
+            }
+            _ => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// How to start a shell with integration injected.
+pub struct ShellLaunch {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+/// Launch arguments that load the integration snippet for `shell`, or `None`
+/// if the shell isn't supported or the snippets couldn't be written.
+#[cfg(unix)]
+pub fn integrated_launch(shell: &str) -> Option<ShellLaunch> {
+    let dir = snippet_dir()?;
+    let name = std::path::Path::new(shell).file_name()?.to_str()?;
+    match name {
+        // --init-file replaces the login startup files; the snippet sources
+        // them itself so the shell still behaves like `bash -l`.
+        "bash" => Some(ShellLaunch {
+            args: vec![
+                "--init-file".to_string(),
+                dir.join("bash/init.bash").to_string_lossy().to_string(),
+            ],
+            env: Vec::new(),
+        }),
+        // zsh reads its startup files from $ZDOTDIR; ours chain to the user's
+        "zsh" => {
+            let mut env = vec![(
+                "ZDOTDIR".to_string(),
+                dir.join("zsh").to_string_lossy().to_string(),
+            )];
+            if let Ok(user_zdotdir) = std::env::var("ZDOTDIR") {
+                env.push(("TT_USER_ZDOTDIR".to_string(), user_zdotdir));
+            }
+            Some(ShellLaunch { args: vec!["-l".to_string()], env })
+        }
+        "fish" => Some(ShellLaunch {
+            args: vec![
+                "-l".to_string(),
+                "--init-command".to_string(),
+                format!("source '{}'", dir.join("fish/init.fish").to_string_lossy()),
+            ],
+            env: Vec::new(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn integrated_launch(_shell: &str) -> Option<ShellLaunch> {
+    None
+}
+
+/// The snippet directory, with the snippets checked and rewritten where
+/// they differ. It lives under the user cache dir rather than /tmp so it
+/// stays visible inside the bwrap sandbox, which mounts a private /tmp and
+/// binds this directory read-only. Sandboxed shells from an earlier run
+/// could still have changed it, so every launch checks it again.
+#[cfg(unix)]
+pub(crate) fn snippet_dir() -> Option<std::path::PathBuf> {
+    use std::path::PathBuf;
+    let cache = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .ok()?;
+    let dir = cache.join("tauri-terminal").join("shell-integration");
+    let files = [
+        ("bash/init.bash", BASH_INIT),
+        ("zsh/.zshenv", ZSH_ENV),
+        ("zsh/.zprofile", ZSH_PROFILE),
+        ("zsh/.zshrc", ZSH_RC),
+        ("zsh/.zlogin", ZSH_LOGIN),
+        ("fish/init.fish", FISH_INIT),
+    ];
+    let real_dir = |path: &std::path::Path| path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    for (name, content) in files {
+        let path = dir.join(name);
+        let parent = path.parent()?;
+        std::fs::create_dir_all(parent).ok()?;
+        // Every directory of ours must be real, or a write could land elsewhere
+        if !real_dir(dir.parent()?) || !real_dir(&dir) || !real_dir(parent) {
+            log::warn!(target: "pty", "Shell integration dir {} isn't a plain directory", parent.display());
+            return None;
+        }
+        let unchanged = path.symlink_metadata().is_ok_and(|m| m.is_file())
+            && std::fs::read_to_string(&path).ok().as_deref() == Some(content);
+        if !unchanged {
+            let _ = std::fs::remove_file(&path);
+            if let Err(e) = std::fs::write(&path, content) {
+                log::warn!(target: "pty", "Failed to write shell integration {}: {}", path.display(), e);
+                return None;
+            }
+        }
+    }
+    Some(dir)
+}
+
+const BASH_INIT: &str = r#"# Emulate `bash -l`: --init-file skips the usual login files
+if [ -r /etc/profile ]; then . /etc/profile; fi
+if [ -r ~/.bash_profile ]; then . ~/.bash_profile
+elif [ -r ~/.bash_login ]; then . ~/.bash_login
+elif [ -r ~/.profile ]; then . ~/.profile
+fi
+
+__tt_osc() { builtin printf '\033]%s\007' "$1"; }
+__tt_prompt() {
+    local code=$? cmd
+    cmd=$(HISTTIMEFORMAT= builtin history 1 2>/dev/null)
+    cmd="${cmd#"${cmd%%[![:space:]]*}"}"
+    cmd="${cmd#*[[:space:]][[:space:]]}"
+    cmd="${cmd//\\/\\\\}"
+    cmd="${cmd//;/\\x3b}"
+    __tt_osc "633;E;$cmd"
+    __tt_osc "133;D;$code"
+    __tt_osc "7;file://${HOSTNAME}${PWD}"
+    __tt_osc "133;A"
+}
+PROMPT_COMMAND="__tt_prompt${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+PS1="${PS1}\[\033]133;B\007\]"
+# PS0 (bash 4.4+) is printed after a command is read, before it runs
+PS0="${PS0}\e]133;C\a"
+"#;
+
+const ZSH_ENV: &str = r#"__tt_integration_dir="$ZDOTDIR"
+ZDOTDIR="${TT_USER_ZDOTDIR:-$HOME}"
+[[ -r "$ZDOTDIR/.zshenv" ]] && source "$ZDOTDIR/.zshenv"
+TT_USER_ZDOTDIR="$ZDOTDIR"
+ZDOTDIR="$__tt_integration_dir"
+"#;
+
+const ZSH_PROFILE: &str = r#"ZDOTDIR="$TT_USER_ZDOTDIR"
+[[ -r "$ZDOTDIR/.zprofile" ]] && source "$ZDOTDIR/.zprofile"
+ZDOTDIR="$__tt_integration_dir"
+"#;
+
+const ZSH_RC: &str = r#"ZDOTDIR="$TT_USER_ZDOTDIR"
+[[ -r "$ZDOTDIR/.zshrc" ]] && source "$ZDOTDIR/.zshrc"
+ZDOTDIR="$__tt_integration_dir"
+
+__tt_osc() { builtin printf '\e]%s\a' "$1" }
+__tt_running=""
+__tt_precmd() {
+    local code=$?
+    [[ -n "$__tt_running" ]] && __tt_osc "133;D;$code"
+    __tt_running=""
+    __tt_osc "7;file://${HOST}${PWD}"
+    __tt_osc "133;A"
+}
+__tt_preexec() {
+    __tt_running=1
+    local cmd="${1//\\/\\\\}"
+    cmd="${cmd//;/\\x3b}"
+    cmd="${cmd//$'\n'/\\x0a}"
+    __tt_osc "633;E;$cmd"
+    __tt_osc "133;C"
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook precmd __tt_precmd
+add-zsh-hook preexec __tt_preexec
+PS1="${PS1}%{$(builtin printf '\e]133;B\a')%}"
+"#;
+
+const ZSH_LOGIN: &str = r#"ZDOTDIR="$TT_USER_ZDOTDIR"
+[[ -r "$ZDOTDIR/.zlogin" ]] && source "$ZDOTDIR/.zlogin"
+unset __tt_integration_dir
+"#;
+
+const FISH_INIT: &str = r#"function __tt_osc
+    printf '\e]%s\a' $argv[1]
+end
+function __tt_prompt --on-event fish_prompt
+    __tt_osc "7;file://$hostname$PWD"
+    __tt_osc "133;A"
+end
+function __tt_preexec --on-event fish_preexec
+    set -l cmd (string replace -a '\\' '\\\\' -- $argv[1] | string replace -a ';' '\\x3b' | string join '\\x0a')
+    __tt_osc "633;E;$cmd"
+    __tt_osc "133;C"
+end
+function __tt_postexec --on-event fish_postexec
+    __tt_osc "133;D;$status"
+end
+"#;
@@ -0,0 +1,186 @@
+//! Input snippets: named sequences of input replayed into a session.
+//!
+//! Snippets live in `snippets.json` in the app config dir. Each step's input
+//! may use `${cwd}`, `${project_dir}` and `${branch}`, resolved against the
+//! target session when the snippet runs. Each is shell-quoted as one word, so
+//! a space or `$(...)` in a directory or branch name stays text. Other
+//! `${...}` text is left alone so shell variables like `${HOME}` still reach
+//! the shell.
+
+use std::path::Path;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::config_store;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::fs::{find_git_root, get_current_branch, terminal_cwd};
+use crate::pty::container::shell_join;
+use crate::pty::manager::write_session;
+use crate::state::{lock_session, AppState};
+
+const STORE_FILE: &str = "snippets.json";
+/// Longest pause a single step may ask for.
+const MAX_STEP_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnippetStep {
+    pub input: String,
+    /// Pause before writing this step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snippet {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub steps: Vec<SnippetStep>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SnippetRun {
+    pub name: String,
+    pub steps_run: usize,
+}
+
+/// All saved snippets, in the order they were first saved.
+pub fn load_snippets(config_dir: &Path) -> AppResult<Vec<Snippet>> {
+    config_store::load(config_dir, STORE_FILE, "snippets")
+}
+
+fn store_snippets(config_dir: &Path, snippets: &[Snippet]) -> AppResult<()> {
+    config_store::store(config_dir, STORE_FILE, "snippets", snippets)
+}
+
+/// Add a snippet, or replace the one with the same name.
+pub fn save_snippet(config_dir: &Path, snippet: Snippet) -> AppResult<Vec<Snippet>> {
+    if snippet.name.trim().is_empty() {
+        return Err(AppError::new(ErrorCode::InvalidInput, "Snippet name must not be empty"));
+    }
+    let mut snippets = load_snippets(config_dir)?;
+    match snippets.iter_mut().find(|s| s.name == snippet.name) {
+        Some(existing) => *existing = snippet,
+        None => snippets.push(snippet),
+    }
+    store_snippets(config_dir, &snippets)?;
+    Ok(snippets)
+}
+
+pub fn delete_snippet(config_dir: &Path, name: &str) -> AppResult<Vec<Snippet>> {
+    let mut snippets = load_snippets(config_dir)?;
+    let before = snippets.len();
+    snippets.retain(|s| s.name != name);
+    if snippets.len() == before {
+        return Err(snippet_not_found(name));
+    }
+    store_snippets(config_dir, &snippets)?;
+    Ok(snippets)
+}
+
+fn snippet_not_found(name: &str) -> AppError {
+    AppError::new(ErrorCode::NotFound, format!("Snippet not found: {}", name))
+}
+
+/// Placeholder values for one run, looked up only if a step uses them.
+struct Placeholders<'a> {
+    state: &'a AppState,
+    session_id: &'a str,
+    cwd: Option<String>,
+    project_dir: Option<String>,
+    branch: Option<String>,
+}
+
+impl Placeholders<'_> {
+    fn cwd(&mut self) -> AppResult<String> {
+        if self.cwd.is_none() {
+            self.cwd = Some(terminal_cwd(self.state, self.session_id)?);
+        }
+        Ok(self.cwd.clone().unwrap_or_default())
+    }
+
+    fn project_dir(&mut self) -> AppResult<String> {
+        if self.project_dir.is_none() {
+            let dir = lock_session(&self.state.session(self.session_id)?)?.project_dir.clone();
+            self.project_dir = Some(match dir {
+                Some(dir) => dir,
+                None => self.cwd()?,
+            });
+        }
+        Ok(self.project_dir.clone().unwrap_or_default())
+    }
+
+    fn branch(&mut self) -> AppResult<String> {
+        if self.branch.is_none() {
+            let cwd = self.cwd()?;
+            let branch = match find_git_root(Path::new(&cwd)) {
+                Some(root) => get_current_branch(root.to_string_lossy().to_string())?,
+                None => None,
+            };
+            self.branch = Some(branch.unwrap_or_default());
+        }
+        Ok(self.branch.clone().unwrap_or_default())
+    }
+
+    /// Replace the placeholders in `input`, each with its value quoted as a
+    /// single shell word. One pass, so a value that itself contains
+    /// `${branch}` isn't expanded again.
+    fn expand(&mut self, input: &str) -> AppResult<String> {
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = if rest.starts_with("${cwd}") {
+                Some(("${cwd}".len(), self.cwd()?))
+            } else if rest.starts_with("${project_dir}") {
+                Some(("${project_dir}".len(), self.project_dir()?))
+            } else if rest.starts_with("${branch}") {
+                Some(("${branch}".len(), self.branch()?))
+            } else {
+                None
+            };
+            match value {
+                Some((len, value)) => {
+                    out.push_str(&shell_join([value]));
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push_str("${");
+                    rest = &rest[2..];
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// Replay a saved snippet into a session, step by step. Blocks for the
+/// snippet's delays.
+pub fn run_snippet(config_dir: &Path, state: &AppState, session_id: &str, name: &str) -> AppResult<SnippetRun> {
+    let snippet = load_snippets(config_dir)?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| snippet_not_found(name))?;
+
+    let mut placeholders = Placeholders {
+        state,
+        session_id,
+        cwd: None,
+        project_dir: None,
+        branch: None,
+    };
+    log::info!(target: "pty", "Running snippet {} in {}", snippet.name, session_id);
+    for step in &snippet.steps {
+        if let Some(delay) = step.delay_ms {
+            std::thread::sleep(Duration::from_millis(delay).min(MAX_STEP_DELAY));
+        }
+        let input = placeholders.expand(&step.input)?;
+        write_session(state, session_id, &input)?;
+    }
+
+    Ok(SnippetRun {
+        name: snippet.name,
+        steps_run: snippet.steps.len(),
+    })
+}
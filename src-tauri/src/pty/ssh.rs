@@ -0,0 +1,391 @@
+//! SSH-backed sessions.
+//!
+//! A remote session is the local `ssh -tt` client running under a PTY, so
+//! `write_to_terminal`, `resize_terminal` and `close_terminal` treat it like
+//! any other session. Connection profiles live in `ssh_profiles.json` in the
+//! app config dir. Progress is emitted as `ssh-connection-state`. Output
+//! alone doesn't mean the link is up, since `ssh` prints its own errors and
+//! prompts to the same PTY: a connection counts as up once the remote shell
+//! reports through OSC 7 or 133, or once `ssh` has run for [`STABLE_AFTER`].
+//! When `ssh` exits with 255 (its status for connection errors) after a
+//! connection was up, the reader thread respawns it with backoff under the
+//! same session id, keeping screen and history. The remote cwd is only known
+//! once the remote shell reports it through OSC 7.
+
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::config_store;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::events::SharedSink;
+use crate::pty::cwd_watcher::CwdWatch;
+use crate::pty::manager::{launch, new_session, spawn_in_pty, Isolation, PtyProcess, SessionKind, SpawnedSession};
+use crate::state::{lock_session, AppState, SessionHandle};
+
+const STORE_FILE: &str = "ssh_profiles.json";
+/// `ssh` exits with this status when the connection fails or drops.
+const SSH_CONNECTION_ERROR: u32 = 255;
+/// Reconnect attempts in a row before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// An `ssh` that ran this long is connected, and a connection that stayed
+/// up this long starts the attempt count over.
+const STABLE_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SshProfile {
+    pub name: String,
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Private key passed as `ssh -i`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<String>,
+    /// Passed as `ssh -J`, e.g. `user@bastion:22`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump_host: Option<String>,
+    /// Reconnect when an established connection drops
+    #[serde(default = "default_reconnect")]
+    pub reconnect: bool,
+}
+
+fn default_reconnect() -> bool {
+    true
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connecting,
+    /// The remote shell reported through shell integration, or `ssh` kept
+    /// running for a while
+    Connected,
+    Reconnecting,
+    /// `ssh` exited normally, e.g. the remote shell ended
+    Disconnected,
+    /// The connection could not be made, or reconnecting gave up
+    Failed,
+}
+
+/// All saved profiles, in the order they were first saved.
+pub fn load_profiles(config_dir: &Path) -> AppResult<Vec<SshProfile>> {
+    config_store::load(config_dir, STORE_FILE, "SSH profiles")
+}
+
+fn store_profiles(config_dir: &Path, profiles: &[SshProfile]) -> AppResult<()> {
+    config_store::store(config_dir, STORE_FILE, "SSH profiles", profiles)
+}
+
+/// Add a profile, or replace the one with the same name.
+pub fn save_profile(config_dir: &Path, profile: SshProfile) -> AppResult<Vec<SshProfile>> {
+    validate(&profile)?;
+    let mut profiles = load_profiles(config_dir)?;
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    store_profiles(config_dir, &profiles)?;
+    Ok(profiles)
+}
+
+pub fn delete_profile(config_dir: &Path, name: &str) -> AppResult<Vec<SshProfile>> {
+    let mut profiles = load_profiles(config_dir)?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+    if profiles.len() == before {
+        return Err(profile_not_found(name));
+    }
+    store_profiles(config_dir, &profiles)?;
+    Ok(profiles)
+}
+
+fn profile_not_found(name: &str) -> AppError {
+    AppError::new(ErrorCode::NotFound, format!("SSH profile not found: {}", name))
+}
+
+/// Reject values `ssh` would parse as options or that can't name a host.
+fn validate(profile: &SshProfile) -> AppResult<()> {
+    let invalid = |message: &str| Err(AppError::new(ErrorCode::InvalidInput, message.to_string()));
+    let bad_word = |s: &str| s.is_empty() || s.starts_with('-') || s.chars().any(char::is_whitespace);
+    if profile.name.trim().is_empty() {
+        return invalid("SSH profile name must not be empty");
+    }
+    if bad_word(&profile.host) || profile.host.contains('@') {
+        return invalid("SSH host must be a bare host name or address");
+    }
+    if profile.user.as_deref().is_some_and(|u| bad_word(u) || u.contains('@')) {
+        return invalid("Invalid SSH user");
+    }
+    if profile.jump_host.as_deref().is_some_and(bad_word) {
+        return invalid("Invalid SSH jump host");
+    }
+    if profile.identity_file.as_deref().is_some_and(str::is_empty) {
+        return invalid("SSH identity file must not be empty");
+    }
+    Ok(())
+}
+
+/// The `ssh` invocation for a profile. `-tt` forces a remote PTY, and
+/// keepalives make a dead link show up as exit 255 instead of a hang.
+pub fn ssh_command(profile: &SshProfile) -> CommandBuilder {
+    let mut cmd = CommandBuilder::new("ssh");
+    cmd.arg("-tt");
+    if let Some(port) = profile.port {
+        cmd.args(["-p", &port.to_string()]);
+    }
+    if let Some(ref identity) = profile.identity_file {
+        cmd.args(["-i", identity]);
+    }
+    if let Some(ref jump) = profile.jump_host {
+        cmd.args(["-J", jump]);
+    }
+    cmd.args(["-o", "ServerAliveInterval=15", "-o", "ServerAliveCountMax=3"]);
+    let destination = match profile.user {
+        Some(ref user) => format!("{}@{}", user, profile.host),
+        None => profile.host.clone(),
+    };
+    // Nothing after the destination may be read as an option
+    cmd.args(["--", &destination]);
+    cmd.env("TERM", "xterm-256color");
+    if let Ok(home) = std::env::var("HOME") {
+        cmd.cwd(home);
+    }
+    cmd
+}
+
+fn emit_state(
+    sink: &SharedSink,
+    session_id: &str,
+    profile: &str,
+    state: ConnectionState,
+    attempt: u32,
+    extra: serde_json::Value,
+) {
+    let mut payload = serde_json::json!({
+        "session_id": session_id,
+        "profile": profile,
+        "state": state,
+        "attempt": attempt,
+    });
+    if let (Some(payload), serde_json::Value::Object(extra)) = (payload.as_object_mut(), extra) {
+        payload.extend(extra);
+    }
+    sink.emit_event("ssh-connection-state", payload);
+}
+
+fn emit_notice(sink: &SharedSink, session_id: &str, text: &str) {
+    sink.emit_event("terminal-output", serde_json::json!({
+        "session_id": session_id,
+        "data": format!("\r\n[{}]\r\n", text),
+    }));
+}
+
+/// Start a remote session for `profile`. Output arrives as `terminal-output`
+/// like a local terminal's.
+pub fn start_ssh_session(
+    state: &AppState,
+    profile: &SshProfile,
+    rows: u16,
+    cols: u16,
+    sink: SharedSink,
+) -> AppResult<SpawnedSession> {
+    validate(profile)?;
+    let session_id = Uuid::new_v4().to_string();
+    log::info!(target: "pty", "Connecting session {} to SSH profile {}", session_id, profile.name);
+    emit_state(&sink, &session_id, &profile.name, ConnectionState::Connecting, 0, serde_json::json!({}));
+
+    let process = spawn_in_pty(ssh_command(profile), rows, cols).inspect_err(|e| {
+        emit_state(&sink, &session_id, &profile.name, ConnectionState::Failed, 0, serde_json::json!({
+            "error": e.message,
+        }));
+    })?;
+    let mut session = new_session(process, rows, cols, Isolation::None, None);
+    session.remote = Some(profile.clone());
+    session.cwd_watch = Arc::new(Mutex::new(CwdWatch::remote()));
+    launch(state, session_id, session, SessionKind::Remote, sink)
+}
+
+/// Start a remote session for the saved profile called `name`.
+pub fn start_profile_session(
+    config_dir: &Path,
+    state: &AppState,
+    name: &str,
+    rows: u16,
+    cols: u16,
+    sink: SharedSink,
+) -> AppResult<SpawnedSession> {
+    let profile = load_profiles(config_dir)?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| profile_not_found(name))?;
+    start_ssh_session(state, &profile, rows, cols, sink)
+}
+
+/// Connection bookkeeping owned by a remote session's reader thread.
+pub(crate) struct Link {
+    profile: String,
+    /// Reconnects tried since the last stable connection
+    attempts: u32,
+    /// When the current `ssh` was started
+    spawned_at: Instant,
+    /// When the current connection was seen to be up
+    connected_at: Option<Instant>,
+}
+
+impl Link {
+    pub fn for_session(state: &AppState, session_id: &str) -> Self {
+        let profile = state
+            .session(session_id)
+            .ok()
+            .and_then(|handle| lock_session(&handle).ok()?.remote.as_ref().map(|p| p.name.clone()))
+            .unwrap_or_default();
+        Self {
+            profile,
+            attempts: 0,
+            spawned_at: Instant::now(),
+            connected_at: None,
+        }
+    }
+
+    /// Called for every chunk of output, with whether it carried shell
+    /// integration sequences. Those, or `ssh` still running after
+    /// [`STABLE_AFTER`], mark the connection up; `ssh`'s own errors and
+    /// prompts don't.
+    pub fn on_output(&mut self, session_id: &str, sink: &SharedSink, shell_reported: bool) {
+        if self.connected_at.is_none() && (shell_reported || self.spawned_at.elapsed() >= STABLE_AFTER) {
+            self.connected_at = Some(Instant::now());
+            emit_state(sink, session_id, &self.profile, ConnectionState::Connected, self.attempts, serde_json::json!({}));
+        }
+    }
+
+    /// The PTY closed. Reaps `ssh` and, if the connection dropped and the
+    /// profile allows it, respawns it into the same session and returns the
+    /// new reader. Blocks for the backoff.
+    pub fn on_drop(
+        &mut self,
+        state: &AppState,
+        session_id: &str,
+        shutdown: &AtomicBool,
+        sink: &SharedSink,
+    ) -> Option<Box<dyn Read + Send>> {
+        // Closed by the user; close_terminal has already cleaned up
+        if shutdown.load(Ordering::SeqCst) {
+            return None;
+        }
+        let handle = state.session(session_id).ok()?;
+        let (exit_code, profile, rows, cols) = {
+            let mut session = lock_session(&handle).ok()?;
+            let exit_code = session.child.wait().ok().map(|status| status.exit_code());
+            let (rows, cols) = session.screen.lock().map(|s| s.size()).unwrap_or((24, 80));
+            (exit_code, session.remote.clone()?, rows, cols)
+        };
+
+        let was_up = self.connected_at.take();
+        if was_up.is_some_and(|since| since.elapsed() >= STABLE_AFTER) {
+            self.attempts = 0;
+        }
+        // A first connection that never came up is a failure, not a drop
+        let dropped = exit_code == Some(SSH_CONNECTION_ERROR) && (was_up.is_some() || self.attempts > 0);
+        if !dropped || !profile.reconnect {
+            let (final_state, notice) = if exit_code == Some(SSH_CONNECTION_ERROR) {
+                (ConnectionState::Failed, "Connection failed")
+            } else {
+                (ConnectionState::Disconnected, "Process exited")
+            };
+            log::info!(target: "pty", "Remote session {} ended: {:?} (exit {:?})", session_id, final_state, exit_code);
+            emit_state(sink, session_id, &self.profile, final_state, self.attempts, serde_json::json!({
+                "exit_code": exit_code,
+            }));
+            emit_notice(sink, session_id, notice);
+            return None;
+        }
+
+        loop {
+            self.attempts += 1;
+            if self.attempts > MAX_ATTEMPTS {
+                log::warn!(target: "pty", "Giving up on remote session {} after {} attempts", session_id, MAX_ATTEMPTS);
+                emit_state(sink, session_id, &self.profile, ConnectionState::Failed, MAX_ATTEMPTS, serde_json::json!({
+                    "exit_code": exit_code,
+                }));
+                emit_notice(sink, session_id, "Connection lost");
+                return None;
+            }
+            let delay = backoff(self.attempts);
+            emit_state(sink, session_id, &self.profile, ConnectionState::Reconnecting, self.attempts, serde_json::json!({
+                "delay_ms": delay.as_millis() as u64,
+            }));
+            emit_notice(sink, session_id, &format!(
+                "Connection lost, reconnecting in {}s ({}/{})",
+                delay.as_secs(),
+                self.attempts,
+                MAX_ATTEMPTS,
+            ));
+            if !sleep_unless_closed(delay, shutdown) {
+                return None;
+            }
+
+            let process = match spawn_in_pty(ssh_command(&profile), rows, cols) {
+                Ok(process) => process,
+                Err(e) => {
+                    log::warn!(target: "pty", "Reconnect of {} failed: {}", session_id, e);
+                    continue;
+                }
+            };
+            return self.swap_in(&handle, process, session_id, shutdown, sink);
+        }
+    }
+
+    /// Put a freshly spawned `ssh` into the session and hand back its reader.
+    fn swap_in(
+        &mut self,
+        handle: &SessionHandle,
+        process: PtyProcess,
+        session_id: &str,
+        shutdown: &AtomicBool,
+        sink: &SharedSink,
+    ) -> Option<Box<dyn Read + Send>> {
+        let PtyProcess { master, mut child, writer } = process;
+        let reader = master.try_clone_reader().ok();
+        let mut session = lock_session(handle).ok()?;
+        // Closed while we were spawning
+        if shutdown.load(Ordering::SeqCst) || reader.is_none() {
+            let _ = child.kill();
+            return None;
+        }
+        session.master = master;
+        session.child = child;
+        session.writer = writer;
+        drop(session);
+        self.spawned_at = Instant::now();
+        emit_state(sink, session_id, &self.profile, ConnectionState::Connecting, self.attempts, serde_json::json!({}));
+        reader
+    }
+}
+
+/// 1s, 2s, 4s, ... capped at [`MAX_BACKOFF`].
+fn backoff(attempt: u32) -> Duration {
+    FIRST_BACKOFF
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF)
+}
+
+/// Sleep for `delay`, waking early if the session is closed. Returns whether
+/// the session is still open.
+fn sleep_unless_closed(delay: Duration, shutdown: &AtomicBool) -> bool {
+    let deadline = Instant::now() + delay;
+    while Instant::now() < deadline {
+        if shutdown.load(Ordering::SeqCst) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100).min(deadline.saturating_duration_since(Instant::now())));
+    }
+    !shutdown.load(Ordering::SeqCst)
+}
@@ -1,27 +1,108 @@
-use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
+use dashmap::DashMap;
 use portable_pty::{Child, MasterPty};
 use std::io::Write;
+use crate::error::{AppError, AppResult};
+use crate::fs::{DirectoryWatches, FileIndexes, Replacements, Searches};
+use crate::git_cache::GitStatsCache;
+use crate::pty::container::ContainerRef;
+use crate::pty::cwd_watcher::SharedCwdWatch;
+use crate::pty::expect::SharedExpectBuffer;
+use crate::pty::groups::TerminalGroup;
+use crate::pty::manager::Isolation;
+use crate::pty::metrics::SharedMetrics;
+use crate::pty::screen::SharedScreen;
+use crate::pty::shell_integration::SharedShellState;
+use crate::pty::ssh::SshProfile;
 
 pub struct PtySession {
     pub master: Box<dyn MasterPty + Send>,
     pub child: Box<dyn Child + Send>,
     pub writer: Box<dyn Write + Send>,
     pub shutdown: Arc<AtomicBool>,
-    pub sandboxed: bool,
+    pub isolation: Isolation,
+    /// Project directory the session was spawned for
+    pub project_dir: Option<String>,
+    /// SSH profile for remote sessions; the process is the local `ssh`
+    pub remote: Option<SshProfile>,
+    /// Container started for this session, removed when it closes
+    pub container: Option<ContainerRef>,
+    /// Cwd and command history reported by the shell (OSC 7/133)
+    pub shell: SharedShellState,
+    /// Last reported cwd and cwd subscription
+    pub cwd_watch: SharedCwdWatch,
+    /// Text grid and scrollback, fed by the reader thread
+    pub screen: SharedScreen,
+    /// Escape-stripped output for `wait_for_output`
+    pub expect: SharedExpectBuffer,
+    /// I/O counters and latency histograms
+    pub metrics: SharedMetrics,
 }
 
-pub struct AppStateData {
-    pub pty_sessions: HashMap<String, PtySession>,
-    pub git_cache: crate::git_cache::GitStatsCache,
+impl PtySession {
+    /// Whether the shell runs isolated from the host (bwrap or a container).
+    pub fn sandboxed(&self) -> bool {
+        self.isolation != Isolation::None
+    }
 }
 
-pub type AppState = Arc<Mutex<AppStateData>>;
+/// A session behind its own lock, so I/O on one terminal never waits on
+/// another terminal or on unrelated commands.
+pub type SessionHandle = Arc<Mutex<PtySession>>;
+
+/// Backend state shared by all commands.
+///
+/// Each subsystem synchronizes independently: the session map is a sharded
+/// concurrent map whose entries carry their own lock, and the git cache
+/// guards its entries and watchers internally. Directory watches, file indexes,
+/// running searches and replace previews sit behind locks of their own,
+/// taken only to add or drop one. Nothing here
+/// takes a global lock, so a keystroke is never queued behind `get_git_stats`.
+#[derive(Clone)]
+pub struct AppState {
+    pub pty_sessions: Arc<DashMap<String, SessionHandle>>,
+    pub git_cache: Arc<GitStatsCache>,
+    pub terminal_groups: Arc<DashMap<String, TerminalGroup>>,
+    pub dir_watches: Arc<DirectoryWatches>,
+    pub file_indexes: Arc<FileIndexes>,
+    pub searches: Arc<Searches>,
+    pub replacements: Arc<Replacements>,
+}
+
+impl AppState {
+    /// Look up a session. The map shard is only held while cloning the handle.
+    pub fn session(&self, session_id: &str) -> AppResult<SessionHandle> {
+        self.pty_sessions
+            .get(session_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| AppError::session_not_found(session_id))
+    }
+
+    pub fn insert_session(&self, session_id: String, session: PtySession) -> SessionHandle {
+        let handle = Arc::new(Mutex::new(session));
+        self.pty_sessions.insert(session_id, handle.clone());
+        handle
+    }
+
+    pub fn remove_session(&self, session_id: &str) -> Option<SessionHandle> {
+        self.pty_sessions.remove(session_id).map(|(_, handle)| handle)
+    }
+}
+
+/// Lock a single session.
+pub fn lock_session(handle: &SessionHandle) -> AppResult<std::sync::MutexGuard<'_, PtySession>> {
+    handle.lock().map_err(AppError::lock)
+}
 
 pub fn create_state() -> AppState {
-    Arc::new(Mutex::new(AppStateData {
-        pty_sessions: HashMap::new(),
-        git_cache: crate::git_cache::GitStatsCache::new(),
-    }))
+    AppState {
+        pty_sessions: Arc::new(DashMap::new()),
+        git_cache: Arc::new(GitStatsCache::new()),
+        terminal_groups: Arc::new(DashMap::new()),
+        dir_watches: Arc::new(DirectoryWatches::new()),
+        file_indexes: Arc::new(FileIndexes::new()),
+        searches: Arc::new(Searches::new()),
+        replacements: Arc::new(Replacements::new()),
+    }
 }
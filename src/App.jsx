@@ -99,7 +99,7 @@ function App() {
     try {
       const relativePath = getRelativePath(absolutePath, currentPath);
       const escapedPath = escapeShellPath(relativePath);
-      await invoke('write_to_terminal', { sessionId: terminalSessionId, data: `${escapedPath} ` });
+      await invoke('paste_to_terminal', { sessionId: terminalSessionId, text: `${escapedPath} ` });
       terminalRef.current?.focus?.();
     } catch (error) {
       console.error('Failed to send file to terminal:', absolutePath, error);
@@ -63,6 +63,8 @@ export const TreeNode = memo(function TreeNode({
   const isSelected = selectedFiles && selectedFiles.has(node.path);
   const depth = node.depth || 0;
   const isDotfile = node.name?.startsWith('.');
+  // Ignored entries only arrive when the tree was loaded with showIgnored
+  const isDimmed = isDotfile || node.ignored;
 
   // Git stats
   const stats = gitStats?.get(node.path);
@@ -74,7 +76,7 @@ export const TreeNode = memo(function TreeNode({
 
   return (
     <>
-      <SidebarMenuItem className="my-0 p-0 w-full" style={isDotfile ? { opacity: 0.45 } : undefined}>
+      <SidebarMenuItem className="my-0 p-0 w-full" style={isDimmed ? { opacity: 0.45 } : undefined}>
         {node.is_dir ? (
           <FolderNode
             node={node}
@@ -1,48 +1,47 @@
-import { useEffect, useState, useCallback, useRef } from 'react';
+import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 export function useCwdMonitor(sessionId, enabled) {
   const [currentCwd, setCurrentCwd] = useState(null);
-  const lastCwdRef = useRef(null);
-  const checkIntervalRef = useRef(null);
-
-  const checkCwd = useCallback(async () => {
-    if (!sessionId || !enabled) return;
-
-    try {
-      const cwd = await invoke('get_terminal_cwd', { sessionId });
-
-      if (cwd !== lastCwdRef.current) {
-        console.log('CWD changed:', lastCwdRef.current, '->', cwd);
-        lastCwdRef.current = cwd;
-        setCurrentCwd(cwd);
-      }
-    } catch (error) {
-      // Terminal might not be ready yet, ignore
-    }
-  }, [sessionId, enabled]);
 
   useEffect(() => {
-    if (!enabled || !sessionId) {
-      if (checkIntervalRef.current) {
-        clearInterval(checkIntervalRef.current);
-        checkIntervalRef.current = null;
+    if (!enabled || !sessionId) return;
+
+    let cancelled = false;
+    let unlisten;
+
+    const subscribe = async () => {
+      // The backend pushes changes (OSC 7 or its own sampling), so there is
+      // nothing to poll here
+      unlisten = await listen('terminal-cwd-changed', (event) => {
+        if (event.payload.session_id === sessionId) {
+          setCurrentCwd(event.payload.cwd);
+        }
+      });
+      if (cancelled) {
+        unlisten();
+        return;
       }
-      return;
-    }
 
-    // Initial check
-    checkCwd();
+      try {
+        const cwd = await invoke('subscribe_terminal_cwd', { sessionId });
+        if (!cancelled) setCurrentCwd(cwd);
+      } catch (error) {
+        // Terminal might not be ready yet, ignore
+      }
+    };
 
-    // Poll every 500ms while sidebar is open
-    checkIntervalRef.current = setInterval(checkCwd, 500);
+    subscribe();
 
     return () => {
-      if (checkIntervalRef.current) {
-        clearInterval(checkIntervalRef.current);
-      }
+      cancelled = true;
+      if (unlisten) unlisten();
+      invoke('unsubscribe_terminal_cwd', { sessionId }).catch(() => {
+        // Session already closed
+      });
     };
-  }, [sessionId, enabled, checkCwd]);
+  }, [sessionId, enabled]);
 
   return currentCwd;
 }
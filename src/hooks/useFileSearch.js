@@ -1,68 +1,43 @@
 import { useRef, useCallback } from 'react';
-import * as JsSearch from 'js-search';
+import { invoke } from '@tauri-apps/api/core';
+
+const SEARCH_LIMIT = 200;
 
 export function useFileSearch() {
-  const searchRef = useRef(null);
+  const rootRef = useRef(null);
 
   /**
-   * Initialize search index with all files
-   * @param {Array} files - Flat array of all file entries
+   * Set the directory to search. The backend indexes it on the first search
+   * and keeps the index current itself.
+   * @param {string} root - Directory shown in the tree
    */
-  const initializeSearch = useCallback((files) => {
-    if (!files || files.length === 0) {
-      searchRef.current = null;
-      return;
-    }
-
-    // Create new search instance
-    const search = new JsSearch.Search('path');
-
-    // Use AllSubstringsIndexStrategy for fuzzy matching
-    // This allows matching anywhere in the string
-    search.indexStrategy = new JsSearch.AllSubstringsIndexStrategy();
-
-    // Use TfIdfSearchIndex for better relevance ranking
-    search.searchIndex = new JsSearch.TfIdfSearchIndex('path');
-
-    // Case-insensitive search
-    search.sanitizer = new JsSearch.LowerCaseSanitizer();
-
-    // Index both name and full path
-    search.addIndex('name');
-    search.addIndex('path');
-
-    // Add all documents
-    search.addDocuments(files);
-
-    searchRef.current = search;
-    console.log('Search index built for', files.length, 'files');
+  const initializeSearch = useCallback((root) => {
+    rootRef.current = root || null;
   }, []);
 
   /**
-   * Search for files matching query
+   * Fuzzy-find files and folders matching query
    * @param {string} query - Search query
-   * @returns {Array} - Matching file entries
+   * @returns {Promise<Array|null>} - Best matches first: {path, relative_path, is_dir, score, recent, highlights}
    */
-  const search = useCallback((query) => {
-    if (!searchRef.current || !query || query.trim() === '') {
+  const search = useCallback(async (query) => {
+    if (!rootRef.current || !query || query.trim() === '') {
       return null;
     }
 
-    const trimmedQuery = query.trim();
-
-    // Perform search
-    const results = searchRef.current.search(trimmedQuery);
-
-    console.log('Search query:', trimmedQuery, '- Results:', results.length);
-
-    return results;
+    const result = await invoke('find_files', {
+      root: rootRef.current,
+      query: query.trim(),
+      limit: SEARCH_LIMIT,
+    });
+    return result.matches;
   }, []);
 
   /**
-   * Clear search index
+   * Forget the search root
    */
   const clearSearch = useCallback(() => {
-    searchRef.current = null;
+    rootRef.current = null;
   }, []);
 
   return {
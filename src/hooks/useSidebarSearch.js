@@ -8,14 +8,24 @@ export function useSidebarSearch() {
 
   // Debounced search — caller is responsible for expanding results
   useEffect(() => {
-    const timer = setTimeout(() => {
+    let cancelled = false;
+    const timer = setTimeout(async () => {
       if (!searchQuery || searchQuery.trim() === '') {
         setSearchResults(null);
         return;
       }
-      setSearchResults(search(searchQuery));
+      try {
+        const results = await search(searchQuery);
+        if (!cancelled) setSearchResults(results);
+      } catch (err) {
+        console.error('File search failed:', err);
+        if (!cancelled) setSearchResults([]);
+      }
     }, 200);
-    return () => clearTimeout(timer);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
   }, [searchQuery, search]);
 
   const handleSearchChange = useCallback((query) => {
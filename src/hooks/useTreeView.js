@@ -29,7 +29,7 @@ export function useTreeView({ terminalSessionId, setCurrentPath, initializeSearc
       setCurrentPath(cwd);
       setAllFiles(allEntries);
       setTreeLoading(false);
-      initializeSearch(allEntries);
+      initializeSearch(cwd);
     } catch (err) {
       console.error('Failed to load tree data:', err);
       const errorMessage = err?.message || err?.toString() || 'Unknown error';
@@ -51,7 +51,7 @@ export function useTreeView({ terminalSessionId, setCurrentPath, initializeSearc
   const handleIncrementalUpdate = useCallback((changes, rootPath) => {
     setTreeData(prev => incrementallyUpdateTree(prev, changes, rootPath));
 
-    // Update allFiles and search index with new untracked files
+    // Update allFiles with new untracked files
     if (changes.newUntracked.length > 0) {
       setAllFiles(prev => {
         const existingPaths = new Set(prev.map(f => f.path));
@@ -66,15 +66,10 @@ export function useTreeView({ terminalSessionId, setCurrentPath, initializeSearc
               parent_path: path.substring(0, lastSep),
             };
           });
-        if (newEntries.length > 0) {
-          const updated = [...prev, ...newEntries];
-          initializeSearch(updated);
-          return updated;
-        }
-        return prev;
+        return newEntries.length > 0 ? [...prev, ...newEntries] : prev;
       });
     }
-  }, [initializeSearch]);
+  }, []);
 
   const handleGitChanges = useCallback((changes) => {
     if (changes.newUntracked.length > 0 && !changes.newDeleted.length && !changes.noLongerUntracked.length) {